@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+
+use derive_builder::Builder;
+use serde::{Serialize, Deserialize};
+
+use crate::api::chat_completion::{Model, Tool, FunctionInfo};
+
+// 助手(assistant)对象，以及创建、修改助手的请求体
+
+
+///
+/// 创建助手API-请求体
+///
+#[derive(Debug,Clone,Serialize,Builder)]
+#[builder(pattern = "mutable")]
+pub struct CreateAssistantRequest{
+    /// 要使用的模型ID
+    #[builder(default)]
+    pub model: Model,
+
+    /// 助手名称，最长 256 个字符
+    #[builder(default,setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// 助手描述，最长 512 个字符
+    #[builder(default,setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// 助手使用的系统指令，最长 256000 个字符
+    #[builder(default,setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+
+    /// 助手可以使用的工具列表，最多 128 个
+    #[builder(default,setter(into))]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<AssistantTool>,
+
+    /// 工具使用的资源，例如代码解释器的文件、文件检索的向量库
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_resources: Option<ToolResources>,
+
+    /// 附加在对象上的键值对，最多 16 个
+    #[builder(default,setter(into))]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+
+    /// 采样温度，取值 0~2
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    /// 核采样的累积概率阈值，取值 0~1
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+}
+
+///
+/// 修改助手API-请求体，只有设置了的字段会被修改
+///
+#[derive(Debug,Clone,Default,Serialize,Builder)]
+#[builder(pattern = "mutable", default)]
+pub struct ModifyAssistantRequest{
+    /// 要使用的模型ID
+    #[builder(setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<Model>,
+
+    /// 助手名称
+    #[builder(setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// 助手描述
+    #[builder(setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// 系统指令
+    #[builder(setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+
+    /// 工具列表，设置后会整体替换原有的工具
+    #[builder(setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<AssistantTool>>,
+
+    /// 工具使用的资源
+    #[builder(setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_resources: Option<ToolResources>,
+
+    /// 键值对，设置后会整体替换原有的 metadata
+    #[builder(setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<HashMap<String, String>>,
+
+    /// 采样温度，取值 0~2
+    #[builder(setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    /// 核采样的累积概率阈值，取值 0~1
+    #[builder(setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+}
+
+
+///
+/// 助手对象
+///
+#[derive(Debug,Clone,Deserialize)]
+pub struct Assistant{
+    /// 助手ID
+    pub id: String,
+    /// 对象类型，始终为 assistant
+    pub object: String,
+    /// 创建时的 Unix 时间戳（以秒为单位）
+    pub created_at: u64,
+    /// 助手名称
+    #[serde(default)]
+    pub name: Option<String>,
+    /// 助手描述
+    #[serde(default)]
+    pub description: Option<String>,
+    /// 使用的模型ID
+    pub model: Model,
+    /// 系统指令
+    #[serde(default)]
+    pub instructions: Option<String>,
+    /// 工具列表
+    #[serde(default)]
+    pub tools: Vec<AssistantTool>,
+    /// 工具使用的资源
+    #[serde(default)]
+    pub tool_resources: Option<ToolResources>,
+    /// 键值对
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// 采样温度
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// 核采样的累积概率阈值
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    /// 未建模的其他字段，原样保留
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+
+/// 助手可以使用的工具
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AssistantTool{
+    /// 代码解释器
+    CodeInterpreter,
+    /// 文件检索
+    FileSearch{
+        /// 文件检索的选项
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file_search: Option<FileSearchOptions>,
+    },
+    /// 函数调用，与聊天接口的 `Tool` 使用相同的函数定义
+    Function{
+        /// 函数信息
+        function: FunctionInfo,
+    },
+    /// 未知的工具类型
+    #[serde(other)]
+    Unknown,
+}
+
+impl AssistantTool {
+    /// 文件检索工具，使用默认选项
+    pub fn file_search() -> Self {
+        AssistantTool::FileSearch { file_search: None }
+    }
+}
+
+// 聊天接口的函数工具可以直接作为助手的工具
+impl From<Tool> for AssistantTool {
+    fn from(tool: Tool) -> Self {
+        AssistantTool::Function { function: tool.into() }
+    }
+}
+
+/// 文件检索的选项
+#[derive(Debug,Clone,Default,Serialize,Deserialize)]
+pub struct FileSearchOptions{
+    /// 最多返回的检索结果数量，取值 1~50
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_num_results: Option<u32>,
+}
+
+/// 工具使用的资源
+#[derive(Debug,Clone,Default,Serialize,Deserialize)]
+pub struct ToolResources{
+    /// 代码解释器可以访问的文件
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_interpreter: Option<CodeInterpreterResources>,
+    /// 文件检索使用的向量库
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_search: Option<FileSearchResources>,
+}
+
+/// 代码解释器资源
+#[derive(Debug,Clone,Default,Serialize,Deserialize)]
+pub struct CodeInterpreterResources{
+    /// 文件ID列表，最多 20 个
+    #[serde(default)]
+    pub file_ids: Vec<String>,
+}
+
+/// 文件检索资源
+#[derive(Debug,Clone,Default,Serialize,Deserialize)]
+pub struct FileSearchResources{
+    /// 向量库ID列表，最多 1 个
+    #[serde(default)]
+    pub vector_store_ids: Vec<String>,
+}
+
+impl ToolResources {
+    /// 使用指定向量库进行文件检索
+    pub fn file_search(vector_store_ids: Vec<String>) -> Self {
+        Self { file_search: Some(FileSearchResources { vector_store_ids }), ..Default::default() }
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use anyhow::{Result, Ok};
+    use serde_json::json;
+    use super::*;
+
+    #[test]
+    fn create_assistant_request_should_serialize() -> Result<()>{
+        let tool = Tool::function("lookup_order", "Look up an order by id", json!({
+            "type": "object",
+            "properties": {"order_id": {"type": "string"}},
+            "required": ["order_id"]
+        }));
+        let req = CreateAssistantRequestBuilder::default()
+            .model(Model::Gpt4Turbo)
+            .name("Support")
+            .instructions("You are a support agent.")
+            .tools(vec![tool.into(), AssistantTool::CodeInterpreter, AssistantTool::file_search()])
+            .tool_resources(ToolResources::file_search(vec!["vs_123".into()]))
+            .build()?;
+        let value = serde_json::to_value(&req)?;
+        assert_eq!(value["model"], "gpt-4-1106-preview");
+        assert_eq!(value["tools"][0]["type"], "function");
+        assert_eq!(value["tools"][0]["function"]["name"], "lookup_order");
+        assert_eq!(value["tools"][1], json!({"type": "code_interpreter"}));
+        assert_eq!(value["tools"][2], json!({"type": "file_search"}));
+        assert_eq!(value["tool_resources"]["file_search"]["vector_store_ids"][0], "vs_123");
+
+        let req = ModifyAssistantRequestBuilder::default().name("Renamed").build()?;
+        assert_eq!(serde_json::to_value(&req)?, json!({"name": "Renamed"}));
+        Ok(())
+    }
+
+    #[test]
+    fn assistant_should_deserialize() -> Result<()>{
+        let assistant: Assistant = serde_json::from_value(json!({
+            "id": "asst_abc123",
+            "object": "assistant",
+            "created_at": 1698984975,
+            "name": "Math Tutor",
+            "description": null,
+            "model": "gpt-4o",
+            "instructions": "You are a personal math tutor.",
+            "tools": [{"type": "code_interpreter"}, {"type": "browser"}],
+            "metadata": {},
+            "top_p": 1.0,
+            "temperature": 1.0,
+            "response_format": "auto"
+        }))?;
+        assert_eq!(assistant.model, Model::Other("gpt-4o".into()));
+        assert!(matches!(assistant.tools[0], AssistantTool::CodeInterpreter));
+        assert!(matches!(assistant.tools[1], AssistantTool::Unknown));
+        assert_eq!(assistant.extra["response_format"], "auto");
+        Ok(())
+    }
+}
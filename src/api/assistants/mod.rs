@@ -0,0 +1,218 @@
+//! Assistants v2 接口: 助手、线程、消息、运行以及运行步骤
+//! 所有请求都需要携带 `OpenAI-Beta: assistants=v2` 请求头
+//!
+
+use std::{future::Future, time::Duration};
+
+use anyhow::Result;
+use tokio::time::Instant;
+
+use crate::OpenaiSdk;
+use crate::api::common::{ApiRequest, ListQuery, ListResponse, DeleteResponse, PollOptions};
+use crate::api::message::ToolCall;
+
+mod assistant;
+mod thread;
+mod run;
+pub use assistant::*;
+pub use thread::*;
+pub use run::*;
+
+/// Assistants v2 的 beta 请求头
+const ASSISTANTS_BETA: &str = "assistants=v2";
+
+/// 轮询运行状态的默认间隔
+pub const DEFAULT_RUN_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+
+// 助手相关的 api 请求
+impl OpenaiSdk {
+
+    ///
+    /// 创建助手
+    ///
+    pub async fn create_assistant(&self, req: CreateAssistantRequest) -> Result<Assistant>{
+        self.send_json(ApiRequest::post("assistants", &req)?.beta(ASSISTANTS_BETA)).await
+    }
+
+    ///
+    /// 获取助手
+    ///
+    pub async fn retrieve_assistant(&self, assistant_id: &str) -> Result<Assistant>{
+        self.send_json(ApiRequest::get(format!("assistants/{assistant_id}")).beta(ASSISTANTS_BETA)).await
+    }
+
+    ///
+    /// 修改助手
+    ///
+    pub async fn update_assistant(&self, assistant_id: &str, req: ModifyAssistantRequest) -> Result<Assistant>{
+        self.send_json(ApiRequest::post(format!("assistants/{assistant_id}"), &req)?.beta(ASSISTANTS_BETA)).await
+    }
+
+    ///
+    /// 分页获取助手列表
+    ///
+    pub async fn list_assistants(&self, query: ListQuery) -> Result<ListResponse<Assistant>>{
+        self.send_json(ApiRequest::get("assistants").query(&query)?.beta(ASSISTANTS_BETA)).await
+    }
+
+    ///
+    /// 删除助手
+    ///
+    pub async fn delete_assistant(&self, assistant_id: &str) -> Result<DeleteResponse>{
+        self.send_json(ApiRequest::delete(format!("assistants/{assistant_id}")).beta(ASSISTANTS_BETA)).await
+    }
+
+    ///
+    /// 创建线程
+    ///
+    pub async fn create_thread(&self, req: CreateThreadRequest) -> Result<Thread>{
+        self.send_json(ApiRequest::post("threads", &req)?.beta(ASSISTANTS_BETA)).await
+    }
+
+    ///
+    /// 获取线程
+    ///
+    pub async fn retrieve_thread(&self, thread_id: &str) -> Result<Thread>{
+        self.send_json(ApiRequest::get(format!("threads/{thread_id}")).beta(ASSISTANTS_BETA)).await
+    }
+
+    ///
+    /// 删除线程
+    ///
+    pub async fn delete_thread(&self, thread_id: &str) -> Result<DeleteResponse>{
+        self.send_json(ApiRequest::delete(format!("threads/{thread_id}")).beta(ASSISTANTS_BETA)).await
+    }
+
+    ///
+    /// 在线程中追加一条消息
+    ///
+    pub async fn create_message(&self, thread_id: &str, req: CreateMessageRequest) -> Result<ThreadMessage>{
+        self.send_json(ApiRequest::post(format!("threads/{thread_id}/messages"), &req)?.beta(ASSISTANTS_BETA)).await
+    }
+
+    ///
+    /// 分页获取线程中的消息
+    ///
+    pub async fn list_messages(&self, thread_id: &str, query: ListQuery) -> Result<ListResponse<ThreadMessage>>{
+        self.send_json(ApiRequest::get(format!("threads/{thread_id}/messages")).query(&query)?.beta(ASSISTANTS_BETA)).await
+    }
+
+    ///
+    /// 在线程上创建运行
+    ///
+    pub async fn create_run(&self, thread_id: &str, req: CreateRunRequest) -> Result<Run>{
+        self.send_json(ApiRequest::post(format!("threads/{thread_id}/runs"), &req)?.beta(ASSISTANTS_BETA)).await
+    }
+
+    ///
+    /// 获取运行
+    ///
+    pub async fn retrieve_run(&self, thread_id: &str, run_id: &str) -> Result<Run>{
+        self.send_json(ApiRequest::get(format!("threads/{thread_id}/runs/{run_id}")).beta(ASSISTANTS_BETA)).await
+    }
+
+    ///
+    /// 取消运行
+    ///
+    pub async fn cancel_run(&self, thread_id: &str, run_id: &str) -> Result<Run>{
+        let req = ApiRequest::post(format!("threads/{thread_id}/runs/{run_id}/cancel"), &serde_json::json!({}))?;
+        self.send_json(req.beta(ASSISTANTS_BETA)).await
+    }
+
+    ///
+    /// 提交工具输出，运行会从 requires_action 状态继续
+    ///
+    pub async fn submit_tool_outputs(&self, thread_id: &str, run_id: &str, req: SubmitToolOutputsRequest) -> Result<Run>{
+        let req = ApiRequest::post(format!("threads/{thread_id}/runs/{run_id}/submit_tool_outputs"), &req)?;
+        self.send_json(req.beta(ASSISTANTS_BETA)).await
+    }
+
+    ///
+    /// 分页获取运行步骤
+    ///
+    pub async fn list_run_steps(&self, thread_id: &str, run_id: &str, query: ListQuery) -> Result<ListResponse<RunStep>>{
+        let req = ApiRequest::get(format!("threads/{thread_id}/runs/{run_id}/steps")).query(&query)?;
+        self.send_json(req.beta(ASSISTANTS_BETA)).await
+    }
+
+    ///
+    /// 按照指定间隔轮询运行状态，直到运行不再处于排队、运行中或取消中的状态;
+    /// 传入 `Duration` 时最长等待 10 分钟，超时返回 `PollTimeout` 错误
+    ///
+    pub async fn poll_run(&self, thread_id: &str, run_id: &str, options: impl Into<PollOptions>) -> Result<Run>{
+        self.poll_run_since(thread_id, run_id, &options.into(), Instant::now()).await
+    }
+
+    /// 从 `started` 开始计算等待时间，轮询运行状态
+    async fn poll_run_since(&self, thread_id: &str, run_id: &str, options: &PollOptions, started: Instant) -> Result<Run>{
+        loop {
+            let run = self.retrieve_run(thread_id, run_id).await?;
+            if !run.status.is_pending() {
+                return Ok(run);
+            }
+            options.wait(started, run_id, &run.status).await?;
+        }
+    }
+
+    ///
+    /// 创建运行并等待其结束，运行需要调用函数时使用 `handler` 执行工具调用并提交输出;
+    /// 返回最终状态的运行(completed、failed、expired 等)，整个过程超过 `poll.max_wait` 时返回 `PollTimeout` 错误
+    ///
+    pub async fn run_with_tools<F, Fut>(&self, thread_id: &str, req: CreateRunRequest, poll: impl Into<PollOptions>, mut handler: F) -> Result<Run>
+    where
+        F: FnMut(ToolCall) -> Fut,
+        Fut: Future<Output = Result<String>>,
+    {
+        let (poll, started) = (poll.into(), Instant::now());
+        let run = self.create_run(thread_id, req).await?;
+        let mut run = self.poll_run_since(thread_id, &run.id, &poll, started).await?;
+        while run.status == RunStatus::RequiresAction {
+            let mut tool_outputs = Vec::new();
+            for call in run.required_tool_calls().to_vec() {
+                let tool_call_id = call.id.clone();
+                let output = handler(call).await?;
+                tool_outputs.push(ToolOutput { tool_call_id, output });
+            }
+            let submitted = self.submit_tool_outputs(thread_id, &run.id, SubmitToolOutputsRequest { tool_outputs }).await?;
+            run = self.poll_run_since(thread_id, &submitted.id, &poll, started).await?;
+        }
+        Ok(run)
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use reqwest::Method;
+    use serde_json::json;
+    use crate::error::PollTimeout;
+    use crate::transport::{MockTransport, MockResponse};
+    use super::*;
+
+    #[tokio::test]
+    async fn assistants_requests_should_use_beta_header() -> Result<()>{
+        let sdk = OpenaiSdk::new("sk-test".into());
+        let query = ListQuery { limit: Some(5), ..Default::default() };
+        let req = ApiRequest::get("threads/thread_1/messages").query(&query)?.beta(ASSISTANTS_BETA);
+        let req = sdk.prepare_request(req).await?;
+        assert_eq!(req.url().as_str(), "https://api.openai.com/v1/threads/thread_1/messages?limit=5");
+        assert_eq!(req.headers()["OpenAI-Beta"], "assistants=v2");
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn poll_run_should_time_out() -> Result<()>{
+        let mock = MockTransport::new().on(Method::GET, "threads/thread_1/runs/run_1", MockResponse::json(json!({
+            "id": "run_1", "object": "thread.run", "created_at": 1, "assistant_id": "asst_1",
+            "thread_id": "thread_1", "status": "queued", "model": "gpt-4o"
+        })));
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock.clone());
+        let options = PollOptions::new(Duration::from_secs(1)).max_wait(Duration::from_secs(5));
+        let err = sdk.poll_run("thread_1", "run_1", options).await.unwrap_err();
+        let timeout = err.downcast_ref::<PollTimeout>().unwrap();
+        assert_eq!((timeout.id.as_str(), timeout.status.as_str()), ("run_1", "queued"));
+        assert_eq!(mock.requests().len(), 6);
+        Ok(())
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+
+use derive_builder::Builder;
+use serde::{Serialize, Deserialize};
+
+use crate::api::chat_completion::{Model, ToolChoice, ChatCompleteUsage};
+use crate::api::message::ToolCall;
+use super::assistant::AssistantTool;
+use super::thread::CreateMessageRequest;
+
+// 运行(run)以及运行步骤(run step)
+// 在线程上使用助手发起一次运行，模型需要调用函数时运行状态变为 requires_action，
+// 提交工具输出后继续运行，直到完成。
+
+
+///
+/// 创建运行API-请求体
+///
+#[derive(Debug,Clone,Serialize,Builder)]
+#[builder(pattern = "mutable")]
+pub struct CreateRunRequest{
+    /// 要使用的助手ID
+    #[builder(setter(into))]
+    pub assistant_id: String,
+
+    /// 覆盖助手的模型
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<Model>,
+
+    /// 覆盖助手的系统指令
+    #[builder(default,setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+
+    /// 追加在助手系统指令之后的额外指令
+    #[builder(default,setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub additional_instructions: Option<String>,
+
+    /// 运行前追加到线程中的消息
+    #[builder(default,setter(into))]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub additional_messages: Vec<CreateMessageRequest>,
+
+    /// 覆盖助手的工具列表
+    #[builder(default,setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<AssistantTool>>,
+
+    /// 附加在对象上的键值对，最多 16 个
+    #[builder(default,setter(into))]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+
+    /// 采样温度，取值 0~2
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    /// 核采样的累积概率阈值，取值 0~1
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+
+    /// 整个运行过程中最多使用的提示令牌数
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_prompt_tokens: Option<usize>,
+
+    /// 整个运行过程中最多生成的令牌数
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_completion_tokens: Option<usize>,
+
+    /// 控制模型调用哪个工具
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+
+    /// 是否允许并行调用多个函数
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+}
+
+impl CreateRunRequest {
+    /// 使用指定助手创建运行，其余参数使用助手的配置
+    pub fn new(assistant_id: impl Into<String>) -> Self {
+        CreateRunRequestBuilder::default()
+        .assistant_id(assistant_id)
+        .build()
+        .unwrap()
+    }
+}
+
+
+///
+/// 提交工具输出API-请求体
+///
+#[derive(Debug,Clone,Default,Serialize)]
+pub struct SubmitToolOutputsRequest{
+    /// 工具输出列表，需要包含 required_action 中的所有工具调用
+    pub tool_outputs: Vec<ToolOutput>,
+}
+
+/// 单个工具调用的输出
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct ToolOutput{
+    /// 对应的工具调用ID
+    pub tool_call_id: String,
+    /// 工具的输出内容
+    pub output: String,
+}
+
+impl ToolOutput {
+    /// 为指定的工具调用创建输出
+    pub fn new(call: &ToolCall, output: impl Into<String>) -> Self {
+        Self { tool_call_id: call.id.clone(), output: output.into() }
+    }
+}
+
+
+///
+/// 运行对象
+///
+#[derive(Debug,Clone,Deserialize)]
+pub struct Run{
+    /// 运行ID
+    pub id: String,
+    /// 对象类型，始终为 thread.run
+    pub object: String,
+    /// 创建时的 Unix 时间戳（以秒为单位）
+    pub created_at: u64,
+    /// 所属的线程ID
+    pub thread_id: String,
+    /// 使用的助手ID
+    pub assistant_id: String,
+    /// 运行状态
+    pub status: RunStatus,
+    /// 继续运行需要执行的操作，仅当状态为 requires_action 时存在
+    #[serde(default)]
+    pub required_action: Option<RequiredAction>,
+    /// 运行失败时的错误信息
+    #[serde(default)]
+    pub last_error: Option<RunError>,
+    /// 使用的模型ID
+    pub model: Model,
+    /// 使用的系统指令
+    #[serde(default)]
+    pub instructions: Option<String>,
+    /// 使用的工具列表
+    #[serde(default)]
+    pub tools: Vec<AssistantTool>,
+    /// 运行的使用统计，运行结束前为 null
+    #[serde(default)]
+    pub usage: Option<ChatCompleteUsage>,
+    /// 键值对
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// 未建模的其他字段，原样保留
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl Run {
+    /// 需要提交输出的工具调用列表
+    pub fn required_tool_calls(&self) -> &[ToolCall] {
+        self.required_action
+            .as_ref()
+            .map(|a| a.submit_tool_outputs.tool_calls.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+/// 运行状态，运行步骤也使用该状态
+#[derive(Debug,Clone,PartialEq, Eq,Serialize,Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus{
+    /// 排队中
+    Queued,
+    /// 运行中
+    InProgress,
+    /// 需要提交工具输出
+    RequiresAction,
+    /// 取消中
+    Cancelling,
+    /// 已取消
+    Cancelled,
+    /// 运行失败
+    Failed,
+    /// 运行完成
+    Completed,
+    /// 因为令牌数限制等原因未完成
+    Incomplete,
+    /// 超时
+    Expired,
+    /// 未知的状态，保留原始值
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl RunStatus {
+    /// 运行是否还在进行中，需要继续轮询
+    pub fn is_pending(&self) -> bool {
+        matches!(self, RunStatus::Queued | RunStatus::InProgress | RunStatus::Cancelling)
+    }
+}
+
+/// 继续运行需要执行的操作
+#[derive(Debug,Clone,Deserialize)]
+pub struct RequiredAction{
+    /// 操作类型，目前始终为 submit_tool_outputs
+    pub r#type: String,
+    /// 需要提交输出的工具调用
+    pub submit_tool_outputs: RequiredToolCalls,
+}
+
+/// 需要提交输出的工具调用
+#[derive(Debug,Clone,Deserialize)]
+pub struct RequiredToolCalls{
+    /// 工具调用列表
+    pub tool_calls: Vec<ToolCall>,
+}
+
+/// 运行的错误信息
+#[derive(Debug,Clone,Deserialize)]
+pub struct RunError{
+    /// 错误码，例如 server_error、rate_limit_exceeded
+    pub code: String,
+    /// 错误信息
+    pub message: String,
+}
+
+
+///
+/// 运行步骤对象
+///
+#[derive(Debug,Clone,Deserialize)]
+pub struct RunStep{
+    /// 运行步骤ID
+    pub id: String,
+    /// 对象类型，始终为 thread.run.step
+    pub object: String,
+    /// 创建时的 Unix 时间戳（以秒为单位）
+    pub created_at: u64,
+    /// 所属的运行ID
+    pub run_id: String,
+    /// 使用的助手ID
+    pub assistant_id: String,
+    /// 所属的线程ID
+    pub thread_id: String,
+    /// 步骤状态
+    pub status: RunStatus,
+    /// 步骤详情
+    pub step_details: RunStepDetails,
+    /// 步骤失败时的错误信息
+    #[serde(default)]
+    pub last_error: Option<RunError>,
+    /// 步骤的使用统计
+    #[serde(default)]
+    pub usage: Option<ChatCompleteUsage>,
+    /// 未建模的其他字段，原样保留
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// 运行步骤详情
+#[derive(Debug,Clone,Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RunStepDetails{
+    /// 创建了一条消息
+    MessageCreation{
+        message_creation: MessageCreation,
+    },
+    /// 调用了工具
+    ToolCalls{
+        tool_calls: Vec<RunStepToolCall>,
+    },
+}
+
+/// 运行步骤中创建的消息
+#[derive(Debug,Clone,Deserialize)]
+pub struct MessageCreation{
+    /// 消息ID
+    pub message_id: String,
+}
+
+/// 运行步骤中的工具调用
+#[derive(Debug,Clone,Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RunStepToolCall{
+    /// 函数调用
+    Function{
+        id: String,
+        function: RunStepFunction,
+    },
+    /// 代码解释器调用，包含输入的代码以及输出
+    CodeInterpreter{
+        id: String,
+        code_interpreter: serde_json::Value,
+    },
+    /// 文件检索调用
+    FileSearch{
+        id: String,
+        #[serde(default)]
+        file_search: serde_json::Value,
+    },
+}
+
+/// 运行步骤中的函数调用
+#[derive(Debug,Clone,Deserialize)]
+pub struct RunStepFunction{
+    /// 函数名称
+    pub name: String,
+    /// 函数参数(Json格式)
+    pub arguments: String,
+    /// 提交的函数输出，尚未提交时为 null
+    #[serde(default)]
+    pub output: Option<String>,
+}
+
+
+#[cfg(test)]
+mod tests{
+    use anyhow::{Result, Ok};
+    use serde_json::json;
+    use super::*;
+
+    #[test]
+    fn create_run_request_should_serialize() -> Result<()>{
+        let req = CreateRunRequestBuilder::default()
+            .assistant_id("asst_abc123")
+            .additional_instructions("Answer in French.")
+            .tool_choice(ToolChoice::Auto)
+            .build()?;
+        assert_eq!(
+            serde_json::to_value(&req)?,
+            json!({
+                "assistant_id": "asst_abc123",
+                "additional_instructions": "Answer in French.",
+                "tool_choice": "auto"
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn run_requiring_action_should_deserialize() -> Result<()>{
+        let run: Run = serde_json::from_value(json!({
+            "id": "run_abc123",
+            "object": "thread.run",
+            "created_at": 1699075072,
+            "assistant_id": "asst_abc123",
+            "thread_id": "thread_abc123",
+            "status": "requires_action",
+            "required_action": {
+                "type": "submit_tool_outputs",
+                "submit_tool_outputs": {
+                    "tool_calls": [{
+                        "id": "call_abc123",
+                        "type": "function",
+                        "function": {"name": "lookup_order", "arguments": "{\"order_id\":\"A-1\"}"}
+                    }]
+                }
+            },
+            "last_error": null,
+            "model": "gpt-4-1106-preview",
+            "instructions": null,
+            "tools": [],
+            "usage": null,
+            "metadata": {}
+        }))?;
+        assert_eq!(run.status, RunStatus::RequiresAction);
+        assert!(!run.status.is_pending());
+        let calls = run.required_tool_calls();
+        assert_eq!(calls[0].function.name, "lookup_order");
+        let output = ToolOutput::new(&calls[0], "shipped");
+        assert_eq!(output.tool_call_id, "call_abc123");
+        Ok(())
+    }
+
+    #[test]
+    fn run_step_should_deserialize() -> Result<()>{
+        let step: RunStep = serde_json::from_value(json!({
+            "id": "step_abc123",
+            "object": "thread.run.step",
+            "created_at": 1699063291,
+            "run_id": "run_abc123",
+            "assistant_id": "asst_abc123",
+            "thread_id": "thread_abc123",
+            "type": "tool_calls",
+            "status": "completed",
+            "step_details": {
+                "type": "tool_calls",
+                "tool_calls": [
+                    {"id": "call_1", "type": "function", "function": {"name": "lookup_order", "arguments": "{}", "output": "shipped"}},
+                    {"id": "call_2", "type": "code_interpreter", "code_interpreter": {"input": "1+1", "outputs": []}}
+                ]
+            },
+            "usage": {"prompt_tokens": 123, "completion_tokens": 456, "total_tokens": 579}
+        }))?;
+        let RunStepDetails::ToolCalls { tool_calls } = &step.step_details else {
+            panic!("expected tool calls");
+        };
+        assert!(matches!(&tool_calls[0], RunStepToolCall::Function { function, .. } if function.output.as_deref() == Some("shipped")));
+        assert!(matches!(&tool_calls[1], RunStepToolCall::CodeInterpreter { .. }));
+        assert_eq!(step.extra["type"], "tool_calls");
+        Ok(())
+    }
+}
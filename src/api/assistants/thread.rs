@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+
+use derive_builder::Builder;
+use serde::{Serialize, Deserialize};
+
+use super::assistant::{AssistantTool, ToolResources};
+
+// 会话线程(thread)以及线程中的消息
+// 线程在服务端保存完整的对话历史，每次运行只需要追加新的消息
+
+
+///
+/// 创建线程API-请求体
+///
+#[derive(Debug,Clone,Default,Serialize,Builder)]
+#[builder(pattern = "mutable", default)]
+pub struct CreateThreadRequest{
+    /// 线程的初始消息列表
+    #[builder(setter(into))]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<CreateMessageRequest>,
+
+    /// 工具使用的资源
+    #[builder(setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_resources: Option<ToolResources>,
+
+    /// 附加在对象上的键值对，最多 16 个
+    #[builder(setter(into))]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+}
+
+///
+/// 线程对象
+///
+#[derive(Debug,Clone,Deserialize)]
+pub struct Thread{
+    /// 线程ID
+    pub id: String,
+    /// 对象类型，始终为 thread
+    pub object: String,
+    /// 创建时的 Unix 时间戳（以秒为单位）
+    pub created_at: u64,
+    /// 工具使用的资源
+    #[serde(default)]
+    pub tool_resources: Option<ToolResources>,
+    /// 键值对
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// 未建模的其他字段，原样保留
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+
+///
+/// 创建线程消息API-请求体
+///
+#[derive(Debug,Clone,Serialize,Builder)]
+#[builder(pattern = "mutable")]
+pub struct CreateMessageRequest{
+    /// 消息的角色，user 或 assistant
+    #[builder(default)]
+    pub role: MessageRole,
+
+    /// 消息的文本内容
+    #[builder(setter(into))]
+    pub content: String,
+
+    /// 消息附带的文件，以及文件要添加到的工具
+    #[builder(default,setter(into))]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<MessageAttachment>,
+
+    /// 附加在对象上的键值对，最多 16 个
+    #[builder(default,setter(into))]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+}
+
+impl CreateMessageRequest {
+    /// 创建用户消息
+    pub fn user(content: impl Into<String>) -> Self {
+        Self { role: MessageRole::User, content: content.into(), attachments: Vec::new(), metadata: HashMap::new() }
+    }
+
+    /// 创建助手消息，可以用来在线程中插入历史回复
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self { role: MessageRole::Assistant, ..Self::user(content) }
+    }
+}
+
+/// 线程消息的角色
+#[derive(Debug,Clone,Copy,Default,PartialEq, Eq,Serialize,Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageRole{
+    #[default]
+    User,
+    Assistant,
+}
+
+/// 消息附件
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct MessageAttachment{
+    /// 文件ID
+    pub file_id: String,
+    /// 文件要添加到的工具
+    #[serde(default)]
+    pub tools: Vec<AssistantTool>,
+}
+
+
+///
+/// 线程消息对象
+///
+#[derive(Debug,Clone,Deserialize)]
+pub struct ThreadMessage{
+    /// 消息ID
+    pub id: String,
+    /// 对象类型，始终为 thread.message
+    pub object: String,
+    /// 创建时的 Unix 时间戳（以秒为单位）
+    pub created_at: u64,
+    /// 所属的线程ID
+    pub thread_id: String,
+    /// 消息状态: in_progress、incomplete 或 completed
+    #[serde(default)]
+    pub status: Option<String>,
+    /// 消息的角色
+    pub role: MessageRole,
+    /// 消息内容
+    #[serde(default)]
+    pub content: Vec<MessageContent>,
+    /// 生成该消息的助手ID
+    #[serde(default)]
+    pub assistant_id: Option<String>,
+    /// 生成该消息的运行ID
+    #[serde(default)]
+    pub run_id: Option<String>,
+    /// 消息附件
+    #[serde(default)]
+    pub attachments: Option<Vec<MessageAttachment>>,
+    /// 键值对
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// 未建模的其他字段，原样保留
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl ThreadMessage {
+    /// 拼接消息中所有文本内容
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|c| match c {
+                MessageContent::Text { text } => Some(text.value.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// 消息内容
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MessageContent{
+    /// 文本内容
+    Text{
+        text: MessageText,
+    },
+    /// 上传的图片文件
+    ImageFile{
+        image_file: serde_json::Value,
+    },
+    /// 图片地址
+    ImageUrl{
+        image_url: serde_json::Value,
+    },
+    /// 模型拒绝回答的内容
+    Refusal{
+        refusal: String,
+    },
+    /// 未知的内容类型
+    #[serde(other)]
+    Unknown,
+}
+
+/// 文本内容
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct MessageText{
+    /// 文本
+    pub value: String,
+    /// 文本中的引用标注，例如文件检索的引用
+    #[serde(default)]
+    pub annotations: Vec<serde_json::Value>,
+}
+
+
+#[cfg(test)]
+mod tests{
+    use anyhow::{Result, Ok};
+    use serde_json::json;
+    use super::*;
+
+    #[test]
+    fn create_thread_request_should_serialize() -> Result<()>{
+        let req = CreateThreadRequestBuilder::default()
+            .messages(vec![CreateMessageRequest::user("Where is my order?")])
+            .build()?;
+        assert_eq!(
+            serde_json::to_value(&req)?,
+            json!({"messages": [{"role": "user", "content": "Where is my order?"}]})
+        );
+        assert_eq!(serde_json::to_value(CreateThreadRequest::default())?, json!({}));
+        Ok(())
+    }
+
+    #[test]
+    fn thread_message_should_deserialize() -> Result<()>{
+        let message: ThreadMessage = serde_json::from_value(json!({
+            "id": "msg_abc123",
+            "object": "thread.message",
+            "created_at": 1699017614,
+            "assistant_id": "asst_abc123",
+            "thread_id": "thread_abc123",
+            "run_id": "run_abc123",
+            "role": "assistant",
+            "content": [
+                {"type": "text", "text": {"value": "Your order shipped.", "annotations": []}},
+                {"type": "image_file", "image_file": {"file_id": "file-1", "detail": "auto"}}
+            ],
+            "attachments": [],
+            "metadata": {}
+        }))?;
+        assert_eq!(message.role, MessageRole::Assistant);
+        assert_eq!(message.text(), "Your order shipped.");
+        assert_eq!(message.run_id.as_deref(), Some("run_abc123"));
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{Result, anyhow};
+use bytes::Bytes;
+use derive_builder::Builder;
+use serde::Deserialize;
+use reqwest::{Client, RequestBuilder, multipart::{Form, Part}};
+
+use crate::{IntoRequest, OpenaiSdk, TypedRequest};
+
+// 语音转文字API(/v1/audio/transcriptions): 将音频文件转写为文本
+
+
+///
+/// 语音转文字API-请求体，以 multipart/form-data 格式发送
+///
+#[derive(Debug,Clone,Builder)]
+pub struct TranscriptionRequest{
+    /// 音频文件名，服务端根据扩展名判断音频格式(mp3、mp4、mpeg、mpga、m4a、wav、webm 等)
+    #[builder(setter(into))]
+    filename: String,
+
+    /// 音频文件内容
+    #[builder(setter(into))]
+    content: Bytes,
+
+    /// 要使用的模型ID，默认为 whisper-1
+    #[builder(default = "\"whisper-1\".to_string()",setter(into))]
+    model: String,
+
+    /// 音频的语言(ISO-639-1，例如 zh、en)，指定后可以提高准确率和速度
+    #[builder(default,setter(into,strip_option))]
+    language: Option<String>,
+
+    /// 引导转写风格或者延续上一段音频的提示文本，需要与音频的语言一致
+    #[builder(default,setter(into,strip_option))]
+    prompt: Option<String>,
+
+    /// 采样温度，取值 0~1
+    #[builder(default,setter(strip_option))]
+    temperature: Option<f32>,
+}
+
+impl TranscriptionRequest {
+    pub fn new(filename: impl Into<String>, content: impl Into<Bytes>) -> Self {
+        TranscriptionRequestBuilder::default()
+        .filename(filename)
+        .content(content)
+        .build()
+        .unwrap()
+    }
+
+    /// 读取本地音频文件，使用文件名作为上传的文件名
+    pub async fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let filename = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| anyhow!("invalid file name: {}", path.display()))?;
+        let content = tokio::fs::read(path).await?;
+        Ok(Self::new(filename, content))
+    }
+}
+
+// TranscriptionRequest 实现 IntoRequest 特征，返回对应的网络请求构建器;
+impl IntoRequest for TranscriptionRequest{
+    /// 构建post请求，指定目标url
+    /// 音频文件与各参数作为 multipart 表单发送，响应格式固定为 json
+    fn into_request(self, client: Client) -> RequestBuilder {
+        let part = Part::stream(self.content).file_name(self.filename);
+        let mut form = Form::new()
+            .text("model", self.model)
+            .text("response_format", "json")
+            .part("file", part);
+        if let Some(language) = self.language {
+            form = form.text("language", language);
+        }
+        if let Some(prompt) = self.prompt {
+            form = form.text("prompt", prompt);
+        }
+        if let Some(temperature) = self.temperature {
+            form = form.text("temperature", temperature.to_string());
+        }
+        client.post("https://api.openai.com/v1/audio/transcriptions").multipart(form)
+    }
+
+    /// multipart 表单中的模型，Azure 模式下据此确定部署
+    fn model_id(&self) -> Option<String> {
+        Some(self.model.clone())
+    }
+}
+
+impl TypedRequest for TranscriptionRequest{
+    type Response = Transcription;
+}
+
+
+///
+/// 语音转文字API-响应体
+///
+#[derive(Debug,Clone,Deserialize)]
+pub struct Transcription{
+    /// 转写的文本
+    pub text: String,
+
+    /// 未建模的其他响应字段(例如 usage)，原样保留
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+
+// 语音相关的 api 请求
+impl OpenaiSdk {
+
+    ///
+    /// 语音转文字
+    ///
+    pub async fn create_transcription(&self, req: TranscriptionRequest) -> Result<Transcription>{
+        self.send_json(req).await
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use reqwest::Method;
+    use serde_json::json;
+    use crate::transport::{MockTransport, MockResponse};
+    use super::*;
+
+    #[tokio::test]
+    async fn transcription_request_should_be_multipart() -> Result<()>{
+        let mock = MockTransport::new().on(Method::POST, "audio/transcriptions", MockResponse::json(json!({"text": "你好"})));
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock.clone());
+        let req = TranscriptionRequestBuilder::default()
+            .filename("hello.mp3")
+            .content(b"ID3".to_vec())
+            .language("zh")
+            .build()?;
+        let res = sdk.create_transcription(req).await?;
+        assert_eq!(res.text, "你好");
+
+        let recorded = mock.requests().remove(0);
+        assert_eq!(recorded.path, "audio/transcriptions");
+        assert!(recorded.headers["content-type"].to_str()?.starts_with("multipart/form-data"));
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::Result;
+use derive_builder::Builder;
+use serde::{Serialize, Deserialize};
+
+use crate::OpenaiSdk;
+use super::common::{ApiRequest, ListQuery, ListResponse};
+
+// 批处理API(/v1/batches): 上传 JSONL 格式的请求文件，在 24 小时内异步执行，费用为同步请求的一半
+
+
+///
+/// 创建批处理API-请求体
+/// 输入文件需要先以 `FilePurpose::Batch` 用途上传，每行为一个请求:
+/// `{"custom_id": "req-1", "method": "POST", "url": "/v1/chat/completions", "body": {...}}`
+///
+#[derive(Debug,Clone,Serialize,Builder)]
+#[builder(pattern = "mutable")]
+pub struct CreateBatchRequest{
+    /// 输入文件ID
+    #[builder(setter(into))]
+    pub input_file_id: String,
+
+    /// 输入文件中所有请求的接口
+    #[builder(default)]
+    pub endpoint: BatchEndpoint,
+
+    /// 完成时限，目前只支持 24h
+    #[builder(default = "\"24h\".to_string()",setter(into))]
+    pub completion_window: String,
+
+    /// 附加在对象上的键值对，最多 16 个
+    #[builder(default,setter(into))]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+}
+
+impl CreateBatchRequest {
+    pub fn new(input_file_id: impl Into<String>, endpoint: BatchEndpoint) -> Self {
+        CreateBatchRequestBuilder::default()
+        .input_file_id(input_file_id)
+        .endpoint(endpoint)
+        .build()
+        .unwrap()
+    }
+}
+
+/// 批处理支持的接口
+#[derive(Debug,Clone,Default,PartialEq, Eq,Serialize,Deserialize)]
+pub enum BatchEndpoint{
+    #[default]
+    #[serde(rename = "/v1/chat/completions")]
+    ChatCompletions,
+    #[serde(rename = "/v1/embeddings")]
+    Embeddings,
+    #[serde(rename = "/v1/completions")]
+    Completions,
+    #[serde(rename = "/v1/responses")]
+    Responses,
+    /// 未内置的接口，序列化时原样输出该字符串
+    #[serde(untagged)]
+    Other(String),
+}
+
+
+///
+/// 批处理对象
+///
+#[derive(Debug,Clone,Deserialize)]
+pub struct Batch{
+    /// 批处理ID
+    pub id: String,
+    /// 对象类型，始终为 batch
+    pub object: String,
+    /// 请求的接口
+    pub endpoint: BatchEndpoint,
+    /// 输入文件ID
+    pub input_file_id: String,
+    /// 完成时限
+    pub completion_window: String,
+    /// 处理状态
+    pub status: BatchStatus,
+    /// 成功请求的输出文件ID
+    #[serde(default)]
+    pub output_file_id: Option<String>,
+    /// 失败请求的错误文件ID
+    #[serde(default)]
+    pub error_file_id: Option<String>,
+    /// 创建时的 Unix 时间戳（以秒为单位）
+    pub created_at: u64,
+    /// 完成时的 Unix 时间戳（以秒为单位）
+    #[serde(default)]
+    pub completed_at: Option<u64>,
+    /// 各状态的请求数量
+    #[serde(default)]
+    pub request_counts: BatchRequestCounts,
+    /// 附加的键值对
+    #[serde(default)]
+    pub metadata: Option<HashMap<String, String>>,
+}
+
+/// 批处理的状态
+#[derive(Debug,Clone,PartialEq, Eq,Serialize,Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus{
+    /// 校验输入文件中
+    Validating,
+    /// 输入文件校验失败
+    Failed,
+    /// 执行中
+    InProgress,
+    /// 生成输出文件中
+    Finalizing,
+    /// 已完成
+    Completed,
+    /// 超过完成时限
+    Expired,
+    /// 取消中
+    Cancelling,
+    /// 已取消
+    Cancelled,
+    /// 未知的状态，保留原始值
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl BatchStatus {
+    /// 是否为最终状态
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, BatchStatus::Failed | BatchStatus::Completed | BatchStatus::Expired | BatchStatus::Cancelled)
+    }
+}
+
+/// 批处理中各状态的请求数量
+#[derive(Debug,Clone,Default,Deserialize)]
+pub struct BatchRequestCounts{
+    /// 请求总数
+    pub total: u32,
+    /// 已完成的请求数
+    pub completed: u32,
+    /// 失败的请求数
+    pub failed: u32,
+}
+
+
+// 批处理相关的 api 请求
+impl OpenaiSdk {
+
+    ///
+    /// 创建批处理
+    ///
+    pub async fn create_batch(&self, req: CreateBatchRequest) -> Result<Batch>{
+        self.send_json(ApiRequest::post("batches", &req)?).await
+    }
+
+    ///
+    /// 获取批处理
+    ///
+    pub async fn retrieve_batch(&self, batch_id: &str) -> Result<Batch>{
+        self.send_json(ApiRequest::get(format!("batches/{batch_id}"))).await
+    }
+
+    ///
+    /// 取消批处理
+    ///
+    pub async fn cancel_batch(&self, batch_id: &str) -> Result<Batch>{
+        self.send_json(ApiRequest::post(format!("batches/{batch_id}/cancel"), &serde_json::json!({}))?).await
+    }
+
+    ///
+    /// 获取批处理列表
+    ///
+    pub async fn list_batches(&self, query: ListQuery) -> Result<ListResponse<Batch>>{
+        self.send_json(ApiRequest::get("batches").query(&query)?).await
+    }
+
+    ///
+    /// 轮询批处理直到结束(completed、failed、expired、cancelled)
+    ///
+    pub async fn poll_batch(&self, batch_id: &str, interval: Duration) -> Result<Batch>{
+        loop {
+            let batch = self.retrieve_batch(batch_id).await?;
+            if batch.status.is_terminal() {
+                return Ok(batch);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use reqwest::Method;
+    use serde_json::json;
+    use crate::transport::{MockTransport, MockResponse};
+    use super::*;
+
+    #[tokio::test]
+    async fn batch_requests_should_work() -> Result<()>{
+        let batch = |status: &str| json!({
+            "id": "batch_1", "object": "batch", "endpoint": "/v1/chat/completions", "input_file_id": "file-in",
+            "completion_window": "24h", "status": status, "output_file_id": null, "created_at": 1,
+            "request_counts": {"total": 2, "completed": 0, "failed": 0}
+        });
+        let mock = MockTransport::new()
+            .on(Method::POST, "batches", MockResponse::json(batch("validating")))
+            .once(Method::GET, "batches/batch_1", MockResponse::json(batch("in_progress")))
+            .on(Method::GET, "batches/batch_1", MockResponse::json(batch("completed")));
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock.clone());
+
+        let created = sdk.create_batch(CreateBatchRequest::new("file-in", BatchEndpoint::ChatCompletions)).await?;
+        assert_eq!(created.status, BatchStatus::Validating);
+        assert_eq!(
+            mock.requests()[0].json()?,
+            json!({"input_file_id": "file-in", "endpoint": "/v1/chat/completions", "completion_window": "24h"})
+        );
+
+        let done = sdk.poll_batch("batch_1", Duration::from_millis(1)).await?;
+        assert_eq!(done.status, BatchStatus::Completed);
+        assert_eq!(done.request_counts.total, 2);
+        Ok(())
+    }
+}
@@ -1,17 +1,21 @@
 
 
+use std::collections::HashMap;
+
 use derive_builder::Builder;
 use serde::{Serialize,Deserialize};
 use reqwest::{Client,RequestBuilder};
-use crate::IntoRequest;
+use crate::{IntoRequest, TypedRequest};
 
 use super::message::{ChatMessage, ToolType, AssistantMessage};
+use super::logprobs::ChatCompletionLogprobs;
 
 
 ///
 /// 创建聊天对话API-请求体
 /// 
 #[derive(Debug,Clone,Serialize,Builder)]
+#[builder(build_fn(validate = "Self::validate"))]
 pub struct ChatCompletionRequest{
     /// 该次对话的所有消息列表。
     #[builder(setter(into))]
@@ -33,6 +37,12 @@ pub struct ChatCompletionRequest{
     #[serde(skip_serializing_if = "Option::is_none")]
     max_tokens: Option<usize>,
 
+    /// 聊天完成时生成的最大令牌数上限，包括可见的输出令牌和推理令牌;
+    /// 推理模型(o系列)不支持 max_tokens，需要使用该参数。
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_completion_tokens: Option<usize>,
+
     /// 为每条输入消息生成多少个聊天完成选项。请注意，您将根据所有选项生成的代币数量付费。将 n 保留为 1 以最大限度地降低成本。
     #[builder(default,setter(strip_option))]
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -60,10 +70,10 @@ pub struct ChatCompletionRequest{
     /// 用于指定模型生成文本的停止标记。当模型遇到 stop 中指定的词语或短语时，会停止生成文本并返回结果。
     /// 这个属性允许你控制模型生成文本的长度或确保生成的文本在某个特定点结束，
     /// 例如，当模型生成了特定的句子或段落后立即停止。这有助于限制模型输出的长度或确保生成的文本在特定条件下结束。
-    /// TODO: make this as an enum
-    #[builder(default,setter(strip_option))]
+    /// 最多可以指定 4 个停止标记。
+    #[builder(default,setter(into,strip_option))]
     #[serde(skip_serializing_if = "Option::is_none")]
-    stop: Option<String>,
+    stop: Option<StopSequence>,
 
     /// 设置返回数据是否以流式方式;
     /// 当设置 stream 为 false 时，API 将等待所有结果都准备就绪后一次性返回给用户。
@@ -72,6 +82,11 @@ pub struct ChatCompletionRequest{
     #[serde(skip_serializing_if = "Option::is_none")]
     stream: Option<bool>,
 
+    /// 流式响应的选项，仅当 stream 为 true 时可以设置。
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+
     /// temperature 属性是在生成文本时用来控制模型创造性和多样性的一个重要参数。它影响模型生成下一个词或字符时对概率分布进行的抽样过程。
     /// 设置较高的 temperature 值会增加模型对不同词汇的随机性，从而产生更多样化和更富创造性的输出。这意味着模型更可能选择概率较低的词汇或字符作为下一个生成的内容，增加了多样性，但也可能导致输出的不确定性增加。
     /// 相反，设置较低的 temperature 值会减少模型的随机性，使其更倾向于选择概率较高的词汇或字符作为下一个生成的内容。这样可以使得输出更加可预测，但可能会减少生成文本的多样性。
@@ -96,10 +111,158 @@ pub struct ChatCompletionRequest{
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_choice: Option<ToolChoice>,
 
+    /// 是否允许模型在一次回复中并行调用多个工具。
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parallel_tool_calls: Option<bool>,
+
+    /// 修改指定 token 在生成结果中出现的可能性。
+    /// 键为分词器中的 token ID，值为 -100~100 之间的偏置值;
+    /// -100 表示禁止该 token 出现，100 表示只能选择该 token。
+    #[builder(default,setter(into))]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    logit_bias: HashMap<u32, i8>,
+
     /// 代表您的最终用户的唯一标识符
     #[builder(default,setter(strip_option))]
     #[serde(skip_serializing_if = "Option::is_none")]
-    user: Option<String>
+    user: Option<String>,
+
+    /// 是否返回输出消息中每个 token 的对数概率;
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logprobs: Option<bool>,
+
+    /// 每个 token 位置返回概率最高的候选 token 数量，取值 0~20;
+    /// 使用该参数时必须同时将 `logprobs` 设置为 true。
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_logprobs: Option<u8>,
+
+    /// 处理该请求的服务等级。
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    service_tier: Option<ServiceTier>,
+
+    /// 是否保存该次对话的输出，用于模型蒸馏或评估。
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    store: Option<bool>,
+
+    /// 附加在该次对话上的键值对，最多 16 个;
+    /// 键最长 64 个字符，值最长 512 个字符。
+    #[builder(default,setter(into))]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    metadata: HashMap<String, String>,
+
+    /// 推理模型(o系列)的推理强度，强度越低响应越快，推理使用的令牌越少。
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning_effort: Option<ReasoningEffort>,
+
+    /// 跳过响应缓存，直接请求接口(见 `OpenaiSdk::with_cache`)，不会发送给接口
+    #[builder(default)]
+    #[serde(skip)]
+    bypass_cache: bool,
+}
+
+// ChatCompletionRequest 方法
+impl ChatCompletionRequest {
+    /// 开启流式返回
+    pub(crate) fn streaming(mut self) -> Self {
+        self.stream = Some(true);
+        self
+    }
+
+    /// 对话的消息列表
+    pub fn messages(&self) -> &[ChatMessage] {
+        &self.messages
+    }
+
+    /// 对话的消息列表(可修改)，例如在中间件中脱敏或者插入消息
+    pub fn messages_mut(&mut self) -> &mut Vec<ChatMessage> {
+        &mut self.messages
+    }
+
+    /// 要使用的模型
+    pub fn model(&self) -> &Model {
+        &self.model
+    }
+
+    /// 要使用的模型(可修改)，例如路由时映射为后端的模型
+    pub fn model_mut(&mut self) -> &mut Model {
+        &mut self.model
+    }
+
+    /// 是否跳过响应缓存
+    pub(crate) fn bypass_cache(&self) -> bool {
+        self.bypass_cache
+    }
+
+    /// 本地估算的令牌数，包括提示词以及请求的最大输出令牌数，与限流器预留额度时使用的值一致
+    pub fn estimated_tokens(&self) -> u32 {
+        serde_json::to_value(self).map(|body| crate::rate_limit::estimate_tokens(&body)).unwrap_or_default()
+    }
+}
+
+// 构建请求时校验各参数的取值范围
+impl ChatCompletionRequestBuilder {
+    fn validate(&self) -> Result<(), String> {
+        check_range("temperature", self.temperature.flatten(), 0.0, 2.0)?;
+        check_range("top_p", self.top_p.flatten(), 0.0, 1.0)?;
+        check_range("frequency_penalty", self.frequency_penalty.flatten(), -2.0, 2.0)?;
+        check_range("presence_penalty", self.presence_penalty.flatten(), -2.0, 2.0)?;
+
+        if let Some(Some(n)) = self.n {
+            if n == 0 || n > 128 {
+                return Err(format!("n must be between 1 and 128, got {n}"));
+            }
+        }
+        if let Some(Some(top_logprobs)) = self.top_logprobs {
+            if top_logprobs > 20 {
+                return Err(format!("top_logprobs must be between 0 and 20, got {top_logprobs}"));
+            }
+            if self.logprobs != Some(Some(true)) {
+                return Err("top_logprobs requires logprobs to be true".into());
+            }
+        }
+        if let Some(Some(stop)) = &self.stop {
+            let len = stop.as_slice().len();
+            if len == 0 || len > 4 {
+                return Err(format!("stop must contain 1 to 4 sequences, got {len}"));
+            }
+        }
+        if let Some(logit_bias) = &self.logit_bias {
+            if let Some((token, bias)) = logit_bias.iter().find(|(_, bias)| !(-100..=100).contains(*bias)) {
+                return Err(format!("logit_bias for token {token} must be between -100 and 100, got {bias}"));
+            }
+        }
+        if let Some(metadata) = &self.metadata {
+            if metadata.len() > 16 {
+                return Err(format!("metadata can have at most 16 pairs, got {}", metadata.len()));
+            }
+            for (key, value) in metadata {
+                if key.chars().count() > 64 {
+                    return Err(format!("metadata key `{key}` is longer than 64 characters"));
+                }
+                if value.chars().count() > 512 {
+                    return Err(format!("metadata value for `{key}` is longer than 512 characters"));
+                }
+            }
+        }
+        if self.stream_options.is_some_and(|o| o.is_some()) && self.stream != Some(Some(true)) {
+            return Err("stream_options can only be set when stream is true".into());
+        }
+        Ok(())
+    }
+}
+
+/// 校验可选参数是否位于闭区间 [min, max] 之内
+pub(crate) fn check_range(name: &str, value: Option<f32>, min: f32, max: f32) -> Result<(), String> {
+    match value {
+        Some(v) if !(min..=max).contains(&v) => Err(format!("{name} must be between {min} and {max}, got {v}")),
+        _ => Ok(()),
+    }
 }
 
 // CreateImageRequest 实现 IntoRequest 特征，返回对应的网络请求构建器;
@@ -111,6 +274,95 @@ impl IntoRequest for ChatCompletionRequest{
         client.post("https://api.openai.com/v1/chat/completions")
         .json(&self)
     }
+
+    fn is_stream(&self) -> bool {
+        self.stream == Some(true)
+    }
+}
+
+impl TypedRequest for ChatCompletionRequest{
+    type Response = ChatCompletionResponse;
+}
+
+
+/// 停止标记，可以是单个字符串或者字符串数组(最多 4 个)
+#[derive(Debug,Clone,PartialEq, Eq,Serialize)]
+#[serde(untagged)]
+pub enum StopSequence{
+    /// 单个停止标记
+    Single(String),
+    /// 多个停止标记
+    Multiple(Vec<String>),
+}
+
+impl StopSequence {
+    /// 以切片形式返回所有停止标记
+    pub fn as_slice(&self) -> &[String] {
+        match self {
+            StopSequence::Single(s) => std::slice::from_ref(s),
+            StopSequence::Multiple(v) => v,
+        }
+    }
+}
+
+impl From<&str> for StopSequence {
+    fn from(value: &str) -> Self {
+        StopSequence::Single(value.into())
+    }
+}
+
+impl From<String> for StopSequence {
+    fn from(value: String) -> Self {
+        StopSequence::Single(value)
+    }
+}
+
+impl From<Vec<String>> for StopSequence {
+    fn from(value: Vec<String>) -> Self {
+        StopSequence::Multiple(value)
+    }
+}
+
+impl From<Vec<&str>> for StopSequence {
+    fn from(value: Vec<&str>) -> Self {
+        StopSequence::Multiple(value.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<const N: usize> From<[&str; N]> for StopSequence {
+    fn from(value: [&str; N]) -> Self {
+        StopSequence::Multiple(value.into_iter().map(Into::into).collect())
+    }
+}
+
+/// 流式响应选项
+#[derive(Debug,Clone,Copy,Default,PartialEq, Eq,Serialize)]
+pub struct StreamOptions{
+    /// 是否在数据流结束前额外发送一个包含整个请求使用统计的数据块
+    pub include_usage: bool,
+}
+
+/// 服务等级枚举
+#[derive(Debug,Clone,Copy,Default,PartialEq, Eq,Serialize,Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceTier{
+    /// 由项目设置决定
+    #[default]
+    Auto,
+    /// 标准等级
+    Default,
+    /// 低价、较高延迟的等级
+    Flex,
+}
+
+/// 推理强度枚举
+#[derive(Debug,Clone,Copy,Default,PartialEq, Eq,Serialize,Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReasoningEffort{
+    Low,
+    #[default]
+    Medium,
+    High,
 }
 
 
@@ -131,7 +383,7 @@ pub enum ToolChoice{
 }
 
 /// 工具实体
-#[derive(Debug,Clone,Serialize)]
+#[derive(Debug,Clone,Serialize,Deserialize)]
 pub struct Tool{
     /// 工具的类型,目前仅支持 function。
     r#type: ToolType,
@@ -139,16 +391,58 @@ pub struct Tool{
     function: FunctionInfo,
 }
 /// 工具函数信息实体
-#[derive(Debug,Clone,Serialize)]
+#[derive(Debug,Clone,Serialize,Deserialize)]
 pub struct FunctionInfo{
     /// 工具函数功能的描述，模型使用它来选择何时以及如何调用该函数。
+    #[serde(default)]
     description: String,
     /// 要调用的函数的名称。必须是 a-z、A-Z、0-9，或包含下划线和破折号，最大长度为 64;
     name: String,
     /// 函数的所有参数，以Json格式描述
+    #[serde(default)]
     parameters: serde_json::Value,
 }
 
+impl Tool {
+    /// 创建函数工具
+    pub fn function(name: impl Into<String>, description: impl Into<String>, parameters: serde_json::Value) -> Self {
+        Self { r#type: ToolType::Function, function: FunctionInfo::new(name, description, parameters) }
+    }
+
+    /// 工具对应的函数信息
+    pub fn function_info(&self) -> &FunctionInfo {
+        &self.function
+    }
+}
+
+impl FunctionInfo {
+    /// 创建函数信息
+    pub fn new(name: impl Into<String>, description: impl Into<String>, parameters: serde_json::Value) -> Self {
+        Self { description: description.into(), name: name.into(), parameters }
+    }
+
+    /// 函数名称
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 函数功能描述
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// 函数参数的 Json Schema
+    pub fn parameters(&self) -> &serde_json::Value {
+        &self.parameters
+    }
+}
+
+impl From<Tool> for FunctionInfo {
+    fn from(tool: Tool) -> Self {
+        tool.function
+    }
+}
+
 
 /// 模型响应格式对象
 #[derive(Debug,Clone,Serialize)]
@@ -170,7 +464,7 @@ pub enum  ChatResponseFormat{
 
 /// 可以使用的模型ID枚举，不同的模型价格不同
 /// 具体模型种类可参考: https://openai.com/pricing
-#[derive(Debug,Clone,Copy,Default,PartialEq, Eq,Serialize,Deserialize)]
+#[derive(Debug,Clone,Default,PartialEq, Eq,Hash,Serialize,Deserialize)]
 pub enum Model{
     // GPT3相关模型;
     #[default]
@@ -184,6 +478,30 @@ pub enum Model{
     Gpt4Turbo,
     #[serde(rename = "gpt-4-1106-vision-preview")]
     Gpt4TurboVision,
+
+    /// 未内置的模型ID，例如响应中返回的带日期的模型快照`gpt-4o-2024-08-06`;
+    /// 序列化时原样输出该字符串
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl Model {
+    /// 模型ID字符串，与序列化后的值一致
+    pub fn as_str(&self) -> &str {
+        match self {
+            Model::Gpt3Turbo => "gpt-3.5-turbo-1106",
+            Model::Gpt3TurboInstruct => "gpt-3.5-turbo-instruct",
+            Model::Gpt4Turbo => "gpt-4-1106-preview",
+            Model::Gpt4TurboVision => "gpt-4-1106-vision-preview",
+            Model::Other(id) => id,
+        }
+    }
+}
+
+impl std::fmt::Display for Model {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 
@@ -207,13 +525,20 @@ pub struct ChatCompletionResponse{
     pub model: Model,
 
     /// 该指纹代表模型运行时使用的后端配置。
-    pub system_fingerprint: String,
+    /// 部分模型或兼容服务不会返回该字段，或返回 null;
+    #[serde(default)]
+    pub system_fingerprint: Option<String>,
 
     /// 对象类型，始终为 chat.completion;
     pub object: String,
 
     /// 完成请求的使用统计。
-    pub usage: ChatCompleteUsage,
+    #[serde(default)]
+    pub usage: Option<ChatCompleteUsage>,
+
+    /// 未建模的其他响应字段(例如 service_tier)，原样保留
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 
@@ -233,7 +558,11 @@ pub struct ChatCompleteUsage{
 
     /// 这是指完成请求时总共处理的 token 数量，即 prompt_tokens 加上 completion_tokens 的总和。
     /// 这个指标考虑了你发送的输入文本以及模型生成的输出文本所涉及的所有 token 数量。
-    pub total_tokens: usize
+    pub total_tokens: usize,
+
+    /// 未建模的其他统计字段(例如 prompt_tokens_details)，原样保留
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 /// 聊天完成选项
@@ -248,13 +577,114 @@ pub struct ChatCompletionChoice{
     pub index: usize,
 
     /// 选项的消息内容;
-    pub message: AssistantMessage
+    pub message: AssistantMessage,
+
+    /// 选项的对数概率信息，仅当请求参数 `logprobs` 为 true 时返回;
+    #[serde(default)]
+    pub logprobs: Option<ChatCompletionLogprobs>,
+
+    /// 未建模的其他选项字段，原样保留
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+///
+/// 流式返回时的数据块
+///
+#[derive(Debug,Clone,Deserialize)]
+pub struct ChatCompletionChunk{
+    /// 聊天完成的唯一标识，同一次请求的所有数据块相同
+    pub id: String,
+
+    /// 选项的增量内容，开启 `include_usage` 时最后一个数据块为空列表
+    pub choices: Vec<ChatCompletionChunkChoice>,
+
+    /// 创建聊天完成时的 Unix 时间戳（以秒为单位）。
+    pub created: usize,
+
+    /// 使用的模型ID
+    pub model: Model,
+
+    /// 该指纹代表模型运行时使用的后端配置。
+    #[serde(default)]
+    pub system_fingerprint: Option<String>,
+
+    /// 对象类型，始终为 chat.completion.chunk;
+    pub object: String,
+
+    /// 完成请求的使用统计，仅在开启 `include_usage` 时的最后一个数据块中返回
+    #[serde(default)]
+    pub usage: Option<ChatCompleteUsage>,
+
+    /// 未建模的其他字段，原样保留
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// 数据块中的选项
+#[derive(Debug,Clone,Deserialize)]
+pub struct ChatCompletionChunkChoice{
+    /// 当前选项在选项列表中的索引;
+    pub index: usize,
+
+    /// 本次新增的消息内容
+    pub delta: ChatCompletionDelta,
+
+    /// 停止原因，只有最后一个数据块不为 null
+    #[serde(default)]
+    pub finish_reason: Option<FinishReason>,
+
+    /// 本次新增 token 的对数概率信息
+    #[serde(default)]
+    pub logprobs: Option<ChatCompletionLogprobs>,
+}
+
+/// 消息的增量内容
+#[derive(Debug,Clone,Default,Deserialize)]
+pub struct ChatCompletionDelta{
+    /// 消息角色，只在第一个数据块中返回
+    #[serde(default)]
+    pub role: Option<String>,
+
+    /// 新增的文本内容
+    #[serde(default)]
+    pub content: Option<String>,
+
+    /// 工具调用的增量，同一个工具调用通过 `index` 关联
+    #[serde(default)]
+    pub tool_calls: Option<Vec<ToolCallDelta>>,
+}
+
+/// 工具调用的增量
+#[derive(Debug,Clone,Default,Deserialize)]
+pub struct ToolCallDelta{
+    /// 工具调用在消息中的索引
+    pub index: usize,
+    /// 工具调用ID，只在该工具调用的第一个增量中返回
+    #[serde(default)]
+    pub id: Option<String>,
+    /// 工具类型
+    #[serde(default)]
+    pub r#type: Option<ToolType>,
+    /// 函数名称以及新增的参数片段
+    #[serde(default)]
+    pub function: Option<FunctionCallDelta>,
+}
 
+/// 函数调用的增量
+#[derive(Debug,Clone,Default,Deserialize)]
+pub struct FunctionCallDelta{
+    /// 函数名称，只在第一个增量中返回
+    #[serde(default)]
+    pub name: Option<String>,
+    /// 新增的参数片段(Json 文本)
+    #[serde(default)]
+    pub arguments: Option<String>,
 }
 
 ///
 /// 回复结束的原因标识
-#[derive(Debug,Clone,Copy,Default,PartialEq, Eq,Deserialize)]
+#[derive(Debug,Clone,Default,PartialEq, Eq,Serialize,Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum FinishReason {
     /// 响应内容自然结束或者到达请求时提供的停止标识符(stop);
@@ -264,8 +694,13 @@ pub enum FinishReason {
     Length,
     /// 由于内容过滤器中的标志而省略内容
     ContentFilter,
-    ///
-    ToolCalls
+    /// 模型调用了工具
+    ToolCalls,
+    /// 模型调用了函数(已废弃的 functions 参数)
+    FunctionCall,
+    /// 未知的停止原因，保留原始值
+    #[serde(untagged)]
+    Other(String),
 }
 
 
@@ -276,7 +711,7 @@ pub enum FinishReason {
 mod tests{
     use anyhow::{Result, Ok};
 
-    use crate::{api::message::ChatMessage, OpenaiSdk};
+    use crate::{api::message::ChatMessage, cassette::CassetteMode, OpenaiSdk};
     use super::*;
 
     #[test]
@@ -299,6 +734,7 @@ mod tests{
             json_value,
             serde_json::json!({
                 "tool_choice": "auto",
+                "model": "gpt-3.5-turbo-1106",
                 "messages": [
                     {
                         "role": "system",
@@ -314,13 +750,140 @@ mod tests{
     }
 
 
+    /// 反序列化: 工具调用时 content 为 null
+    #[test]
+    fn chat_completion_response_with_null_content_should_deserialize() -> Result<()>{
+        let res: ChatCompletionResponse = serde_json::from_str(include_str!("../../fixtures/chat_completion/tool_calls_null_content.json"))?;
+        let choice = &res.choices[0];
+        assert_eq!(choice.finish_reason, FinishReason::ToolCalls);
+        assert!(choice.message.content.is_none());
+        assert_eq!(choice.message.tool_calls.len(), 1);
+        assert_eq!(choice.message.tool_calls[0].function.name, "get_current_weather");
+        assert_eq!(res.system_fingerprint.as_deref(), Some("fp_772e8125bb"));
+        Ok(())
+    }
+
+    /// 反序列化: 缺失 system_fingerprint 字段
+    #[test]
+    fn chat_completion_response_without_fingerprint_should_deserialize() -> Result<()>{
+        let res: ChatCompletionResponse = serde_json::from_str(include_str!("../../fixtures/chat_completion/missing_fingerprint.json"))?;
+        assert!(res.system_fingerprint.is_none());
+        assert_eq!(res.model, Model::Gpt4Turbo);
+        assert_eq!(res.usage.map(|u| u.total_tokens), Some(44));
+        Ok(())
+    }
+
+    /// 反序列化: 未知的模型、停止原因以及新增字段
+    #[test]
+    fn chat_completion_response_with_newer_fields_should_deserialize() -> Result<()>{
+        let res: ChatCompletionResponse = serde_json::from_str(include_str!("../../fixtures/chat_completion/newer_fields.json"))?;
+        assert_eq!(res.model, Model::Other("gpt-4o-2024-08-06".into()));
+        assert_eq!(res.extra["service_tier"], "default");
+        assert_eq!(res.choices[0].finish_reason, FinishReason::FunctionCall);
+        assert!(res.choices[0].message.tool_calls.is_empty());
+        assert!(res.choices[0].message.extra.contains_key("function_call"));
+        assert_eq!(res.choices[1].finish_reason, FinishReason::Other("content_filter_v2".into()));
+        assert_eq!(res.choices[1].message.extra["refusal"], "I can't help with that.");
+        let usage = res.usage.unwrap();
+        assert_eq!(usage.extra["completion_tokens_details"]["reasoning_tokens"], 0);
+        // 未知值序列化时保持原样
+        assert_eq!(serde_json::to_value(&res.model)?, "gpt-4o-2024-08-06");
+        Ok(())
+    }
+
+    /// 序列化: logprobs 相关参数
+    #[test]
+    fn chat_completion_request_with_logprobs_should_serialize() -> Result<()>{
+        let req = ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatMessage::new_user("Is this review positive?", "")])
+            .logprobs(true)
+            .top_logprobs(2)
+            .build()?;
+        let json_value = serde_json::to_value(req)?;
+        assert_eq!(json_value["logprobs"], true);
+        assert_eq!(json_value["top_logprobs"], 2);
+        Ok(())
+    }
+
+    /// 反序列化: 带有 logprobs 的响应
+    #[test]
+    fn chat_completion_response_with_logprobs_should_deserialize() -> Result<()>{
+        let res: ChatCompletionResponse = serde_json::from_str(include_str!("../../fixtures/chat_completion/logprobs.json"))?;
+        let logprobs = res.choices[0].logprobs.as_ref().unwrap();
+        let token = &logprobs.tokens()[0];
+        assert_eq!(token.token, "positive");
+        assert_eq!(token.top_logprobs.len(), 2);
+        assert_eq!(token.top_logprobs[1].token, "neutral");
+        assert!(logprobs.sequence_probability() > 0.99);
+        assert!(!res.choices[0].extra.contains_key("logprobs"));
+        Ok(())
+    }
+
+    /// 序列化: 停止标记、logit_bias 以及其他采样参数
+    #[test]
+    fn chat_completion_request_with_sampling_params_should_serialize() -> Result<()>{
+        let req = ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatMessage::new_user("Count to ten.", "")])
+            .stop(["7", "eight"])
+            .logit_bias(HashMap::from([(1734, -100)]))
+            .temperature(0.0)
+            .parallel_tool_calls(false)
+            .service_tier(ServiceTier::Flex)
+            .store(true)
+            .metadata(HashMap::from([("team".to_string(), "eval".to_string())]))
+            .max_completion_tokens(64)
+            .reasoning_effort(ReasoningEffort::Low)
+            .stream(true)
+            .stream_options(StreamOptions { include_usage: true })
+            .build()?;
+        let json_value = serde_json::to_value(req)?;
+        assert_eq!(json_value["stop"], serde_json::json!(["7", "eight"]));
+        assert_eq!(json_value["logit_bias"], serde_json::json!({"1734": -100}));
+        assert_eq!(json_value["service_tier"], "flex");
+        assert_eq!(json_value["metadata"]["team"], "eval");
+        assert_eq!(json_value["reasoning_effort"], "low");
+        assert_eq!(json_value["stream_options"]["include_usage"], true);
+
+        let req = ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatMessage::new_user("Count to ten.", "")])
+            .stop("7")
+            .build()?;
+        assert_eq!(serde_json::to_value(req)?["stop"], "7");
+        Ok(())
+    }
+
+    /// 构建时校验参数取值范围
+    #[test]
+    fn chat_completion_request_out_of_range_should_fail(){
+        let builder = || {
+            let mut builder = ChatCompletionRequestBuilder::default();
+            builder.messages(vec![ChatMessage::new_user("hi", "")]);
+            builder
+        };
+        assert!(builder().temperature(2.5).build().is_err());
+        assert!(builder().top_p(-0.1).build().is_err());
+        assert!(builder().frequency_penalty(-2.1).build().is_err());
+        assert!(builder().presence_penalty(3.0).build().is_err());
+        assert!(builder().n(0).build().is_err());
+        assert!(builder().top_logprobs(5).build().is_err());
+        assert!(builder().logprobs(true).top_logprobs(21).build().is_err());
+        assert!(builder().stop(["a", "b", "c", "d", "e"]).build().is_err());
+        assert!(builder().logit_bias(HashMap::from([(42, 101)])).build().is_err());
+        assert!(builder().stream_options(StreamOptions { include_usage: true }).build().is_err());
+        let metadata: HashMap<String, String> = (0..17).map(|i| (i.to_string(), String::new())).collect();
+        assert!(builder().metadata(metadata).build().is_err());
+        assert!(builder().temperature(2.0).presence_penalty(-2.0).build().is_ok());
+    }
+
     /// 测试chat请求
+    /// 默认回放录制的响应，设置 OPENAI_CASSETTE=record 以及 OPENAI_API_KEY 时重新录制
     #[tokio::test]
     async fn simple_chat_completion_should_work() -> Result<()>{
         // 获取环境变量中的openai api key
-        let api_key = std::env::var("OPENAI_API_KEY")?;
+        let api_key = std::env::var("OPENAI_API_KEY").unwrap_or_default();
         // 构建sdk
-        let sdk = OpenaiSdk::new(api_key);
+        let cassette = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/cassettes/simple_chat_completion.json");
+        let sdk = OpenaiSdk::new(api_key).with_cassette(cassette, CassetteMode::from_env())?;
         // 构建请求
         let req = get_simple_chat_completion_request();
         // 发送请求
@@ -0,0 +1,221 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use reqwest::{Client, Method, RequestBuilder};
+use tokio::time::Instant;
+use crate::IntoRequest;
+use crate::error::PollTimeout;
+
+// 各类资源接口(assistants、threads、vector_stores 等)通用的请求与响应结构
+
+
+///
+/// 路径中带有资源ID，或者没有请求体的接口请求
+/// 例如 `GET /v1/assistants/{id}`、`DELETE /v1/threads/{id}`
+///
+#[derive(Debug,Clone)]
+pub(crate) struct ApiRequest{
+    /// 请求方法
+    method: Method,
+    /// `/v1/` 之后的接口路径
+    path: String,
+    /// 查询参数
+    query: Vec<(String, String)>,
+    /// Json 请求体
+    body: Option<serde_json::Value>,
+    /// `OpenAI-Beta` 请求头
+    beta: Option<&'static str>,
+}
+
+impl ApiRequest {
+
+    /// 构建 GET 请求
+    pub(crate) fn get(path: impl Into<String>) -> Self {
+        Self { method: Method::GET, path: path.into(), query: Vec::new(), body: None, beta: None }
+    }
+
+    /// 构建 DELETE 请求
+    pub(crate) fn delete(path: impl Into<String>) -> Self {
+        Self { method: Method::DELETE, ..Self::get(path) }
+    }
+
+    /// 构建带有Json请求体的 POST 请求
+    pub(crate) fn post(path: impl Into<String>, body: &impl Serialize) -> anyhow::Result<Self> {
+        Ok(Self { method: Method::POST, body: Some(serde_json::to_value(body)?), ..Self::get(path) })
+    }
+
+    /// 设置查询参数，值为 null 的字段会被忽略
+    pub(crate) fn query(mut self, query: &impl Serialize) -> anyhow::Result<Self> {
+        if let serde_json::Value::Object(map) = serde_json::to_value(query)? {
+            for (key, value) in map {
+                match value {
+                    serde_json::Value::Null => {}
+                    serde_json::Value::String(s) => self.query.push((key, s)),
+                    other => self.query.push((key, other.to_string())),
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    /// 设置 `OpenAI-Beta` 请求头，例如 `assistants=v2`
+    pub(crate) fn beta(mut self, beta: &'static str) -> Self {
+        self.beta = Some(beta);
+        self
+    }
+}
+
+// ApiRequest 实现 IntoRequest 特征，返回对应的网络请求构建器;
+impl IntoRequest for ApiRequest{
+    fn into_request(self, client: Client) -> RequestBuilder {
+        let mut req = client.request(self.method, format!("https://api.openai.com/v1/{}", self.path));
+        if !self.query.is_empty() {
+            req = req.query(&self.query);
+        }
+        if let Some(beta) = self.beta {
+            req = req.header("OpenAI-Beta", beta);
+        }
+        match self.body {
+            Some(body) => req.json(&body),
+            None => req,
+        }
+    }
+}
+
+
+/// 列表接口的分页查询参数
+#[derive(Debug,Clone,Default,Serialize)]
+pub struct ListQuery{
+    /// 返回的对象数量，取值 1~100，默认 20
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// 按照创建时间排序的方向
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<ListOrder>,
+    /// 分页游标，返回该对象ID之后的数据
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    /// 分页游标，返回该对象ID之前的数据
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+}
+
+/// 排序方向
+#[derive(Debug,Clone,Copy,Default,PartialEq, Eq,Serialize,Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListOrder{
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// 列表接口的分页响应
+#[derive(Debug,Clone,Deserialize)]
+pub struct ListResponse<T>{
+    /// 对象类型，始终为 list
+    pub object: String,
+    /// 当前页的数据
+    pub data: Vec<T>,
+    /// 当前页第一个对象的ID
+    #[serde(default)]
+    pub first_id: Option<String>,
+    /// 当前页最后一个对象的ID，可作为下一页的 `after` 参数
+    #[serde(default)]
+    pub last_id: Option<String>,
+    /// 是否还有更多数据
+    #[serde(default)]
+    pub has_more: bool,
+}
+
+/// 轮询的默认最长等待时间
+pub const DEFAULT_POLL_MAX_WAIT: Duration = Duration::from_secs(600);
+
+///
+/// 轮询异步任务(运行、文件批次等)的参数: 轮询间隔以及最长等待时间，
+/// 超过最长等待时间仍未结束时返回 `PollTimeout` 错误
+///
+#[derive(Debug,Clone,Copy,PartialEq, Eq)]
+pub struct PollOptions{
+    /// 轮询间隔
+    pub interval: Duration,
+    /// 最长等待时间，`None` 表示一直等待，默认为 10 分钟
+    pub max_wait: Option<Duration>,
+}
+
+impl PollOptions {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, max_wait: Some(DEFAULT_POLL_MAX_WAIT) }
+    }
+
+    /// 设置最长等待时间
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = Some(max_wait);
+        self
+    }
+
+    /// 不限制等待时间
+    pub fn unbounded(mut self) -> Self {
+        self.max_wait = None;
+        self
+    }
+
+    ///
+    /// 等待下一次轮询，`started` 为开始轮询的时间，`status` 为对象的当前状态;
+    /// 超过最长等待时间时返回 `PollTimeout` 错误
+    ///
+    pub(crate) async fn wait(&self, started: Instant, id: &str, status: &impl Serialize) -> Result<()> {
+        let waited = started.elapsed();
+        if let Some(max_wait) = self.max_wait {
+            if waited + self.interval > max_wait {
+                let status = match serde_json::to_value(status)? {
+                    serde_json::Value::String(status) => status,
+                    other => other.to_string(),
+                };
+                return Err(PollTimeout { id: id.to_string(), status, waited }.into());
+            }
+        }
+        tokio::time::sleep(self.interval).await;
+        Ok(())
+    }
+}
+
+// 只指定轮询间隔时，使用默认的最长等待时间
+impl From<Duration> for PollOptions {
+    fn from(interval: Duration) -> Self {
+        Self::new(interval)
+    }
+}
+
+/// 删除接口的响应
+#[derive(Debug,Clone,Deserialize)]
+pub struct DeleteResponse{
+    /// 被删除对象的ID
+    pub id: String,
+    /// 对象类型，例如 assistant.deleted
+    pub object: String,
+    /// 是否删除成功
+    pub deleted: bool,
+}
+
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn api_request_should_build_query_and_headers(){
+        let query = ListQuery { limit: Some(10), order: Some(ListOrder::Asc), ..Default::default() };
+        let req = ApiRequest::get("assistants")
+            .query(&query)
+            .unwrap()
+            .beta("assistants=v2")
+            .into_request(Client::new())
+            .build()
+            .unwrap();
+        assert_eq!(req.method(), Method::GET);
+        assert_eq!(req.url().as_str(), "https://api.openai.com/v1/assistants?limit=10&order=asc");
+        assert_eq!(req.headers()["OpenAI-Beta"], "assistants=v2");
+        assert!(req.body().is_none());
+    }
+}
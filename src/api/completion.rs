@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, bail};
+use derive_builder::Builder;
+use serde::{Serialize,Deserialize};
+use reqwest::{Client,RequestBuilder};
+use crate::{IntoRequest, TypedRequest};
+
+use super::chat_completion::{Model, StopSequence, FinishReason, ChatCompleteUsage, check_range};
+
+// 传统的文本补全API(/v1/completions)
+// 仅 instruct 类模型(例如 gpt-3.5-turbo-instruct)可以使用，支持前缀 + 后缀的中间填充(fill-in-the-middle)
+
+
+///
+/// 文本补全API-请求体
+///
+#[derive(Debug,Clone,Serialize,Builder)]
+#[builder(build_fn(validate = "Self::validate"))]
+pub struct CompletionRequest{
+    /// 要补全的提示文本，可以是单个字符串或者字符串数组
+    #[builder(setter(into))]
+    prompt: Prompt,
+
+    /// 要使用的模型ID，默认为 gpt-3.5-turbo-instruct
+    #[builder(default = "Model::Gpt3TurboInstruct")]
+    model: Model,
+
+    /// 插入文本补全之后的后缀，用于中间填充;
+    #[builder(default,setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suffix: Option<String>,
+
+    /// 补全时生成的最大令牌数。
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<usize>,
+
+    /// 采样温度，取值 0~2
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+
+    /// 核采样的累积概率阈值，取值 0~1
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+
+    /// 为每个提示生成多少个补全选项。
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n: Option<usize>,
+
+    /// 是否以流式方式返回结果
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+
+    /// 返回概率最高的候选 token 的对数概率，最大为 5;
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logprobs: Option<u8>,
+
+    /// 是否在补全结果中回显提示文本
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    echo: Option<bool>,
+
+    /// 停止标记，最多 4 个
+    #[builder(default,setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<StopSequence>,
+
+    /// 存在惩罚，取值 -2~2
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+
+    /// 频率惩罚，取值 -2~2
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+
+    /// 在服务端生成 best_of 个补全，并返回其中对数概率最高的 n 个;
+    /// 必须大于等于 n，且不能与流式返回同时使用。
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    best_of: Option<usize>,
+
+    /// token ID 到偏置值(-100~100)的映射
+    #[builder(default,setter(into))]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    logit_bias: HashMap<u32, i8>,
+
+    /// 随机种子
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<usize>,
+
+    /// 代表您的最终用户的唯一标识符
+    #[builder(default,setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+}
+
+// CompletionRequest 构造方法
+impl CompletionRequest {
+    pub fn new(prompt: impl Into<Prompt>) -> Self {
+        CompletionRequestBuilder::default()
+        .prompt(prompt)
+        .build()
+        .unwrap()
+    }
+
+    /// 开启流式返回，并校验与流式返回冲突的参数
+    pub(crate) fn streaming(mut self) -> Result<Self> {
+        if self.best_of.is_some() {
+            bail!("best_of cannot be used together with stream");
+        }
+        self.stream = Some(true);
+        Ok(self)
+    }
+}
+
+// 构建请求时校验各参数的取值范围
+impl CompletionRequestBuilder {
+    fn validate(&self) -> Result<(), String> {
+        check_range("temperature", self.temperature.flatten(), 0.0, 2.0)?;
+        check_range("top_p", self.top_p.flatten(), 0.0, 1.0)?;
+        check_range("frequency_penalty", self.frequency_penalty.flatten(), -2.0, 2.0)?;
+        check_range("presence_penalty", self.presence_penalty.flatten(), -2.0, 2.0)?;
+
+        if let Some(Some(n)) = self.n {
+            if n == 0 || n > 128 {
+                return Err(format!("n must be between 1 and 128, got {n}"));
+            }
+        }
+        if let Some(Some(logprobs)) = self.logprobs {
+            if logprobs > 5 {
+                return Err(format!("logprobs must be between 0 and 5, got {logprobs}"));
+            }
+        }
+        if let Some(Some(best_of)) = self.best_of {
+            let n = self.n.flatten().unwrap_or(1);
+            if best_of < n {
+                return Err(format!("best_of ({best_of}) must be greater than or equal to n ({n})"));
+            }
+            if self.stream == Some(Some(true)) {
+                return Err("best_of cannot be used together with stream".into());
+            }
+        }
+        if let Some(Some(stop)) = &self.stop {
+            let len = stop.as_slice().len();
+            if len == 0 || len > 4 {
+                return Err(format!("stop must contain 1 to 4 sequences, got {len}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+// CompletionRequest 实现 IntoRequest 特征，返回对应的网络请求构建器;
+impl IntoRequest for CompletionRequest{
+    /// 构建post请求，指定目标url
+    /// 将自身序列化为json格式，作为请求体
+    fn into_request(self,client: Client) -> RequestBuilder {
+        client.post("https://api.openai.com/v1/completions").json(&self)
+    }
+
+    fn is_stream(&self) -> bool {
+        self.stream == Some(true)
+    }
+}
+
+impl TypedRequest for CompletionRequest{
+    type Response = CompletionResponse;
+}
+
+
+/// 补全提示，可以是单个字符串或者字符串数组(批量补全)
+#[derive(Debug,Clone,PartialEq, Eq,Serialize)]
+#[serde(untagged)]
+pub enum Prompt{
+    /// 单个提示
+    Single(String),
+    /// 多个提示
+    Multiple(Vec<String>),
+}
+
+impl From<&str> for Prompt {
+    fn from(value: &str) -> Self {
+        Prompt::Single(value.into())
+    }
+}
+
+impl From<String> for Prompt {
+    fn from(value: String) -> Self {
+        Prompt::Single(value)
+    }
+}
+
+impl From<Vec<String>> for Prompt {
+    fn from(value: Vec<String>) -> Self {
+        Prompt::Multiple(value)
+    }
+}
+
+impl From<Vec<&str>> for Prompt {
+    fn from(value: Vec<&str>) -> Self {
+        Prompt::Multiple(value.into_iter().map(Into::into).collect())
+    }
+}
+
+
+
+///
+/// 文本补全API-响应体，流式返回时每个数据块也是该结构
+///
+#[derive(Debug,Clone,Deserialize)]
+pub struct CompletionResponse{
+    /// 补全的唯一标识
+    pub id: String,
+
+    /// 补全选项列表
+    pub choices: Vec<CompletionChoice>,
+
+    /// 创建补全时的 Unix 时间戳（以秒为单位）。
+    pub created: usize,
+
+    /// 使用的模型ID
+    pub model: Model,
+
+    /// 该指纹代表模型运行时使用的后端配置。
+    #[serde(default)]
+    pub system_fingerprint: Option<String>,
+
+    /// 对象类型，始终为 text_completion;
+    pub object: String,
+
+    /// 完成请求的使用统计，流式返回时的数据块中没有该字段。
+    #[serde(default)]
+    pub usage: Option<ChatCompleteUsage>,
+
+    /// 未建模的其他响应字段，原样保留
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// 补全选项
+#[derive(Debug,Clone,Deserialize)]
+pub struct CompletionChoice{
+    /// 补全生成的文本
+    pub text: String,
+
+    /// 当前选项在选项列表中的索引;
+    pub index: usize,
+
+    /// 对数概率信息，仅当请求参数 `logprobs` 不为空时返回
+    #[serde(default)]
+    pub logprobs: Option<CompletionLogprobs>,
+
+    /// 停止原因，流式返回的中间数据块为 null
+    #[serde(default)]
+    pub finish_reason: Option<FinishReason>,
+}
+
+/// 补全结果的对数概率信息
+#[derive(Debug,Clone,Default,Deserialize)]
+pub struct CompletionLogprobs{
+    /// token 列表
+    #[serde(default)]
+    pub tokens: Vec<String>,
+    /// 每个 token 的对数概率，回显的第一个 token 为 null
+    #[serde(default)]
+    pub token_logprobs: Vec<Option<f64>>,
+    /// 每个位置上概率最高的候选 token 及其对数概率
+    #[serde(default)]
+    pub top_logprobs: Vec<Option<HashMap<String, f64>>>,
+    /// 每个 token 在文本中的字符偏移量
+    #[serde(default)]
+    pub text_offset: Vec<usize>,
+}
+
+
+
+/// 单元测试
+#[cfg(test)]
+mod tests{
+    use anyhow::{Result, Ok};
+    use serde_json::json;
+    use super::*;
+
+    #[test]
+    fn completion_request_should_serialize() -> Result<()>{
+        let req = CompletionRequestBuilder::default()
+            .prompt("def add(a, b):\n    ")
+            .suffix("\n\nprint(add(1, 2))")
+            .max_tokens(32)
+            .echo(true)
+            .best_of(3)
+            .n(2)
+            .logprobs(2)
+            .stop(vec!["\n\n"])
+            .build()?;
+        assert_eq!(
+            serde_json::to_value(&req)?,
+            json!({
+                "prompt": "def add(a, b):\n    ",
+                "model": "gpt-3.5-turbo-instruct",
+                "suffix": "\n\nprint(add(1, 2))",
+                "max_tokens": 32,
+                "echo": true,
+                "best_of": 3,
+                "n": 2,
+                "logprobs": 2,
+                "stop": ["\n\n"]
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn completion_request_invalid_should_fail(){
+        assert!(CompletionRequestBuilder::default().prompt("a").logprobs(6).build().is_err());
+        assert!(CompletionRequestBuilder::default().prompt("a").best_of(1).n(2).build().is_err());
+        assert!(CompletionRequestBuilder::default().prompt("a").best_of(2).stream(true).build().is_err());
+        assert!(CompletionRequestBuilder::default().prompt("a").temperature(2.5).build().is_err());
+        assert!(CompletionRequestBuilder::default().prompt("a").presence_penalty(-3.0).build().is_err());
+        assert!(CompletionRequestBuilder::default().prompt("a").n(0).build().is_err());
+        // 流式接口在开启流式返回之后再校验
+        let req = CompletionRequestBuilder::default().prompt("a").best_of(2).build().unwrap();
+        assert!(req.streaming().is_err());
+    }
+
+    #[test]
+    fn completion_response_should_deserialize() -> Result<()>{
+        let res: CompletionResponse = serde_json::from_value(json!({
+            "id": "cmpl-8Z6b0cRzYj7nO8XkUJpB2aTq4VvLs",
+            "object": "text_completion",
+            "created": 1703300000,
+            "model": "gpt-3.5-turbo-instruct",
+            "choices": [{
+                "text": "return a + b",
+                "index": 0,
+                "logprobs": {
+                    "tokens": ["return", " a", " +", " b"],
+                    "token_logprobs": [-0.01, -0.02, -0.03, -0.001],
+                    "top_logprobs": [{"return": -0.01}, {" a": -0.02}, {" +": -0.03}, {" b": -0.001}],
+                    "text_offset": [0, 6, 8, 10]
+                },
+                "finish_reason": "stop"
+            }],
+            "usage": {"prompt_tokens": 12, "completion_tokens": 4, "total_tokens": 16}
+        }))?;
+        assert_eq!(res.model, Model::Gpt3TurboInstruct);
+        assert_eq!(res.choices[0].text, "return a + b");
+        assert_eq!(res.choices[0].finish_reason, Some(FinishReason::Stop));
+        assert_eq!(res.choices[0].logprobs.as_ref().unwrap().tokens.len(), 4);
+        Ok(())
+    }
+}
@@ -1,14 +1,14 @@
+//!
+//! 用于生成图像的API构建
+//! 输入要生成的图像描述，让模型生成新的图像并且返回;
+//!
 
 use  serde::{Serialize,Deserialize};
 use reqwest::{Client,RequestBuilder};
-use crate::IntoRequest;
+use crate::{IntoRequest, TypedRequest};
 use derive_builder::Builder;
 
 
-/// 用于生成图像的API构建
-/// 输入要生成的图像描述，让模型生成新的图像并且返回;
-
-
 ///
 /// 图像生成API-请求体
 /// 
@@ -56,6 +56,11 @@ pub struct CreateImageRequest{
     #[builder(default,setter(strip_option,into))]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user: Option<String>,
+
+    /// 跳过响应缓存，直接请求接口(见 `OpenaiSdk::with_cache`)，不会发送给接口
+    #[builder(default)]
+    #[serde(skip)]
+    pub bypass_cache: bool,
 }
 // CreateImageRequest 构造方法
 impl CreateImageRequest{
@@ -77,6 +82,10 @@ impl IntoRequest for CreateImageRequest{
     }
 }
 
+impl TypedRequest for CreateImageRequest{
+    type Response = CreateImageResponse;
+}
+
 
 
 ///
@@ -109,7 +118,7 @@ pub struct ImageObject{
 
 /// 可以使用的模型枚举
 /// dall-e-3  更加强大
-#[derive(Debug,Clone,Copy,PartialEq,Eq,Default,Serialize,Deserialize)]
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash,Default,Serialize,Deserialize)]
 pub enum ImageModel {
     #[serde(rename = "dall-e-2")]
     DallE2,
@@ -122,7 +131,7 @@ pub enum ImageModel {
 /// 图像生成质量枚举
 /// hd`具有更高质量，但是仅 dall-e-3 模型支持此参数。
 /// 枚举值直接序列化为 首字母小写
-#[derive(Debug,Clone,Copy,PartialEq,Eq,Default,Serialize,Deserialize)]
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash,Default,Serialize,Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ImageQuality{
     Standard,
@@ -145,9 +154,14 @@ pub enum ImageResponseFormat {
 
 
 /// 生成的图像分辨率大小
+/// 对于 dall-e-2 模型，必须是 256x256 、 512x512 或 1024x1024 之一。
 /// 对于 dall-e-3 模型，必须是 1024x1024 、 1792x1024 或 1024x1792 之一。
-#[derive(Debug,Clone,Copy,PartialEq,Eq,Default,Serialize,Deserialize)]
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash,Default,Serialize,Deserialize)]
 pub enum ImageSize {
+    #[serde(rename = "256x256")]
+    Small,
+    #[serde(rename = "512x512")]
+    Medium,
     #[serde(rename = "1024x1024")]
     #[default]
     Large,
@@ -175,8 +189,8 @@ pub enum ImageStyle {
 /// 单元测试
 #[cfg(test)]
 mod test{
-    use std::fs;
     use crate::OpenaiSdk;
+    use crate::cassette::CassetteMode;
     use super::*;
     use anyhow::{Result, Ok};
     use serde_json::json;
@@ -220,26 +234,23 @@ mod test{
     }
 
 
-    /// 单元测试: 发送请求，生成图像，并且下载到本地
+    /// 单元测试: 发送请求，生成图像
+    /// 默认回放录制的响应，设置 OPENAI_CASSETTE=record 以及 OPENAI_API_KEY 时重新录制
     #[tokio::test]
-    #[ignore] // 跳过单元测试
     async fn create_image_should_work() -> Result<()>{
         // 获取环境变量中的openai api key
-        let api_key = std::env::var("OPENAI_API_KEYs")?;
+        let api_key = std::env::var("OPENAI_API_KEY").unwrap_or_default();
         // 构建sdk
-        let sdk = OpenaiSdk::new(api_key);
+        let cassette = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/cassettes/create_image.json");
+        let sdk = OpenaiSdk::new(api_key).with_cassette(cassette, CassetteMode::from_env())?;
         // 构建创建图像请求
-        let img_req = CreateImageRequest::new("");
+        let img_req = CreateImageRequest::new("a white siamese cat");
         // 发送请求
         let res = sdk.create_image(img_req).await?;
         assert_eq!(res.data.len(), 1);
         // 获取生成的图像信息
         let img = &res.data[0];
         assert!(img.url.is_some());
-        // 根据生成url，下载图片
-        let content = reqwest::get(img.url.as_ref().unwrap()).await?.bytes().await?;
-        // 将图片保存到本地
-        fs::write("/Users/zero/Desktop/dog1.png", content)?;
         println!("图片地址: {}",&img.url.clone().unwrap());
         Ok(())
     }
@@ -0,0 +1,142 @@
+use derive_builder::Builder;
+use serde::{Serialize, Deserialize};
+use reqwest::{Client, RequestBuilder};
+
+use crate::{IntoRequest, TypedRequest};
+use super::completion::Prompt;
+
+// 向量嵌入API(/v1/embeddings): 将文本转换为向量，用于检索、聚类、相似度计算等
+
+
+///
+/// 向量嵌入API-请求体
+///
+#[derive(Debug,Clone,Serialize,Builder)]
+pub struct EmbeddingRequest{
+    /// 要嵌入的文本，可以是单个字符串或者字符串数组(批量嵌入)
+    #[builder(setter(into))]
+    input: Prompt,
+
+    /// 要使用的模型ID
+    #[builder(default)]
+    model: EmbeddingModel,
+
+    /// 输出向量的维度，仅 text-embedding-3 及之后的模型支持
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<usize>,
+
+    /// 代表您的最终用户的唯一标识符
+    #[builder(default,setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+}
+
+// EmbeddingRequest 构造方法
+impl EmbeddingRequest {
+    pub fn new(input: impl Into<Prompt>) -> Self {
+        EmbeddingRequestBuilder::default()
+        .input(input)
+        .build()
+        .unwrap()
+    }
+}
+
+// EmbeddingRequest 实现 IntoRequest 特征，返回对应的网络请求构建器;
+impl IntoRequest for EmbeddingRequest{
+    /// 构建post请求，指定目标url
+    /// 将自身序列化为json格式，作为请求体
+    fn into_request(self, client: Client) -> RequestBuilder {
+        client.post("https://api.openai.com/v1/embeddings").json(&self)
+    }
+}
+
+impl TypedRequest for EmbeddingRequest{
+    type Response = EmbeddingResponse;
+}
+
+
+/// 向量嵌入模型枚举
+#[derive(Debug,Clone,Default,PartialEq, Eq,Hash,Serialize,Deserialize)]
+pub enum EmbeddingModel{
+    #[default]
+    #[serde(rename = "text-embedding-3-small")]
+    TextEmbedding3Small,
+    #[serde(rename = "text-embedding-3-large")]
+    TextEmbedding3Large,
+    #[serde(rename = "text-embedding-ada-002")]
+    TextEmbeddingAda002,
+    /// 未内置的模型ID，序列化时原样输出该字符串
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl EmbeddingModel {
+    /// 模型ID字符串，与序列化后的值一致
+    pub fn as_str(&self) -> &str {
+        match self {
+            EmbeddingModel::TextEmbedding3Small => "text-embedding-3-small",
+            EmbeddingModel::TextEmbedding3Large => "text-embedding-3-large",
+            EmbeddingModel::TextEmbeddingAda002 => "text-embedding-ada-002",
+            EmbeddingModel::Other(id) => id,
+        }
+    }
+}
+
+
+///
+/// 向量嵌入API-响应体
+///
+#[derive(Debug,Clone,Deserialize)]
+pub struct EmbeddingResponse{
+    /// 对象类型，始终为 list
+    pub object: String,
+    /// 嵌入结果，与输入的顺序一致
+    pub data: Vec<Embedding>,
+    /// 使用的模型ID
+    pub model: EmbeddingModel,
+    /// 令牌使用统计
+    pub usage: EmbeddingUsage,
+}
+
+/// 单个输入的嵌入结果
+#[derive(Debug,Clone,Deserialize)]
+pub struct Embedding{
+    /// 对象类型，始终为 embedding
+    pub object: String,
+    /// 嵌入向量
+    pub embedding: Vec<f32>,
+    /// 对应的输入在输入列表中的索引
+    pub index: usize,
+}
+
+/// 向量嵌入的令牌使用统计
+#[derive(Debug,Clone,Default,Deserialize)]
+pub struct EmbeddingUsage{
+    /// 输入的令牌数
+    pub prompt_tokens: usize,
+    /// 总令牌数
+    pub total_tokens: usize,
+}
+
+
+#[cfg(test)]
+mod tests{
+    use anyhow::Result;
+    use serde_json::json;
+    use super::*;
+
+    #[test]
+    fn embedding_request_should_serialize() -> Result<()>{
+        let req = EmbeddingRequestBuilder::default()
+            .input(vec!["hello", "world"])
+            .model(EmbeddingModel::TextEmbedding3Large)
+            .dimensions(256)
+            .build()?;
+        assert_eq!(
+            serde_json::to_value(&req)?,
+            json!({"input": ["hello", "world"], "model": "text-embedding-3-large", "dimensions": 256})
+        );
+        Ok(())
+    }
+}
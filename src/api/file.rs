@@ -0,0 +1,252 @@
+use std::{path::{Path, PathBuf}, time::Duration};
+
+use anyhow::{Result, anyhow};
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, TryStreamExt};
+use serde::{Serialize, Deserialize};
+use reqwest::{Body, Client, RequestBuilder, multipart::{Form, Part}};
+use tokio::io::AsyncReadExt;
+
+use crate::{IntoRequest, OpenaiSdk, TypedRequest};
+use super::common::{ApiRequest, ListResponse, DeleteResponse};
+
+// 文件API: 上传文件，供助手、向量库、批处理等接口使用
+
+/// 上传文件的请求超时时间，大文件上传需要的时间远超普通请求
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// 从本地文件分块读取时每块的大小
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+
+///
+/// 上传文件API-请求体，以 multipart/form-data 格式发送
+///
+#[derive(Debug,Clone)]
+pub struct UploadFileRequest{
+    /// 文件名
+    pub filename: String,
+    /// 文件内容
+    pub content: FileContent,
+    /// 文件用途
+    pub purpose: FilePurpose,
+}
+
+impl UploadFileRequest {
+    pub fn new(filename: impl Into<String>, content: impl Into<Bytes>, purpose: FilePurpose) -> Self {
+        Self { filename: filename.into(), content: FileContent::Bytes(content.into()), purpose }
+    }
+
+    /// 使用本地文件，发送时再分块读取，不会将整个文件读入内存;使用文件名作为上传的文件名
+    pub async fn from_path(path: impl AsRef<Path>, purpose: FilePurpose) -> Result<Self> {
+        let path = path.as_ref();
+        let filename = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| anyhow!("invalid file name: {}", path.display()))?;
+        let len = tokio::fs::metadata(path).await?.len();
+        let content = FileContent::File { path: path.to_path_buf(), len };
+        Ok(Self { filename: filename.to_string(), content, purpose })
+    }
+}
+
+///
+/// 上传的文件内容
+///
+#[derive(Debug,Clone)]
+pub enum FileContent{
+    /// 内存中的内容
+    Bytes(Bytes),
+    /// 本地文件及其大小，发送时分块读取
+    File{ path: PathBuf, len: u64 },
+}
+
+impl FileContent {
+    fn into_part(self) -> Part {
+        match self {
+            FileContent::Bytes(content) => Part::stream(content),
+            FileContent::File { path, len } => Part::stream_with_length(Body::wrap_stream(read_chunks(path)), len),
+        }
+    }
+}
+
+/// 打开文件并按 `UPLOAD_CHUNK_SIZE` 分块读取
+fn read_chunks(path: PathBuf) -> impl Stream<Item = std::io::Result<Bytes>> + Send + Sync + 'static {
+    futures::stream::once(tokio::fs::File::open(path))
+        .map_ok(|file| futures::stream::try_unfold(file, |mut file| async move {
+            let mut buf = BytesMut::with_capacity(UPLOAD_CHUNK_SIZE);
+            match file.read_buf(&mut buf).await? {
+                0 => Ok(None),
+                _ => Ok(Some((buf.freeze(), file))),
+            }
+        }))
+        .try_flatten()
+}
+
+// UploadFileRequest 实现 IntoRequest 特征，返回对应的网络请求构建器;
+impl IntoRequest for UploadFileRequest{
+    /// 构建post请求，指定目标url
+    /// 文件内容与用途作为 multipart 表单发送，使用较长的超时时间
+    fn into_request(self, client: Client) -> RequestBuilder {
+        let part = self.content.into_part().file_name(self.filename);
+        let form = Form::new()
+            .text("purpose", self.purpose.as_str())
+            .part("file", part);
+        client.post("https://api.openai.com/v1/files").multipart(form).timeout(UPLOAD_TIMEOUT)
+    }
+}
+
+impl TypedRequest for UploadFileRequest{
+    type Response = FileObject;
+}
+
+
+/// 文件用途枚举
+#[derive(Debug,Clone,Copy,Default,PartialEq, Eq,Serialize,Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilePurpose{
+    /// 助手以及向量库使用的文件
+    #[default]
+    Assistants,
+    /// 助手生成的文件
+    AssistantsOutput,
+    /// 批处理的输入文件
+    Batch,
+    /// 批处理的输出文件
+    BatchOutput,
+    /// 微调训练数据
+    #[serde(rename = "fine-tune")]
+    FineTune,
+    /// 图片输入
+    Vision,
+    /// 其他用途
+    UserData,
+}
+
+impl FilePurpose {
+    /// 用途字符串，与序列化后的值一致
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilePurpose::Assistants => "assistants",
+            FilePurpose::AssistantsOutput => "assistants_output",
+            FilePurpose::Batch => "batch",
+            FilePurpose::BatchOutput => "batch_output",
+            FilePurpose::FineTune => "fine-tune",
+            FilePurpose::Vision => "vision",
+            FilePurpose::UserData => "user_data",
+        }
+    }
+}
+
+
+///
+/// 文件对象
+///
+#[derive(Debug,Clone,Deserialize)]
+pub struct FileObject{
+    /// 文件ID
+    pub id: String,
+    /// 对象类型，始终为 file
+    pub object: String,
+    /// 文件大小(字节)
+    pub bytes: u64,
+    /// 创建时的 Unix 时间戳（以秒为单位）
+    pub created_at: u64,
+    /// 文件名
+    pub filename: String,
+    /// 文件用途，未知的用途保留为原始字符串
+    pub purpose: String,
+}
+
+
+// 文件相关的 api 请求
+impl OpenaiSdk {
+
+    ///
+    /// 上传文件
+    ///
+    pub async fn upload_file(&self, req: UploadFileRequest) -> Result<FileObject>{
+        self.send_json(req).await
+    }
+
+    ///
+    /// 获取文件列表
+    ///
+    pub async fn list_files(&self) -> Result<ListResponse<FileObject>>{
+        self.send_json(ApiRequest::get("files")).await
+    }
+
+    ///
+    /// 获取文件信息
+    ///
+    pub async fn retrieve_file(&self, file_id: &str) -> Result<FileObject>{
+        self.send_json(ApiRequest::get(format!("files/{file_id}"))).await
+    }
+
+    ///
+    /// 删除文件
+    ///
+    pub async fn delete_file(&self, file_id: &str) -> Result<DeleteResponse>{
+        self.send_json(ApiRequest::delete(format!("files/{file_id}"))).await
+    }
+
+    ///
+    /// 下载文件内容
+    ///
+    pub async fn file_content(&self, file_id: &str) -> Result<Bytes>{
+        let res = self.send(ApiRequest::get(format!("files/{file_id}/content"))).await?;
+        Ok(crate::error_for_status(res).await?.bytes().await?)
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[tokio::test]
+    async fn upload_file_request_should_be_multipart() -> Result<()>{
+        let sdk = OpenaiSdk::new("sk-test".into());
+        let req = UploadFileRequest::new("guide.md", "# Guide", FilePurpose::Assistants);
+        let req = sdk.prepare_request(req).await?;
+        assert_eq!(req.url().as_str(), "https://api.openai.com/v1/files");
+        let content_type = req.headers()[reqwest::header::CONTENT_TYPE].to_str()?;
+        assert!(content_type.starts_with("multipart/form-data; boundary="));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_from_path_should_stream_file() -> Result<()>{
+        let path = std::env::temp_dir().join(format!("openai-sdk-upload-{}.md", std::process::id()));
+        let content = "# Guide\n".repeat(UPLOAD_CHUNK_SIZE / 4);
+        std::fs::write(&path, &content)?;
+        let req = UploadFileRequest::from_path(&path, FilePurpose::Assistants).await?;
+        assert!(matches!(req.content, FileContent::File { len, .. } if len == content.len() as u64));
+
+        let req = OpenaiSdk::new("sk-test".into()).prepare_request(req).await?;
+        assert!(req.body().unwrap().as_bytes().is_none());
+        assert_eq!(req.timeout(), Some(&UPLOAD_TIMEOUT));
+        let chunks: Vec<Bytes> = read_chunks(path.clone()).try_collect().await?;
+        std::fs::remove_file(&path)?;
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), content.as_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn file_object_should_deserialize() -> Result<()>{
+        let file: FileObject = serde_json::from_value(serde_json::json!({
+            "id": "file-abc123",
+            "object": "file",
+            "bytes": 120000,
+            "created_at": 1677610602,
+            "filename": "guide.md",
+            "purpose": "assistants"
+        }))?;
+        assert_eq!(file.filename, "guide.md");
+        for purpose in [FilePurpose::Assistants, FilePurpose::FineTune, FilePurpose::BatchOutput] {
+            assert_eq!(serde_json::to_value(purpose)?, purpose.as_str());
+        }
+        Ok(())
+    }
+}
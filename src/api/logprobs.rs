@@ -0,0 +1,132 @@
+use serde::{Serialize, Deserialize};
+
+//
+// 聊天完成选项中的 token 对数概率信息，以及基于对数概率的置信度计算
+//
+
+
+/// 选项的对数概率信息
+/// 仅当请求参数 `logprobs` 为 true 时返回;
+#[derive(Debug,Clone,Default,Serialize,Deserialize)]
+pub struct ChatCompletionLogprobs{
+    /// 消息内容中每个 token 的对数概率列表
+    #[serde(default)]
+    pub content: Option<Vec<TokenLogprob>>,
+    /// 拒绝消息中每个 token 的对数概率列表
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refusal: Option<Vec<TokenLogprob>>,
+}
+
+/// 单个 token 的对数概率
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct TokenLogprob{
+    /// token 文本
+    pub token: String,
+    /// 该 token 的对数概率(自然对数)
+    pub logprob: f64,
+    /// token 的 UTF-8 字节表示;当一个字符被拆分为多个 token 时，需要合并字节才能还原文本;
+    /// 如果 token 没有字节表示，则为 null
+    #[serde(default)]
+    pub bytes: Option<Vec<u8>>,
+    /// 该位置上概率最高的候选 token 列表，数量由请求参数 `top_logprobs` 决定
+    #[serde(default)]
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+/// 候选 token 的对数概率
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct TopLogprob{
+    /// token 文本
+    pub token: String,
+    /// 该 token 的对数概率(自然对数)
+    pub logprob: f64,
+    /// token 的 UTF-8 字节表示
+    #[serde(default)]
+    pub bytes: Option<Vec<u8>>,
+}
+
+
+impl TokenLogprob {
+    /// 该 token 的概率，取值 0~1
+    pub fn probability(&self) -> f64 {
+        self.logprob.exp()
+    }
+}
+
+impl TopLogprob {
+    /// 该候选 token 的概率，取值 0~1
+    pub fn probability(&self) -> f64 {
+        self.logprob.exp()
+    }
+}
+
+
+impl ChatCompletionLogprobs {
+
+    /// 消息内容的 token 列表，没有返回时为空
+    pub fn tokens(&self) -> &[TokenLogprob] {
+        self.content.as_deref().unwrap_or_default()
+    }
+
+    /// 整个序列的对数概率，即所有 token 对数概率之和
+    pub fn sequence_logprob(&self) -> f64 {
+        self.tokens().iter().map(|t| t.logprob).sum()
+    }
+
+    /// 整个序列的联合概率，取值 0~1
+    pub fn sequence_probability(&self) -> f64 {
+        self.sequence_logprob().exp()
+    }
+
+    /// 序列的困惑度: exp(-平均对数概率)，越接近1表示模型越确定;
+    /// 没有 token 时返回 None
+    pub fn perplexity(&self) -> Option<f64> {
+        let tokens = self.tokens();
+        if tokens.is_empty() {
+            return None;
+        }
+        Some((-self.sequence_logprob() / tokens.len() as f64).exp())
+    }
+
+    /// 每个 token 的置信度(概率)，按 token 顺序返回
+    pub fn token_confidences(&self) -> Vec<(&str, f64)> {
+        self.tokens()
+            .iter()
+            .map(|t| (t.token.as_str(), t.probability()))
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn logprobs() -> ChatCompletionLogprobs {
+        serde_json::from_value(serde_json::json!({
+            "content": [
+                {"token": "yes", "logprob": -0.1, "bytes": [121, 101, 115], "top_logprobs": []},
+                {"token": ".", "logprob": -0.3, "bytes": [46], "top_logprobs": []}
+            ],
+            "refusal": null
+        })).unwrap()
+    }
+
+    #[test]
+    fn sequence_metrics_should_work(){
+        let logprobs = logprobs();
+        assert!((logprobs.sequence_logprob() + 0.4).abs() < 1e-9);
+        assert!((logprobs.sequence_probability() - (-0.4f64).exp()).abs() < 1e-9);
+        assert!((logprobs.perplexity().unwrap() - 0.2f64.exp()).abs() < 1e-9);
+        let confidences = logprobs.token_confidences();
+        assert_eq!(confidences[0].0, "yes");
+        assert!((confidences[0].1 - (-0.1f64).exp()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn empty_logprobs_should_have_no_perplexity(){
+        let logprobs = ChatCompletionLogprobs::default();
+        assert!(logprobs.perplexity().is_none());
+        assert_eq!(logprobs.sequence_probability(), 1.0);
+    }
+}
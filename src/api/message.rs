@@ -1,8 +1,10 @@
-use serde::{Serialize, Deserialize};
+//!
+//! 各种类型的对话消息实体
+//!
+
+use std::collections::HashMap;
 
-///
-/// 各种类型的对话消息实体
-/// 
+use serde::{Serialize, Deserialize};
 
 
 /// 聊天消息类型枚举
@@ -25,20 +27,20 @@ pub enum ChatMessage {
 #[derive(Debug,Clone,Serialize)]
 pub struct SystemMessage{
     /// 系统消息的内容。
-    content: String,
+    pub(crate) content: String,
     /// 参与者的可选名称。提供模型信息以区分相同角色的参与者。
     #[serde(skip_serializing_if = "Option::is_none")]
-    name: Option<String>
+    pub(crate) name: Option<String>
 }
 
 /// 用户消息，一般指用户向模型系统发送的消息;
 #[derive(Debug,Clone,Serialize)]
 pub struct UserMessage{
     /// 用户消息的内容。
-    content: String,
+    pub(crate) content: String,
     /// 参与者的可选名称。提供模型信息以区分相同角色的参与者。
     #[serde(skip_serializing_if = "Option::is_none")]
-    name: Option<String>
+    pub(crate) name: Option<String>
 }
 
 
@@ -46,22 +48,27 @@ pub struct UserMessage{
 #[derive(Debug,Clone,Serialize,Deserialize)]
 pub struct AssistantMessage{
     /// 消息的内容。
-    pub content: String,
+    /// 当模型进行工具调用时，API 会返回 null 内容;
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub content: Option<String>,
     /// 参与者的可选名称。提供模型信息以区分相同角色的参与者。
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub name: Option<String>,
     /// 模型生成的工具调用信息，例如函数调用。
-    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    #[serde(skip_serializing_if = "Vec::is_empty", default, deserialize_with = "null_as_default")]
     pub tool_calls: Vec<ToolCall>,
+    /// 未建模的其他消息字段(例如 refusal)，原样保留
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 /// 工具消息
 #[derive(Debug,Clone,Serialize)]
 pub struct ToolMessage{
     /// 工具消息的内容。
-    content: String,
+    pub(crate) content: String,
     /// 此消息正在响应的工具调用。
-    tool_call_id: String,
+    pub(crate) tool_call_id: String,
 }
 
 
@@ -82,6 +89,33 @@ impl ChatMessage {
         })
     }
 
+    /// 文本消息内容(可修改)，助手消息没有内容时返回 None
+    pub fn content_mut(&mut self) -> Option<&mut String>{
+        match self {
+            ChatMessage::System(message) => Some(&mut message.content),
+            ChatMessage::User(message) => Some(&mut message.content),
+            ChatMessage::Assistant(message) => message.content.as_mut(),
+            ChatMessage::Tool(message) => Some(&mut message.content),
+        }
+    }
+
+    /// 创建助手消息，例如恢复历史对话时添加模型之前的回复
+    pub fn new_assistant(content: impl Into<String>) -> ChatMessage{
+        ChatMessage::Assistant(AssistantMessage {
+            content: Some(content.into()),
+            name: None,
+            tool_calls: Vec::new(),
+            extra: HashMap::new(),
+        })
+    }
+
+    /// 创建工具消息，作为对指定工具调用的响应
+    pub fn new_tool(content: impl Into<String>, tool_call_id: impl Into<String>) -> ChatMessage{
+        ChatMessage::Tool(ToolMessage {
+            content: content.into(),
+            tool_call_id: tool_call_id.into(),
+        })
+    }
 
     /// 获取name
     fn get_name(name: &str) -> Option<String>{
@@ -94,6 +128,16 @@ impl ChatMessage {
 }
 
 
+/// 将 null 值反序列化为类型的默认值
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+
 /// 辅助工具信息
 #[derive(Debug,Clone,Serialize,Deserialize)]
 pub struct ToolCall{
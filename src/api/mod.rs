@@ -3,8 +3,32 @@
 //! 
 
 // 统一定义模块，并且对外公开
+mod assistants;
+mod audio;
+mod batch;
 mod chat_completion;
+mod common;
+mod completion;
 mod create_image;
+mod embedding;
+mod file;
+mod logprobs;
 mod message;
+mod models;
+mod responses;
+mod vector_store;
+pub use assistants::*;
+pub use audio::*;
+pub use batch::*;
 pub use chat_completion::*;
-pub use create_image::*;
\ No newline at end of file
+pub use common::{ListQuery, ListOrder, ListResponse, DeleteResponse, PollOptions, DEFAULT_POLL_MAX_WAIT};
+pub use completion::*;
+pub use create_image::*;
+pub use embedding::*;
+pub use file::*;
+pub use logprobs::*;
+pub use message::*;
+pub use models::*;
+pub use responses::*;
+pub use vector_store::*;
+
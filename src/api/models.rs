@@ -0,0 +1,40 @@
+use anyhow::Result;
+use serde::Deserialize;
+
+use crate::OpenaiSdk;
+use super::common::{ApiRequest, ListResponse};
+
+// 模型API(/v1/models): 查询当前账号可以使用的模型
+
+
+/// 模型对象
+#[derive(Debug,Clone,Deserialize)]
+pub struct ModelObject{
+    /// 模型ID，可以作为请求中的 model 参数
+    pub id: String,
+    /// 对象类型，始终为 model
+    pub object: String,
+    /// 创建时的 Unix 时间戳（以秒为单位）
+    pub created: u64,
+    /// 模型的所有者
+    pub owned_by: String,
+}
+
+
+// 模型相关的 api 请求
+impl OpenaiSdk {
+
+    ///
+    /// 获取可以使用的模型列表
+    ///
+    pub async fn list_models(&self) -> Result<ListResponse<ModelObject>>{
+        self.send_json(ApiRequest::get("models")).await
+    }
+
+    ///
+    /// 获取模型信息
+    ///
+    pub async fn retrieve_model(&self, model: &str) -> Result<ModelObject>{
+        self.send_json(ApiRequest::get(format!("models/{model}"))).await
+    }
+}
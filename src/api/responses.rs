@@ -0,0 +1,766 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use derive_builder::Builder;
+use serde::{Serialize, Deserialize};
+use reqwest::{Client, RequestBuilder};
+
+use crate::{IntoRequest, OpenaiSdk, TypedRequest};
+use crate::stream::EventStream;
+use super::chat_completion::{Model, Tool, ReasoningEffort};
+use super::message::{ChatMessage, AssistantMessage, ToolCall, ToolType, CallFunction};
+
+// Responses API(/v1/responses)
+// 新一代的生成接口: 输入为一组输入项，输出为一组类型化的输出项，支持内置工具以及通过 previous_response_id 串联多轮对话
+
+
+///
+/// 创建响应API-请求体
+///
+#[derive(Debug,Clone,Serialize,Builder)]
+#[builder(build_fn(validate = "Self::validate"))]
+pub struct ResponsesRequest{
+    /// 模型输入，可以是一段文本或者一组输入项
+    #[builder(setter(into))]
+    input: ResponseInput,
+
+    /// 要使用的模型ID枚举
+    #[builder(default)]
+    model: Model,
+
+    /// 系统(开发者)指令，不会随 previous_response_id 带入下一轮
+    #[builder(default,setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instructions: Option<String>,
+
+    /// 上一轮响应的ID，用于串联多轮对话，服务端会自动带上之前的上下文
+    #[builder(default,setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous_response_id: Option<String>,
+
+    /// 模型可以使用的工具，包括函数以及网页搜索、文件检索等内置工具
+    #[builder(default,setter(into))]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<ResponseTool>,
+
+    /// 是否允许模型并行调用多个工具
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parallel_tool_calls: Option<bool>,
+
+    /// 文本输出的配置，例如通过 JSON Schema 约束结构化输出
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<TextConfig>,
+
+    /// 推理模型(o系列)的推理配置
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning: Option<ReasoningConfig>,
+
+    /// 生成的最大令牌数上限，包括可见的输出令牌和推理令牌
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<usize>,
+
+    /// 采样温度，取值 0~2
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+
+    /// 核采样的累积概率阈值，取值 0~1
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+
+    /// 是否在服务端保存响应，保存后才能被 previous_response_id 引用
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    store: Option<bool>,
+
+    /// 是否以流式方式返回结果
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+
+    /// 附加到响应上的键值对，最多 16 个
+    #[builder(default,setter(into))]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    metadata: HashMap<String, String>,
+
+    /// 代表您的最终用户的唯一标识符
+    #[builder(default,setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+}
+
+// ResponsesRequest 构造方法
+impl ResponsesRequest {
+    pub fn new(input: impl Into<ResponseInput>, model: Model) -> Self {
+        ResponsesRequestBuilder::default()
+        .input(input)
+        .model(model)
+        .build()
+        .unwrap()
+    }
+
+    /// 开启流式返回
+    pub(crate) fn streaming(mut self) -> Self {
+        self.stream = Some(true);
+        self
+    }
+}
+
+// 构建请求时校验各参数的取值范围
+impl ResponsesRequestBuilder {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(Some(temperature)) = self.temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                return Err(format!("temperature must be between 0 and 2, got {temperature}"));
+            }
+        }
+        if let Some(Some(top_p)) = self.top_p {
+            if !(0.0..=1.0).contains(&top_p) {
+                return Err(format!("top_p must be between 0 and 1, got {top_p}"));
+            }
+        }
+        if let Some(metadata) = &self.metadata {
+            if metadata.len() > 16 {
+                return Err(format!("metadata must contain at most 16 pairs, got {}", metadata.len()));
+            }
+        }
+        Ok(())
+    }
+}
+
+// ResponsesRequest 实现 IntoRequest 特征，返回对应的网络请求构建器;
+impl IntoRequest for ResponsesRequest{
+    /// 构建post请求，指定目标url
+    /// 将自身序列化为json格式，作为请求体
+    fn into_request(self, client: Client) -> RequestBuilder {
+        client.post("https://api.openai.com/v1/responses").json(&self)
+    }
+
+    fn is_stream(&self) -> bool {
+        self.stream == Some(true)
+    }
+}
+
+impl TypedRequest for ResponsesRequest{
+    type Response = Response;
+}
+
+
+/// 模型输入，可以是单个文本或者一组输入项
+#[derive(Debug,Clone,Serialize)]
+#[serde(untagged)]
+pub enum ResponseInput{
+    /// 文本输入，等价于一条用户消息
+    Text(String),
+    /// 输入项列表
+    Items(Vec<InputItem>),
+}
+
+impl From<&str> for ResponseInput {
+    fn from(value: &str) -> Self {
+        ResponseInput::Text(value.into())
+    }
+}
+
+impl From<String> for ResponseInput {
+    fn from(value: String) -> Self {
+        ResponseInput::Text(value)
+    }
+}
+
+impl From<Vec<InputItem>> for ResponseInput {
+    fn from(value: Vec<InputItem>) -> Self {
+        ResponseInput::Items(value)
+    }
+}
+
+/// 聊天消息列表按照 `InputItem::from_chat_message` 转换为输入项
+impl From<Vec<ChatMessage>> for ResponseInput {
+    fn from(value: Vec<ChatMessage>) -> Self {
+        ResponseInput::Items(value.into_iter().flat_map(InputItem::from_chat_message).collect())
+    }
+}
+
+
+/// 输入项，使用 type 区分类型
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputItem{
+    /// 对话消息
+    Message{
+        role: InputRole,
+        content: String,
+    },
+    /// 模型之前发起的函数调用
+    FunctionCall{
+        call_id: String,
+        name: String,
+        arguments: String,
+    },
+    /// 函数调用的输出
+    FunctionCallOutput{
+        call_id: String,
+        output: String,
+    },
+}
+
+/// 输入消息的角色
+#[derive(Debug,Clone,Copy,PartialEq, Eq,Serialize,Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputRole{
+    User,
+    Assistant,
+    System,
+    Developer,
+}
+
+impl InputItem {
+    /// 创建消息输入项
+    pub fn message(role: InputRole, content: impl Into<String>) -> Self {
+        InputItem::Message { role, content: content.into() }
+    }
+
+    /// 创建函数调用的输出
+    pub fn function_output(call_id: impl Into<String>, output: impl Into<String>) -> Self {
+        InputItem::FunctionCallOutput { call_id: call_id.into(), output: output.into() }
+    }
+
+    /// 将聊天消息转换为输入项;
+    /// 携带工具调用的辅助消息会拆分为一条消息(有内容时)以及每个工具调用对应的 function_call 输入项
+    pub fn from_chat_message(message: ChatMessage) -> Vec<InputItem> {
+        match message {
+            ChatMessage::System(m) => vec![InputItem::message(InputRole::System, m.content)],
+            ChatMessage::User(m) => vec![InputItem::message(InputRole::User, m.content)],
+            ChatMessage::Tool(m) => vec![InputItem::function_output(m.tool_call_id, m.content)],
+            ChatMessage::Assistant(m) => {
+                let mut items: Vec<InputItem> = m.content
+                    .filter(|c| !c.is_empty())
+                    .map(|c| InputItem::message(InputRole::Assistant, c))
+                    .into_iter()
+                    .collect();
+                items.extend(m.tool_calls.into_iter().map(InputItem::from));
+                items
+            }
+        }
+    }
+}
+
+impl From<ToolCall> for InputItem {
+    fn from(call: ToolCall) -> Self {
+        InputItem::FunctionCall { call_id: call.id, name: call.function.name, arguments: call.function.arguments }
+    }
+}
+
+
+/// 模型可以使用的工具
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseTool{
+    /// 自定义函数，字段直接位于工具对象上
+    Function{
+        name: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        description: String,
+        #[serde(default)]
+        parameters: serde_json::Value,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        strict: Option<bool>,
+    },
+    /// 内置的网页搜索
+    WebSearchPreview{
+        /// 搜索上下文的大小: low、medium、high
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        search_context_size: Option<String>,
+    },
+    /// 内置的向量库文件检索
+    FileSearch{
+        vector_store_ids: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_num_results: Option<u32>,
+    },
+    /// 内置的代码解释器
+    CodeInterpreter{
+        /// 运行代码的容器，例如 `{"type": "auto"}`
+        container: serde_json::Value,
+    },
+}
+
+impl ResponseTool {
+    /// 网页搜索工具
+    pub fn web_search() -> Self {
+        ResponseTool::WebSearchPreview { search_context_size: None }
+    }
+
+    /// 在指定向量库中检索的文件检索工具
+    pub fn file_search(vector_store_ids: Vec<String>) -> Self {
+        ResponseTool::FileSearch { vector_store_ids, max_num_results: None }
+    }
+
+    /// 使用自动创建容器的代码解释器工具
+    pub fn code_interpreter() -> Self {
+        ResponseTool::CodeInterpreter { container: serde_json::json!({"type": "auto"}) }
+    }
+}
+
+impl From<Tool> for ResponseTool {
+    fn from(tool: Tool) -> Self {
+        let function = tool.function_info();
+        ResponseTool::Function {
+            name: function.name().into(),
+            description: function.description().into(),
+            parameters: function.parameters().clone(),
+            strict: None,
+        }
+    }
+}
+
+
+/// 文本输出配置
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct TextConfig{
+    /// 输出格式
+    pub format: TextFormat,
+}
+
+impl TextConfig {
+    /// 按照 JSON Schema 输出结构化的结果(严格模式)
+    pub fn json_schema(name: impl Into<String>, schema: serde_json::Value) -> Self {
+        Self { format: TextFormat::JsonSchema { name: name.into(), schema, description: None, strict: Some(true) } }
+    }
+}
+
+/// 文本输出格式
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TextFormat{
+    /// 普通文本
+    Text,
+    /// 任意 Json 对象
+    JsonObject,
+    /// 符合指定 JSON Schema 的 Json
+    JsonSchema{
+        name: String,
+        schema: serde_json::Value,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        strict: Option<bool>,
+    },
+}
+
+/// 推理配置
+#[derive(Debug,Clone,Default,Serialize,Deserialize)]
+pub struct ReasoningConfig{
+    /// 推理强度
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effort: Option<ReasoningEffort>,
+    /// 是否返回推理过程的摘要，以及摘要的详细程度
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<ReasoningSummary>,
+}
+
+/// 推理摘要的详细程度
+#[derive(Debug,Clone,Copy,PartialEq, Eq,Serialize,Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReasoningSummary{
+    Auto,
+    Concise,
+    Detailed,
+}
+
+
+
+///
+/// 创建响应API-响应体
+///
+#[derive(Debug,Clone,Deserialize)]
+pub struct Response{
+    /// 响应ID，可以作为下一轮请求的 previous_response_id
+    pub id: String,
+    /// 对象类型，始终为 response
+    pub object: String,
+    /// 创建时的 Unix 时间戳（以秒为单位）
+    pub created_at: u64,
+    /// 响应状态
+    pub status: ResponseStatus,
+    /// 使用的模型ID
+    pub model: Model,
+    /// 输出项列表
+    #[serde(default)]
+    pub output: Vec<OutputItem>,
+    /// 上一轮响应的ID
+    #[serde(default)]
+    pub previous_response_id: Option<String>,
+    /// 令牌使用统计
+    #[serde(default)]
+    pub usage: Option<ResponseUsage>,
+    /// 失败时的错误信息
+    #[serde(default)]
+    pub error: Option<serde_json::Value>,
+    /// 未建模的其他响应字段，原样保留
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl Response {
+    /// 所有消息输出项中的文本，按顺序拼接
+    pub fn output_text(&self) -> String {
+        self.output.iter().filter_map(OutputItem::text).collect()
+    }
+
+    /// 模型发起的函数调用
+    pub fn tool_calls(&self) -> Vec<ToolCall> {
+        self.output.iter().filter_map(OutputItem::tool_call).collect()
+    }
+
+    /// 将输出转换为一条辅助消息，便于追加到聊天消息列表中
+    pub fn to_chat_message(&self) -> ChatMessage {
+        let text = self.output_text();
+        ChatMessage::Assistant(AssistantMessage {
+            content: (!text.is_empty()).then_some(text),
+            name: None,
+            tool_calls: self.tool_calls(),
+            extra: HashMap::new(),
+        })
+    }
+}
+
+/// 响应状态
+#[derive(Debug,Clone,PartialEq, Eq,Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseStatus{
+    Queued,
+    InProgress,
+    Completed,
+    Incomplete,
+    Failed,
+    Cancelled,
+    /// 未内置的状态，保留原始字符串
+    #[serde(untagged)]
+    Other(String),
+}
+
+/// 令牌使用统计
+#[derive(Debug,Clone,Default,Deserialize)]
+pub struct ResponseUsage{
+    /// 输入令牌数
+    pub input_tokens: usize,
+    /// 输出令牌数，包括推理令牌
+    pub output_tokens: usize,
+    /// 总令牌数
+    pub total_tokens: usize,
+    /// 未建模的其他统计字段(例如 output_tokens_details)，原样保留
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+
+/// 输出项，使用 type 区分类型
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputItem{
+    /// 模型生成的消息
+    Message{
+        id: String,
+        #[serde(default)]
+        content: Vec<OutputContent>,
+    },
+    /// 模型发起的函数调用
+    FunctionCall{
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        call_id: String,
+        name: String,
+        arguments: String,
+    },
+    /// 推理过程，请求了推理摘要时包含摘要文本
+    Reasoning{
+        id: String,
+        #[serde(default)]
+        summary: Vec<ReasoningSummaryText>,
+    },
+    /// 内置网页搜索的调用
+    WebSearchCall{
+        id: String,
+        #[serde(default)]
+        status: Option<String>,
+    },
+    /// 内置文件检索的调用
+    FileSearchCall{
+        id: String,
+        #[serde(default)]
+        queries: Vec<String>,
+        #[serde(default)]
+        results: Option<serde_json::Value>,
+    },
+    /// 未内置的输出项类型
+    #[serde(other)]
+    Unknown,
+}
+
+impl OutputItem {
+    /// 消息输出项中的文本，其他类型返回 None
+    pub fn text(&self) -> Option<String> {
+        match self {
+            OutputItem::Message { content, .. } => Some(content.iter().filter_map(|c| match c {
+                OutputContent::OutputText { text, .. } => Some(text.as_str()),
+                _ => None,
+            }).collect()),
+            _ => None,
+        }
+    }
+
+    /// 函数调用输出项对应的工具调用，其他类型返回 None
+    pub fn tool_call(&self) -> Option<ToolCall> {
+        match self {
+            OutputItem::FunctionCall { call_id, name, arguments, .. } => Some(ToolCall {
+                id: call_id.clone(),
+                r#type: ToolType::Function,
+                function: CallFunction { name: name.clone(), arguments: arguments.clone() },
+            }),
+            _ => None,
+        }
+    }
+
+    /// 将输出项作为下一轮的输入项，用于不使用 previous_response_id 时手动维护上下文;
+    /// 推理、内置工具调用等无法转换的输出项返回 None
+    pub fn to_input(&self) -> Option<InputItem> {
+        match self {
+            OutputItem::Message { .. } => self.text().map(|text| InputItem::message(InputRole::Assistant, text)),
+            OutputItem::FunctionCall { .. } => self.tool_call().map(InputItem::from),
+            _ => None,
+        }
+    }
+}
+
+impl From<ToolCall> for OutputItem {
+    fn from(call: ToolCall) -> Self {
+        OutputItem::FunctionCall { id: None, call_id: call.id, name: call.function.name, arguments: call.function.arguments }
+    }
+}
+
+/// 消息输出项的内容
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputContent{
+    /// 输出文本，annotations 为文件引用、网址引用等标注
+    OutputText{
+        text: String,
+        #[serde(default)]
+        annotations: Vec<serde_json::Value>,
+    },
+    /// 模型拒绝回答
+    Refusal{
+        refusal: String,
+    },
+    /// 未内置的内容类型
+    #[serde(other)]
+    Unknown,
+}
+
+/// 推理摘要文本
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct ReasoningSummaryText{
+    /// 摘要文本
+    pub text: String,
+}
+
+
+/// 流式返回时的事件，使用 type 区分类型
+#[derive(Debug,Clone,Deserialize)]
+#[serde(tag = "type")]
+pub enum ResponseStreamEvent{
+    /// 响应已创建
+    #[serde(rename = "response.created")]
+    Created{ response: Box<Response> },
+    /// 响应生成中
+    #[serde(rename = "response.in_progress")]
+    InProgress{ response: Box<Response> },
+    /// 响应已完成，包含完整的输出
+    #[serde(rename = "response.completed")]
+    Completed{ response: Box<Response> },
+    /// 响应未完成，例如达到了 max_output_tokens
+    #[serde(rename = "response.incomplete")]
+    Incomplete{ response: Box<Response> },
+    /// 响应失败
+    #[serde(rename = "response.failed")]
+    Failed{ response: Box<Response> },
+    /// 新增了一个输出项
+    #[serde(rename = "response.output_item.added")]
+    OutputItemAdded{ output_index: usize, item: OutputItem },
+    /// 输出项已完成
+    #[serde(rename = "response.output_item.done")]
+    OutputItemDone{ output_index: usize, item: OutputItem },
+    /// 输出文本的增量
+    #[serde(rename = "response.output_text.delta")]
+    OutputTextDelta{ item_id: String, output_index: usize, content_index: usize, delta: String },
+    /// 输出文本已完成
+    #[serde(rename = "response.output_text.done")]
+    OutputTextDone{ item_id: String, output_index: usize, content_index: usize, text: String },
+    /// 函数调用参数的增量
+    #[serde(rename = "response.function_call_arguments.delta")]
+    FunctionCallArgumentsDelta{ item_id: String, output_index: usize, delta: String },
+    /// 函数调用参数已完成
+    #[serde(rename = "response.function_call_arguments.done")]
+    FunctionCallArgumentsDone{ item_id: String, output_index: usize, arguments: String },
+    /// 推理摘要的增量
+    #[serde(rename = "response.reasoning_summary_text.delta")]
+    ReasoningSummaryTextDelta{ item_id: String, output_index: usize, summary_index: usize, delta: String },
+    /// 流中的错误
+    #[serde(rename = "error")]
+    Error{
+        #[serde(default)]
+        code: Option<String>,
+        message: String,
+        #[serde(default)]
+        param: Option<String>,
+    },
+    /// 未内置的事件类型
+    #[serde(other)]
+    Unknown,
+}
+
+
+// Responses 相关的 api 请求
+impl OpenaiSdk {
+
+    ///
+    /// 创建响应
+    ///
+    pub async fn create_response(&self, req: ResponsesRequest) -> Result<Response>{
+        self.send_json(req).await
+    }
+
+    ///
+    /// 创建响应，以流式方式逐个返回事件，最后一个事件为 response.completed(或 incomplete、failed)
+    ///
+    pub async fn create_response_stream(&self, req: ResponsesRequest) -> Result<EventStream<ResponseStreamEvent>>{
+        let res = self.send(req.streaming()).await?;
+        self.event_stream(res).await
+    }
+}
+
+
+/// 单元测试
+#[cfg(test)]
+mod tests{
+    use anyhow::{Result, Ok};
+    use futures::StreamExt;
+    use serde_json::json;
+    use super::*;
+
+    #[test]
+    fn responses_request_should_serialize() -> Result<()>{
+        let tool = Tool::function("get_weather", "Get the weather", json!({"type": "object"}));
+        let req = ResponsesRequestBuilder::default()
+            .model(Model::Other("o4-mini".into()))
+            .input("What's the weather in Paris?")
+            .previous_response_id("resp_1")
+            .tools(vec![tool.into(), ResponseTool::web_search()])
+            .text(TextConfig::json_schema("weather", json!({"type": "object"})))
+            .reasoning(ReasoningConfig { effort: Some(ReasoningEffort::Low), summary: Some(ReasoningSummary::Auto) })
+            .build()?;
+        assert_eq!(
+            serde_json::to_value(&req)?,
+            json!({
+                "input": "What's the weather in Paris?",
+                "model": "o4-mini",
+                "previous_response_id": "resp_1",
+                "tools": [
+                    {"type": "function", "name": "get_weather", "description": "Get the weather", "parameters": {"type": "object"}},
+                    {"type": "web_search_preview"}
+                ],
+                "text": {"format": {"type": "json_schema", "name": "weather", "schema": {"type": "object"}, "strict": true}},
+                "reasoning": {"effort": "low", "summary": "auto"}
+            })
+        );
+        assert!(ResponsesRequestBuilder::default().input("a").temperature(3.0).build().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn chat_messages_should_convert_to_input_items() -> Result<()>{
+        let call = ToolCall {
+            id: "call_1".into(),
+            r#type: ToolType::Function,
+            function: CallFunction { name: "get_weather".into(), arguments: "{}".into() },
+        };
+        let messages = vec![
+            ChatMessage::new_system("Be brief.", ""),
+            ChatMessage::new_user("Weather?", ""),
+            ChatMessage::Assistant(AssistantMessage { content: None, name: None, tool_calls: vec![call], extra: HashMap::new() }),
+            ChatMessage::new_tool("sunny", "call_1"),
+        ];
+        let ResponseInput::Items(items) = ResponseInput::from(messages) else { unreachable!() };
+        assert_eq!(
+            serde_json::to_value(&items)?,
+            json!([
+                {"type": "message", "role": "system", "content": "Be brief."},
+                {"type": "message", "role": "user", "content": "Weather?"},
+                {"type": "function_call", "call_id": "call_1", "name": "get_weather", "arguments": "{}"},
+                {"type": "function_call_output", "call_id": "call_1", "output": "sunny"}
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn response_should_deserialize() -> Result<()>{
+        let res: Response = serde_json::from_value(json!({
+            "id": "resp_2",
+            "object": "response",
+            "created_at": 1741476542,
+            "status": "completed",
+            "model": "o4-mini-2025-04-16",
+            "previous_response_id": "resp_1",
+            "output": [
+                {"type": "reasoning", "id": "rs_1", "summary": [{"type": "summary_text", "text": "Looking up weather."}]},
+                {"type": "web_search_call", "id": "ws_1", "status": "completed"},
+                {"type": "message", "id": "msg_1", "role": "assistant", "status": "completed",
+                 "content": [{"type": "output_text", "text": "Checking.", "annotations": []}]},
+                {"type": "function_call", "id": "fc_1", "call_id": "call_1", "name": "get_weather", "arguments": "{\"city\":\"Paris\"}", "status": "completed"},
+                {"type": "image_generation_call", "id": "ig_1"}
+            ],
+            "usage": {"input_tokens": 36, "output_tokens": 87, "total_tokens": 123, "output_tokens_details": {"reasoning_tokens": 64}},
+            "temperature": 1.0
+        }))?;
+        assert_eq!(res.status, ResponseStatus::Completed);
+        assert_eq!(res.output_text(), "Checking.");
+        assert!(matches!(res.output[4], OutputItem::Unknown));
+        let calls = res.tool_calls();
+        assert_eq!(calls[0].id, "call_1");
+        let ChatMessage::Assistant(message) = res.to_chat_message() else { unreachable!() };
+        assert_eq!(message.content.as_deref(), Some("Checking."));
+        assert_eq!(message.tool_calls.len(), 1);
+        assert!(res.output[0].to_input().is_none());
+        assert!(matches!(res.output[3].to_input(), Some(InputItem::FunctionCall { .. })));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn response_stream_events_should_parse() -> Result<()>{
+        let body = concat!(
+            "event: response.created\n",
+            "data: {\"type\":\"response.created\",\"response\":{\"id\":\"resp_1\",\"object\":\"response\",\"created_at\":1,\"status\":\"in_progress\",\"model\":\"gpt-4o\",\"output\":[],\"error\":null}}\n\n",
+            "event: response.output_text.delta\n",
+            "data: {\"type\":\"response.output_text.delta\",\"item_id\":\"msg_1\",\"output_index\":0,\"content_index\":0,\"delta\":\"Hi\"}\n\n",
+            "event: response.content_part.added\n",
+            "data: {\"type\":\"response.content_part.added\",\"item_id\":\"msg_1\",\"output_index\":0,\"content_index\":0}\n\n",
+            "event: response.completed\n",
+            "data: {\"type\":\"response.completed\",\"response\":{\"id\":\"resp_1\",\"object\":\"response\",\"created_at\":1,\"status\":\"completed\",\"model\":\"gpt-4o\",\"output\":[{\"type\":\"message\",\"id\":\"msg_1\",\"role\":\"assistant\",\"content\":[{\"type\":\"output_text\",\"text\":\"Hi\",\"annotations\":[]}]}]}}\n\n",
+        );
+        let chunks = futures::stream::iter([std::result::Result::<_, std::io::Error>::Ok(bytes::Bytes::from(body))]);
+        let events: Vec<ResponseStreamEvent> = EventStream::new(chunks).map(|e| e.unwrap()).collect().await;
+        assert_eq!(events.len(), 4);
+        assert!(matches!(&events[1], ResponseStreamEvent::OutputTextDelta { delta, .. } if delta == "Hi"));
+        assert!(matches!(events[2], ResponseStreamEvent::Unknown));
+        let ResponseStreamEvent::Completed { response } = &events[3] else { unreachable!() };
+        assert_eq!(response.output_text(), "Hi");
+        Ok(())
+    }
+}
@@ -0,0 +1,619 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, time::Duration};
+
+use anyhow::Result;
+use derive_builder::Builder;
+use serde::{Serialize, Deserialize};
+
+use crate::OpenaiSdk;
+use crate::error::FileBatchFailed;
+use super::common::{ApiRequest, ListQuery, ListResponse, DeleteResponse, PollOptions};
+use super::file::{UploadFileRequest, FilePurpose};
+
+// 向量库API: 向量库、向量库文件、文件批次以及检索
+// 文件加入向量库后会被切块并建立索引，索引完成后可以被助手的文件检索工具或者检索接口使用
+
+/// 向量库接口的 beta 请求头
+const VECTOR_STORES_BETA: &str = "assistants=v2";
+
+/// 单个文件批次最多包含的文件数量
+const MAX_BATCH_FILES: usize = 500;
+
+/// 轮询文件批次状态的默认间隔
+pub const DEFAULT_BATCH_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+
+///
+/// 创建向量库API-请求体
+///
+#[derive(Debug,Clone,Default,Serialize,Builder)]
+#[builder(pattern = "mutable", default)]
+pub struct CreateVectorStoreRequest{
+    /// 向量库名称
+    #[builder(setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// 创建时加入向量库的文件ID列表
+    #[builder(setter(into))]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub file_ids: Vec<String>,
+
+    /// 过期策略
+    #[builder(setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_after: Option<ExpiresAfter>,
+
+    /// 文件切块策略，未设置时使用 auto 策略
+    #[builder(setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunking_strategy: Option<ChunkingStrategy>,
+
+    /// 附加在对象上的键值对，最多 16 个
+    #[builder(setter(into))]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+}
+
+///
+/// 修改向量库API-请求体
+///
+#[derive(Debug,Clone,Default,Serialize,Builder)]
+#[builder(pattern = "mutable", default)]
+pub struct ModifyVectorStoreRequest{
+    /// 向量库名称
+    #[builder(setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// 过期策略
+    #[builder(setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_after: Option<ExpiresAfter>,
+
+    /// 键值对，设置后会整体替换原有的 metadata
+    #[builder(setter(into,strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<HashMap<String, String>>,
+}
+
+/// 过期策略: 从锚点时间开始，经过指定天数后过期
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct ExpiresAfter{
+    /// 锚点，目前仅支持 last_active_at
+    pub anchor: String,
+    /// 天数，取值 1~365
+    pub days: u32,
+}
+
+impl ExpiresAfter {
+    /// 最后一次使用之后经过指定天数过期
+    pub fn last_active(days: u32) -> Self {
+        Self { anchor: "last_active_at".into(), days }
+    }
+}
+
+/// 文件切块策略
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChunkingStrategy{
+    /// 自动切块(800 tokens，重叠 400 tokens)
+    Auto,
+    /// 自定义切块大小
+    Static{
+        r#static: StaticChunking,
+    },
+}
+
+/// 自定义切块大小
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct StaticChunking{
+    /// 每块最大 token 数，取值 100~4096
+    pub max_chunk_size_tokens: u32,
+    /// 块之间重叠的 token 数，不能超过最大值的一半
+    pub chunk_overlap_tokens: u32,
+}
+
+
+///
+/// 向量库对象
+///
+#[derive(Debug,Clone,Deserialize)]
+pub struct VectorStore{
+    /// 向量库ID
+    pub id: String,
+    /// 对象类型，始终为 vector_store
+    pub object: String,
+    /// 创建时的 Unix 时间戳（以秒为单位）
+    pub created_at: u64,
+    /// 向量库名称
+    #[serde(default)]
+    pub name: Option<String>,
+    /// 向量库占用的存储空间(字节)
+    #[serde(default)]
+    pub usage_bytes: u64,
+    /// 各状态的文件数量
+    pub file_counts: FileCounts,
+    /// 向量库状态: expired、in_progress 或 completed
+    pub status: String,
+    /// 过期策略
+    #[serde(default)]
+    pub expires_after: Option<ExpiresAfter>,
+    /// 过期时的 Unix 时间戳
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    /// 最后一次使用时的 Unix 时间戳
+    #[serde(default)]
+    pub last_active_at: Option<u64>,
+    /// 键值对
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// 未建模的其他字段，原样保留
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// 各状态的文件数量
+#[derive(Debug,Clone,Copy,Default,PartialEq, Eq,Deserialize)]
+pub struct FileCounts{
+    /// 处理中
+    pub in_progress: u32,
+    /// 处理完成
+    pub completed: u32,
+    /// 处理失败
+    pub failed: u32,
+    /// 已取消
+    pub cancelled: u32,
+    /// 总数
+    pub total: u32,
+}
+
+
+///
+/// 向量库文件API-请求体
+///
+#[derive(Debug,Clone,Serialize)]
+pub struct CreateVectorStoreFileRequest{
+    /// 要加入向量库的文件ID
+    pub file_id: String,
+    /// 文件切块策略
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunking_strategy: Option<ChunkingStrategy>,
+}
+
+impl CreateVectorStoreFileRequest {
+    pub fn new(file_id: impl Into<String>) -> Self {
+        Self { file_id: file_id.into(), chunking_strategy: None }
+    }
+}
+
+///
+/// 创建文件批次API-请求体
+///
+#[derive(Debug,Clone,Serialize)]
+pub struct CreateFileBatchRequest{
+    /// 要加入向量库的文件ID列表，最多 500 个
+    pub file_ids: Vec<String>,
+    /// 文件切块策略
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunking_strategy: Option<ChunkingStrategy>,
+}
+
+impl CreateFileBatchRequest {
+    pub fn new(file_ids: Vec<String>) -> Self {
+        Self { file_ids, chunking_strategy: None }
+    }
+}
+
+/// 向量库文件、文件批次的处理状态
+#[derive(Debug,Clone,PartialEq, Eq,Serialize,Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexingStatus{
+    /// 处理中
+    InProgress,
+    /// 处理完成
+    Completed,
+    /// 已取消
+    Cancelled,
+    /// 处理失败
+    Failed,
+    /// 未知的状态，保留原始值
+    #[serde(untagged)]
+    Other(String),
+}
+
+///
+/// 向量库文件对象
+///
+#[derive(Debug,Clone,Deserialize)]
+pub struct VectorStoreFile{
+    /// 文件ID
+    pub id: String,
+    /// 对象类型，始终为 vector_store.file
+    pub object: String,
+    /// 文件在向量库中占用的存储空间(字节)
+    #[serde(default)]
+    pub usage_bytes: u64,
+    /// 创建时的 Unix 时间戳（以秒为单位）
+    pub created_at: u64,
+    /// 所属的向量库ID
+    pub vector_store_id: String,
+    /// 处理状态
+    pub status: IndexingStatus,
+    /// 处理失败时的错误信息
+    #[serde(default)]
+    pub last_error: Option<serde_json::Value>,
+    /// 未建模的其他字段，原样保留
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+///
+/// 向量库文件批次对象
+///
+#[derive(Debug,Clone,Deserialize)]
+pub struct VectorStoreFileBatch{
+    /// 批次ID
+    pub id: String,
+    /// 对象类型，始终为 vector_store.file_batch
+    pub object: String,
+    /// 创建时的 Unix 时间戳（以秒为单位）
+    pub created_at: u64,
+    /// 所属的向量库ID
+    pub vector_store_id: String,
+    /// 处理状态
+    pub status: IndexingStatus,
+    /// 各状态的文件数量
+    pub file_counts: FileCounts,
+}
+
+impl VectorStoreFileBatch {
+    /// 批次已完成，并且没有处理失败或者被取消的文件
+    pub fn is_success(&self) -> bool {
+        self.status == IndexingStatus::Completed && self.file_counts.failed == 0 && self.file_counts.cancelled == 0
+    }
+}
+
+
+///
+/// 向量库检索API-请求体
+///
+#[derive(Debug,Clone,Serialize,Builder)]
+#[builder(pattern = "mutable")]
+pub struct VectorStoreSearchRequest{
+    /// 检索的查询文本
+    #[builder(setter(into))]
+    pub query: String,
+
+    /// 最多返回的结果数量，取值 1~50
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_num_results: Option<u32>,
+
+    /// 基于文件属性的过滤条件
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filters: Option<serde_json::Value>,
+
+    /// 是否由模型改写查询文本以提升检索效果
+    #[builder(default,setter(strip_option))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rewrite_query: Option<bool>,
+}
+
+impl VectorStoreSearchRequest {
+    pub fn new(query: impl Into<String>) -> Self {
+        VectorStoreSearchRequestBuilder::default()
+        .query(query)
+        .build()
+        .unwrap()
+    }
+}
+
+///
+/// 向量库检索API-响应体
+///
+#[derive(Debug,Clone,Deserialize)]
+pub struct VectorStoreSearchResponse{
+    /// 对象类型，始终为 vector_store.search_results.page
+    pub object: String,
+    /// 实际使用的查询文本(可能被改写)
+    #[serde(default)]
+    pub search_query: serde_json::Value,
+    /// 检索结果，按相关度从高到低排序
+    pub data: Vec<VectorStoreSearchResult>,
+    /// 是否还有更多结果
+    #[serde(default)]
+    pub has_more: bool,
+    /// 下一页的游标
+    #[serde(default)]
+    pub next_page: Option<String>,
+}
+
+/// 单条检索结果
+#[derive(Debug,Clone,Deserialize)]
+pub struct VectorStoreSearchResult{
+    /// 文件ID
+    pub file_id: String,
+    /// 文件名
+    pub filename: String,
+    /// 相关度得分，取值 0~1
+    pub score: f64,
+    /// 文件属性
+    #[serde(default)]
+    pub attributes: HashMap<String, serde_json::Value>,
+    /// 命中的文本块
+    #[serde(default)]
+    pub content: Vec<SearchResultContent>,
+}
+
+/// 命中的文本块
+#[derive(Debug,Clone,Deserialize)]
+pub struct SearchResultContent{
+    /// 内容类型，目前始终为 text
+    pub r#type: String,
+    /// 文本
+    pub text: String,
+}
+
+
+// 向量库相关的 api 请求
+impl OpenaiSdk {
+
+    ///
+    /// 创建向量库
+    ///
+    pub async fn create_vector_store(&self, req: CreateVectorStoreRequest) -> Result<VectorStore>{
+        self.send_json(ApiRequest::post("vector_stores", &req)?.beta(VECTOR_STORES_BETA)).await
+    }
+
+    ///
+    /// 获取向量库
+    ///
+    pub async fn retrieve_vector_store(&self, vector_store_id: &str) -> Result<VectorStore>{
+        self.send_json(ApiRequest::get(format!("vector_stores/{vector_store_id}")).beta(VECTOR_STORES_BETA)).await
+    }
+
+    ///
+    /// 修改向量库
+    ///
+    pub async fn update_vector_store(&self, vector_store_id: &str, req: ModifyVectorStoreRequest) -> Result<VectorStore>{
+        self.send_json(ApiRequest::post(format!("vector_stores/{vector_store_id}"), &req)?.beta(VECTOR_STORES_BETA)).await
+    }
+
+    ///
+    /// 分页获取向量库列表
+    ///
+    pub async fn list_vector_stores(&self, query: ListQuery) -> Result<ListResponse<VectorStore>>{
+        self.send_json(ApiRequest::get("vector_stores").query(&query)?.beta(VECTOR_STORES_BETA)).await
+    }
+
+    ///
+    /// 删除向量库
+    ///
+    pub async fn delete_vector_store(&self, vector_store_id: &str) -> Result<DeleteResponse>{
+        self.send_json(ApiRequest::delete(format!("vector_stores/{vector_store_id}")).beta(VECTOR_STORES_BETA)).await
+    }
+
+    ///
+    /// 将已上传的文件加入向量库
+    ///
+    pub async fn create_vector_store_file(&self, vector_store_id: &str, req: CreateVectorStoreFileRequest) -> Result<VectorStoreFile>{
+        let req = ApiRequest::post(format!("vector_stores/{vector_store_id}/files"), &req)?;
+        self.send_json(req.beta(VECTOR_STORES_BETA)).await
+    }
+
+    ///
+    /// 获取向量库文件
+    ///
+    pub async fn retrieve_vector_store_file(&self, vector_store_id: &str, file_id: &str) -> Result<VectorStoreFile>{
+        let req = ApiRequest::get(format!("vector_stores/{vector_store_id}/files/{file_id}"));
+        self.send_json(req.beta(VECTOR_STORES_BETA)).await
+    }
+
+    ///
+    /// 分页获取向量库文件列表
+    ///
+    pub async fn list_vector_store_files(&self, vector_store_id: &str, query: ListQuery) -> Result<ListResponse<VectorStoreFile>>{
+        let req = ApiRequest::get(format!("vector_stores/{vector_store_id}/files")).query(&query)?;
+        self.send_json(req.beta(VECTOR_STORES_BETA)).await
+    }
+
+    ///
+    /// 从向量库中移除文件(不会删除文件本身)
+    ///
+    pub async fn delete_vector_store_file(&self, vector_store_id: &str, file_id: &str) -> Result<DeleteResponse>{
+        let req = ApiRequest::delete(format!("vector_stores/{vector_store_id}/files/{file_id}"));
+        self.send_json(req.beta(VECTOR_STORES_BETA)).await
+    }
+
+    ///
+    /// 批量将文件加入向量库
+    ///
+    pub async fn create_vector_store_file_batch(&self, vector_store_id: &str, req: CreateFileBatchRequest) -> Result<VectorStoreFileBatch>{
+        let req = ApiRequest::post(format!("vector_stores/{vector_store_id}/file_batches"), &req)?;
+        self.send_json(req.beta(VECTOR_STORES_BETA)).await
+    }
+
+    ///
+    /// 获取文件批次
+    ///
+    pub async fn retrieve_vector_store_file_batch(&self, vector_store_id: &str, batch_id: &str) -> Result<VectorStoreFileBatch>{
+        let req = ApiRequest::get(format!("vector_stores/{vector_store_id}/file_batches/{batch_id}"));
+        self.send_json(req.beta(VECTOR_STORES_BETA)).await
+    }
+
+    ///
+    /// 取消文件批次
+    ///
+    pub async fn cancel_vector_store_file_batch(&self, vector_store_id: &str, batch_id: &str) -> Result<VectorStoreFileBatch>{
+        let req = ApiRequest::post(format!("vector_stores/{vector_store_id}/file_batches/{batch_id}/cancel"), &serde_json::json!({}))?;
+        self.send_json(req.beta(VECTOR_STORES_BETA)).await
+    }
+
+    ///
+    /// 按照指定间隔轮询文件批次，直到批次中的文件全部处理结束;
+    /// `options` 可以直接传入轮询间隔，超过最长等待时间(默认 10 分钟)仍未结束时返回 `PollTimeout` 错误
+    ///
+    pub async fn poll_vector_store_file_batch(&self, vector_store_id: &str, batch_id: &str, options: impl Into<PollOptions>) -> Result<VectorStoreFileBatch>{
+        let options = options.into();
+        let started = tokio::time::Instant::now();
+        loop {
+            let batch = self.retrieve_vector_store_file_batch(vector_store_id, batch_id).await?;
+            if batch.status != IndexingStatus::InProgress {
+                return Ok(batch);
+            }
+            options.wait(started, batch_id, &batch.status).await?;
+        }
+    }
+
+    ///
+    /// 在向量库中检索与查询相关的文本块
+    ///
+    pub async fn search_vector_store(&self, vector_store_id: &str, req: VectorStoreSearchRequest) -> Result<VectorStoreSearchResponse>{
+        let req = ApiRequest::post(format!("vector_stores/{vector_store_id}/search"), &req)?;
+        self.send_json(req.beta(VECTOR_STORES_BETA)).await
+    }
+
+    ///
+    /// 上传目录(包含子目录)中的所有文件，以文件批次加入向量库，并等待索引完成;
+    /// 文件超过 500 个时分多个批次，返回每个批次的最终状态;
+    /// 文件以流的方式逐块上传，不会整体读入内存;
+    /// 有批次失败、被取消或者存在处理失败的文件时返回 `FileBatchFailed` 错误，其中包含所有批次的最终状态
+    ///
+    pub async fn upload_directory_to_vector_store(&self, vector_store_id: &str, dir: impl AsRef<Path>) -> Result<Vec<VectorStoreFileBatch>>{
+        let mut file_ids = Vec::new();
+        for path in collect_files(dir.as_ref()).await? {
+            let req = UploadFileRequest::from_path(&path, FilePurpose::Assistants).await?;
+            file_ids.push(self.upload_file(req).await?.id);
+        }
+        let mut batches = Vec::new();
+        for chunk in file_ids.chunks(MAX_BATCH_FILES) {
+            let batch = self.create_vector_store_file_batch(vector_store_id, CreateFileBatchRequest::new(chunk.to_vec())).await?;
+            batches.push(self.poll_vector_store_file_batch(vector_store_id, &batch.id, DEFAULT_BATCH_POLL_INTERVAL).await?);
+        }
+        if !batches.iter().all(VectorStoreFileBatch::is_success) {
+            return Err(FileBatchFailed { batches }.into());
+        }
+        Ok(batches)
+    }
+}
+
+/// 递归收集目录中的所有文件(忽略以 `.` 开头的隐藏文件和目录)，按路径排序
+async fn collect_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+
+#[cfg(test)]
+mod tests{
+    use anyhow::{Result, Ok};
+    use reqwest::Method;
+    use serde_json::json;
+    use crate::transport::{MockTransport, MockResponse};
+    use super::*;
+
+    #[test]
+    fn create_vector_store_request_should_serialize() -> Result<()>{
+        let req = CreateVectorStoreRequestBuilder::default()
+            .name("Product docs")
+            .expires_after(ExpiresAfter::last_active(7))
+            .chunking_strategy(ChunkingStrategy::Static { r#static: StaticChunking { max_chunk_size_tokens: 400, chunk_overlap_tokens: 100 } })
+            .build()?;
+        assert_eq!(
+            serde_json::to_value(&req)?,
+            json!({
+                "name": "Product docs",
+                "expires_after": {"anchor": "last_active_at", "days": 7},
+                "chunking_strategy": {"type": "static", "static": {"max_chunk_size_tokens": 400, "chunk_overlap_tokens": 100}}
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn vector_store_objects_should_deserialize() -> Result<()>{
+        let batch: VectorStoreFileBatch = serde_json::from_value(json!({
+            "id": "vsfb_abc123",
+            "object": "vector_store.file_batch",
+            "created_at": 1699061776,
+            "vector_store_id": "vs_abc123",
+            "status": "in_progress",
+            "file_counts": {"in_progress": 1, "completed": 1, "failed": 0, "cancelled": 0, "total": 2}
+        }))?;
+        assert_eq!(batch.status, IndexingStatus::InProgress);
+        assert_eq!(batch.file_counts.total, 2);
+
+        let res: VectorStoreSearchResponse = serde_json::from_value(json!({
+            "object": "vector_store.search_results.page",
+            "search_query": "How do I reset my password?",
+            "data": [{
+                "file_id": "file-123",
+                "filename": "account.md",
+                "score": 0.85,
+                "attributes": {"section": "account"},
+                "content": [{"type": "text", "text": "Open Settings and choose Reset password."}]
+            }],
+            "has_more": false,
+            "next_page": null
+        }))?;
+        assert_eq!(res.data[0].filename, "account.md");
+        assert_eq!(res.data[0].content[0].text, "Open Settings and choose Reset password.");
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn upload_directory_should_fail_on_failed_files() -> Result<()>{
+        let batch = |status: &str, failed: u32| MockResponse::json(json!({
+            "id": "vsfb_1", "object": "vector_store.file_batch", "created_at": 1, "vector_store_id": "vs_1", "status": status,
+            "file_counts": {"in_progress": 0, "completed": 1 - failed, "failed": failed, "cancelled": 0, "total": 1}
+        }));
+        let mock = MockTransport::new()
+            .on(Method::POST, "files", MockResponse::json(json!({
+                "id": "file-1", "object": "file", "bytes": 1, "created_at": 1, "filename": "a.md", "purpose": "assistants"
+            })))
+            .on(Method::POST, "vector_stores/vs_1/file_batches", batch("in_progress", 0))
+            .on(Method::GET, "vector_stores/vs_1/file_batches/vsfb_1", batch("completed", 1));
+        let dir = std::env::temp_dir().join(format!("openai-sdk-upload-dir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("a.md"), "a")?;
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock.clone());
+        let err = sdk.upload_directory_to_vector_store("vs_1", &dir).await.unwrap_err();
+        std::fs::remove_dir_all(&dir)?;
+        let err = err.downcast::<FileBatchFailed>()?;
+        assert_eq!(err.batches[0].file_counts.failed, 1);
+        assert_eq!(err.to_string(), "1 of 1 vector store file batches did not complete: 1 files failed, 0 cancelled");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn collect_files_should_walk_directories() -> Result<()>{
+        let dir = std::env::temp_dir().join(format!("openai-sdk-collect-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested"))?;
+        std::fs::create_dir_all(dir.join(".git"))?;
+        std::fs::write(dir.join("a.md"), "a")?;
+        std::fs::write(dir.join("nested/b.md"), "b")?;
+        std::fs::write(dir.join(".git/config"), "ignored")?;
+        std::fs::write(dir.join(".hidden"), "ignored")?;
+        let files = collect_files(&dir).await?;
+        std::fs::remove_dir_all(&dir)?;
+        assert_eq!(files, vec![dir.join("a.md"), dir.join("nested/b.md")]);
+        Ok(())
+    }
+}
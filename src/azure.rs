@@ -0,0 +1,215 @@
+//!
+//! Azure OpenAI 部署配置
+//! Azure 上的接口地址格式为 `https://{resource}.openai.azure.com/openai/deployments/{deployment}/chat/completions?api-version=...`,
+//! 使用 `api-key` 请求头或者 Entra ID 令牌进行认证，令牌通过 `CredentialProvider` 获取，可以在过期前自动刷新。
+//! 各请求类型仍然按照 OpenAI 的地址构建，由 SDK 在发送前统一改写为 Azure 的地址;
+//! 部署名称根据请求中的模型确定，multipart 请求(例如语音转文字)使用表单中的模型。
+//!
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Result, anyhow};
+use reqwest::{Url, Request, header::{HeaderValue, AUTHORIZATION}};
+
+use crate::credential::{self, CredentialProvider, SecretString, StaticCredentials};
+
+/// 默认使用的 api-version
+pub const DEFAULT_AZURE_API_VERSION: &str = "2024-10-21";
+
+/// 需要按照部署路由的接口路径，其余接口(例如 assistants、files)直接位于 `/openai/` 下
+const DEPLOYMENT_PATHS: &[&str] = &[
+    "chat/completions",
+    "completions",
+    "embeddings",
+    "images/generations",
+    "audio/transcriptions",
+    "audio/translations",
+    "audio/speech",
+];
+
+/// Azure 认证方式
+#[derive(Debug,Clone)]
+pub enum AzureAuth{
+    /// 资源的 api key，通过 `api-key` 请求头发送
+    ApiKey(SecretString),
+    /// 固定的 Entra ID (Azure AD) 访问令牌，通过 `Authorization: Bearer` 请求头发送;令牌通常一小时后过期
+    EntraToken(SecretString),
+    /// 获取 Entra ID 访问令牌的提供者，例如使用 `RefreshingCredentials` 在过期前换取新的令牌
+    EntraId(Arc<dyn CredentialProvider>),
+}
+
+impl AzureAuth {
+    /// 使用提供者获取 Entra ID 访问令牌
+    pub fn entra_id(provider: impl CredentialProvider + 'static) -> Self {
+        AzureAuth::EntraId(Arc::new(provider))
+    }
+
+    /// 获取认证信息的提供者，作为 `OpenaiSdk` 的凭证
+    pub(crate) fn provider(&self) -> Arc<dyn CredentialProvider> {
+        match self {
+            AzureAuth::ApiKey(token) | AzureAuth::EntraToken(token) => Arc::new(StaticCredentials(token.clone())),
+            AzureAuth::EntraId(provider) => provider.clone(),
+        }
+    }
+}
+
+///
+/// Azure OpenAI 资源配置
+///
+#[derive(Debug,Clone)]
+pub struct AzureConfig{
+    /// 资源地址，例如 `https://my-resource.openai.azure.com`
+    pub(crate) endpoint: String,
+    /// 请求时携带的 api-version 查询参数
+    pub(crate) api_version: String,
+    /// 认证方式
+    pub(crate) auth: AzureAuth,
+    /// 模型ID到部署名称的映射
+    pub(crate) deployments: HashMap<String, String>,
+    /// 没有匹配到映射时使用的部署名称，未设置时直接使用模型ID作为部署名称
+    pub(crate) default_deployment: Option<String>,
+}
+
+impl AzureConfig {
+
+    /// 使用资源名称和 api key 创建配置
+    pub fn new(resource: &str, auth: AzureAuth) -> Self {
+        Self::with_endpoint(format!("https://{resource}.openai.azure.com"), auth)
+    }
+
+    /// 使用完整的资源地址创建配置，适用于自定义域名或者私有终结点
+    pub fn with_endpoint(endpoint: impl Into<String>, auth: AzureAuth) -> Self {
+        Self {
+            endpoint: endpoint.into().trim_end_matches('/').to_string(),
+            api_version: DEFAULT_AZURE_API_VERSION.into(),
+            auth,
+            deployments: HashMap::new(),
+            default_deployment: None,
+        }
+    }
+
+    /// 设置 api-version
+    pub fn api_version(mut self, api_version: impl Into<String>) -> Self {
+        self.api_version = api_version.into();
+        self
+    }
+
+    /// 将模型映射到指定的部署，`model` 为模型ID，例如 `Model::Gpt4Turbo.as_str()`
+    pub fn deployment(mut self, model: impl Into<String>, deployment: impl Into<String>) -> Self {
+        self.deployments.insert(model.into(), deployment.into());
+        self
+    }
+
+    /// 设置默认部署
+    pub fn default_deployment(mut self, deployment: impl Into<String>) -> Self {
+        self.default_deployment = Some(deployment.into());
+        self
+    }
+
+    /// 根据模型ID查找部署名称
+    fn deployment_for(&self, model: Option<&str>) -> Result<String> {
+        if let Some(deployment) = model.and_then(|m| self.deployments.get(m)) {
+            return Ok(deployment.clone());
+        }
+        if let Some(deployment) = &self.default_deployment {
+            return Ok(deployment.clone());
+        }
+        model
+            .map(Into::into)
+            .ok_or_else(|| anyhow!("no azure deployment configured for request without model"))
+    }
+
+    /// 将 OpenAI 的接口路径(v1 之后的部分)改写为 Azure 的地址
+    pub(crate) fn rewrite_url(&self, path: &str, query: Option<&str>, model: Option<&str>) -> Result<Url> {
+        let url = if DEPLOYMENT_PATHS.contains(&path) {
+            let deployment = self.deployment_for(model)?;
+            format!("{}/openai/deployments/{}/{}", self.endpoint, deployment, path)
+        } else {
+            format!("{}/openai/{}", self.endpoint, path)
+        };
+        let mut url = Url::parse(&url)?;
+        url.set_query(query);
+        url.query_pairs_mut().append_pair("api-version", &self.api_version);
+        Ok(url)
+    }
+
+    /// 按照认证方式设置请求头，`token` 为凭证提供者返回的 api key 或者访问令牌
+    pub(crate) fn authorize(&self, req: &mut Request, token: &SecretString) -> Result<()> {
+        let (name, value) = match &self.auth {
+            AzureAuth::ApiKey(_) => {
+                let mut value = HeaderValue::from_str(token.expose_secret())?;
+                value.set_sensitive(true);
+                ("api-key", value)
+            }
+            AzureAuth::EntraToken(_) | AzureAuth::EntraId(_) => (AUTHORIZATION.as_str(), credential::bearer(token)?),
+        };
+        req.headers_mut().insert(name, value);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use std::{sync::atomic::{AtomicU32, Ordering}, time::Duration};
+    use super::*;
+    use crate::{OpenaiSdk, api::*};
+    use crate::credential::{ExpiringToken, RefreshingCredentials};
+
+    fn config() -> AzureConfig {
+        AzureConfig::new("contoso", AzureAuth::ApiKey("azure-key".into()))
+            .deployment(Model::Gpt4Turbo.as_str(), "gpt4-prod")
+            .api_version("2024-06-01")
+    }
+
+    #[test]
+    fn rewrite_url_should_route_by_deployment(){
+        let config = config();
+        let url = config.rewrite_url("chat/completions", None, Some("gpt-4-1106-preview")).unwrap();
+        assert_eq!(url.as_str(), "https://contoso.openai.azure.com/openai/deployments/gpt4-prod/chat/completions?api-version=2024-06-01");
+        // 未映射的模型直接使用模型ID作为部署名称
+        let url = config.rewrite_url("images/generations", None, Some("dall-e-3")).unwrap();
+        assert_eq!(url.path(), "/openai/deployments/dall-e-3/images/generations");
+        // 非部署类接口，保留原有查询参数
+        let url = config.rewrite_url("assistants", Some("limit=10"), None).unwrap();
+        assert_eq!(url.as_str(), "https://contoso.openai.azure.com/openai/assistants?limit=10&api-version=2024-06-01");
+    }
+
+    #[tokio::test]
+    async fn prepare_request_should_use_azure_url_and_api_key() -> Result<()>{
+        let sdk = OpenaiSdk::azure(config());
+        let req = ChatCompletionRequestBuilder::default()
+            .model(Model::Gpt4Turbo)
+            .messages(vec![ChatMessage::new_user("hi", "")])
+            .build()?;
+        let req = sdk.prepare_request(req).await?;
+        assert_eq!(req.url().path(), "/openai/deployments/gpt4-prod/chat/completions");
+        assert_eq!(req.headers()["api-key"], "azure-key");
+        assert!(req.headers().get(AUTHORIZATION).is_none());
+
+        let sdk = OpenaiSdk::azure(AzureConfig::new("contoso", AzureAuth::EntraToken("entra".into())));
+        let req = sdk.prepare_request(CreateImageRequest::new("a cat")).await?;
+        assert_eq!(req.url().path(), "/openai/deployments/dall-e-3/images/generations");
+        assert_eq!(req.headers()[AUTHORIZATION], "Bearer entra");
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn azure_should_refresh_entra_token_and_route_transcription() -> Result<()>{
+        let fetched = Arc::new(AtomicU32::new(0));
+        let counter = fetched.clone();
+        let provider = RefreshingCredentials::new(move || {
+            let n = counter.fetch_add(1, Ordering::Relaxed);
+            async move { Ok(ExpiringToken { token: format!("entra-{n}").into(), expires_in: Duration::from_secs(3600) }) }
+        });
+        let sdk = OpenaiSdk::azure(AzureConfig::new("contoso", AzureAuth::entra_id(provider)).deployment("whisper-1", "whisper-prod"));
+        let req = sdk.prepare_request(TranscriptionRequest::new("hello.mp3", b"ID3".to_vec())).await?;
+        assert_eq!(req.url().path(), "/openai/deployments/whisper-prod/audio/transcriptions");
+        assert_eq!(req.headers()[AUTHORIZATION], "Bearer entra-0");
+
+        tokio::time::advance(Duration::from_secs(3600)).await;
+        let req = sdk.prepare_request(CreateImageRequest::new("a cat")).await?;
+        assert_eq!(req.headers()[AUTHORIZATION], "Bearer entra-1");
+        Ok(())
+    }
+}
@@ -0,0 +1,577 @@
+//!
+//! openai 命令行工具(cli 特性)
+//! 提供聊天(交互式对话、流式输出、会话保存/加载)、图像生成、向量嵌入、语音转文字、批处理与模型列表等子命令;
+//! 输入可以来自命令行参数、`--file` 指定的文件或者标准输入，`--json` 输出 Json 便于脚本处理。
+//! API key 从 `--api-key` 或者环境变量 `OPENAI_API_KEY` 读取。
+//!
+
+use std::{
+    io::{BufRead, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, anyhow, bail};
+use base64::Engine;
+use clap::{Args, Parser, Subcommand};
+use futures::StreamExt;
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+use serde_json::json;
+
+use openai_llm_sdk::OpenaiSdk;
+use openai_llm_sdk::api::*;
+
+#[derive(Debug, Parser)]
+#[command(name = "openai", version, about = "OpenAI 命令行工具")]
+struct Cli {
+    /// API key
+    #[arg(long, env = "OPENAI_API_KEY", hide_env_values = true)]
+    api_key: String,
+
+    /// 接口的基础地址，例如 http://localhost:8080/v1
+    #[arg(long, env = "OPENAI_BASE_URL")]
+    base_url: Option<String>,
+
+    /// 以 Json 格式输出结果
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// 聊天: 传入消息时回复一次，否则进入交互式对话
+    Chat(ChatArgs),
+    /// 生成图像并保存到文件
+    Image(ImageArgs),
+    /// 计算文本的嵌入向量
+    Embed(EmbedArgs),
+    /// 语音转文字
+    Transcribe(TranscribeArgs),
+    /// 批处理
+    #[command(subcommand)]
+    Batch(BatchCommand),
+    /// 模型
+    #[command(subcommand)]
+    Models(ModelsCommand),
+}
+
+/// 文本输入: 命令行参数(`-` 表示标准输入)、文件，或者通过管道传入的标准输入
+#[derive(Debug, Args)]
+struct Input {
+    /// 输入文本，`-` 表示从标准输入读取
+    text: Option<String>,
+
+    /// 从文件读取输入文本
+    #[arg(long, short = 'f', conflicts_with = "text")]
+    file: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct ChatArgs {
+    #[command(flatten)]
+    input: Input,
+
+    /// 模型ID
+    #[arg(long, short = 'm', value_parser = parse::<Model>)]
+    model: Option<Model>,
+
+    /// 系统提示词，只在新会话中使用
+    #[arg(long, short = 's')]
+    system: Option<String>,
+
+    /// 会话文件: 存在时加载历史消息，每轮对话后保存
+    #[arg(long)]
+    session: Option<PathBuf>,
+
+    /// 采样温度，取值 0~2
+    #[arg(long)]
+    temperature: Option<f32>,
+
+    /// 关闭流式输出
+    #[arg(long)]
+    no_stream: bool,
+}
+
+#[derive(Debug, Args)]
+struct ImageArgs {
+    #[command(flatten)]
+    input: Input,
+
+    /// 保存的文件路径，生成多张图像时依次添加序号
+    #[arg(long, short = 'o', default_value = "image.png")]
+    output: PathBuf,
+
+    /// 模型: dall-e-2、dall-e-3
+    #[arg(long, short = 'm', value_parser = parse::<ImageModel>)]
+    model: Option<ImageModel>,
+
+    /// 分辨率: 1024x1024、1792x1024、1024x1792
+    #[arg(long, value_parser = parse::<ImageSize>)]
+    size: Option<ImageSize>,
+
+    /// 质量: standard、hd
+    #[arg(long, value_parser = parse::<ImageQuality>)]
+    quality: Option<ImageQuality>,
+
+    /// 生成的图像数量
+    #[arg(long, short = 'n')]
+    count: Option<usize>,
+}
+
+#[derive(Debug, Args)]
+struct EmbedArgs {
+    #[command(flatten)]
+    input: Input,
+
+    /// 模型ID
+    #[arg(long, short = 'm', value_parser = parse::<EmbeddingModel>)]
+    model: Option<EmbeddingModel>,
+
+    /// 输出向量的维度
+    #[arg(long)]
+    dimensions: Option<usize>,
+}
+
+#[derive(Debug, Args)]
+struct TranscribeArgs {
+    /// 音频文件路径
+    file: PathBuf,
+
+    /// 模型ID
+    #[arg(long, short = 'm')]
+    model: Option<String>,
+
+    /// 音频的语言(ISO-639-1，例如 zh、en)
+    #[arg(long, short = 'l')]
+    language: Option<String>,
+
+    /// 提示文本
+    #[arg(long)]
+    prompt: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+enum BatchCommand {
+    /// 上传 JSONL 请求文件并创建批处理
+    Submit {
+        /// JSONL 请求文件路径
+        file: PathBuf,
+        /// 请求的接口，例如 /v1/chat/completions、/v1/embeddings
+        #[arg(long, short = 'e', default_value = "/v1/chat/completions", value_parser = parse::<BatchEndpoint>)]
+        endpoint: BatchEndpoint,
+    },
+    /// 查询批处理状态
+    Status {
+        /// 批处理ID
+        id: String,
+    },
+    /// 下载批处理的输出文件
+    Download {
+        /// 批处理ID
+        id: String,
+        /// 保存的文件路径，未设置时输出到标准输出
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+        /// 下载失败请求的错误文件
+        #[arg(long)]
+        errors: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ModelsCommand {
+    /// 列出可用的模型
+    List,
+}
+
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let mut sdk = OpenaiSdk::new(cli.api_key);
+    if let Some(base_url) = cli.base_url {
+        sdk = sdk.with_base_url(base_url);
+    }
+    match cli.command {
+        Command::Chat(args) => chat(&sdk, args, cli.json).await,
+        Command::Image(args) => image(&sdk, args, cli.json).await,
+        Command::Embed(args) => embed(&sdk, args, cli.json).await,
+        Command::Transcribe(args) => transcribe(&sdk, args, cli.json).await,
+        Command::Batch(command) => batch(&sdk, command, cli.json).await,
+        Command::Models(ModelsCommand::List) => list_models(&sdk, cli.json).await,
+    }
+}
+
+
+/// 会话文件中的一条消息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SessionMessage {
+    role: String,
+    content: String,
+}
+
+/// 一次聊天会话
+#[derive(Debug, Default)]
+struct Session {
+    messages: Vec<SessionMessage>,
+}
+
+impl Session {
+    fn load(path: &Path) -> Result<Self> {
+        let messages = serde_json::from_slice(&std::fs::read(path)?)?;
+        Ok(Self { messages })
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(&self.messages)?)?;
+        Ok(())
+    }
+
+    fn push(&mut self, role: &str, content: impl Into<String>) {
+        self.messages.push(SessionMessage { role: role.into(), content: content.into() });
+    }
+
+    fn chat_messages(&self) -> Result<Vec<ChatMessage>> {
+        self.messages.iter().map(|m| match m.role.as_str() {
+            "system" => Ok(ChatMessage::new_system(m.content.as_str(), "")),
+            "user" => Ok(ChatMessage::new_user(m.content.as_str(), "")),
+            "assistant" => Ok(ChatMessage::new_assistant(m.content.as_str())),
+            role => Err(anyhow!("unsupported role in session: {role}")),
+        }).collect()
+    }
+}
+
+async fn chat(sdk: &OpenaiSdk, args: ChatArgs, json: bool) -> Result<()> {
+    let mut session = match &args.session {
+        Some(path) if path.exists() => Session::load(path)?,
+        _ => Session::default(),
+    };
+    if session.messages.is_empty() {
+        if let Some(system) = &args.system {
+            session.push("system", system.as_str());
+        }
+    }
+    // 有输入时只回复一次
+    if let Some(input) = read_input(&args.input)? {
+        session.push("user", input);
+        let reply = chat_turn(sdk, &args, &session, json).await?;
+        session.push("assistant", reply);
+        if let Some(path) = &args.session {
+            session.save(path)?;
+        }
+        return Ok(());
+    }
+
+    eprintln!("输入消息开始对话，/save <文件> 保存会话，/load <文件> 加载会话，/clear 清空会话，/exit 退出");
+    let stdin = std::io::stdin();
+    loop {
+        eprint!("> ");
+        std::io::stderr().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let line = line.trim();
+        match line.split_once(' ').unwrap_or((line, "")) {
+            ("", _) => continue,
+            ("/exit" | "/quit", _) => return Ok(()),
+            ("/clear", _) => {
+                session = Session::default();
+                if let Some(system) = &args.system {
+                    session.push("system", system.as_str());
+                }
+            }
+            ("/save", path) => match path_or(path, args.session.as_deref()) {
+                Some(path) => session.save(&path)?,
+                None => eprintln!("用法: /save <文件>"),
+            },
+            ("/load", path) => match path_or(path, args.session.as_deref()) {
+                Some(path) => session = Session::load(&path)?,
+                None => eprintln!("用法: /load <文件>"),
+            },
+            _ => {
+                session.push("user", line);
+                match chat_turn(sdk, &args, &session, json).await {
+                    Ok(reply) => session.push("assistant", reply),
+                    Err(e) => {
+                        session.messages.pop();
+                        eprintln!("error: {e}");
+                        continue;
+                    }
+                }
+                if let Some(path) = &args.session {
+                    session.save(path)?;
+                }
+            }
+        }
+    }
+}
+
+/// REPL 命令中的文件参数，未传入时使用 `--session` 指定的文件
+fn path_or(path: &str, default: Option<&Path>) -> Option<PathBuf> {
+    let path = path.trim();
+    if path.is_empty() { default.map(Path::to_path_buf) } else { Some(PathBuf::from(path)) }
+}
+
+/// 发送一轮对话并输出回复，返回回复的内容
+async fn chat_turn(sdk: &OpenaiSdk, args: &ChatArgs, session: &Session, json: bool) -> Result<String> {
+    let mut builder = ChatCompletionRequestBuilder::default();
+    builder.messages(session.chat_messages()?);
+    if let Some(model) = &args.model {
+        builder.model(model.clone());
+    }
+    if let Some(temperature) = args.temperature {
+        builder.temperature(temperature);
+    }
+    let req = builder.build()?;
+
+    if json || args.no_stream {
+        let res = sdk.chat_completion(req).await?;
+        let content = res.choices.first().and_then(|c| c.message.content.clone()).unwrap_or_default();
+        if json {
+            let usage = res.usage.as_ref().map(|u| json!({
+                "prompt_tokens": u.prompt_tokens, "completion_tokens": u.completion_tokens, "total_tokens": u.total_tokens,
+            }));
+            print_json(&json!({"id": res.id, "model": res.model, "content": content, "usage": usage}))?;
+        } else {
+            println!("{content}");
+        }
+        return Ok(content);
+    }
+
+    let mut stream = sdk.chat_completion_stream(req).await?;
+    let mut content = String::new();
+    let mut stdout = std::io::stdout();
+    while let Some(chunk) = stream.next().await {
+        if let Some(delta) = chunk?.choices.first().and_then(|c| c.delta.content.clone()) {
+            stdout.write_all(delta.as_bytes())?;
+            stdout.flush()?;
+            content.push_str(&delta);
+        }
+    }
+    println!();
+    Ok(content)
+}
+
+async fn image(sdk: &OpenaiSdk, args: ImageArgs, json: bool) -> Result<()> {
+    let prompt = read_input(&args.input)?.ok_or_else(|| anyhow!("missing image prompt"))?;
+    let mut builder = CreateImageRequestBuilder::default();
+    builder.prompt(prompt).response_format(ImageResponseFormat::B64Json);
+    if let Some(model) = args.model {
+        builder.model(model);
+    }
+    if let Some(size) = args.size {
+        builder.size(size);
+    }
+    if let Some(quality) = args.quality {
+        builder.quality(quality);
+    }
+    if let Some(count) = args.count {
+        builder.n(count);
+    }
+    let res = sdk.create_image(builder.build()?).await?;
+
+    let mut files = Vec::new();
+    for (index, image) in res.data.iter().enumerate() {
+        let path = numbered_path(&args.output, index, res.data.len());
+        let data = image.b64_json.as_deref().ok_or_else(|| anyhow!("image response has no b64_json data"))?;
+        std::fs::write(&path, base64::engine::general_purpose::STANDARD.decode(data)?)?;
+        files.push(json!({"path": path, "revised_prompt": image.revised_prompt}));
+    }
+    if json {
+        print_json(&json!({"created": res.created, "images": files}))?;
+    } else {
+        files.iter().for_each(|f| println!("{}", f["path"].as_str().unwrap_or_default()));
+    }
+    Ok(())
+}
+
+/// 生成多个文件时在文件名后添加序号，例如 image-1.png
+fn numbered_path(path: &Path, index: usize, total: usize) -> PathBuf {
+    if total <= 1 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("image");
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{stem}-{}.{ext}", index + 1),
+        None => format!("{stem}-{}", index + 1),
+    };
+    path.with_file_name(name)
+}
+
+async fn embed(sdk: &OpenaiSdk, args: EmbedArgs, json: bool) -> Result<()> {
+    let text = read_input(&args.input)?.ok_or_else(|| anyhow!("missing text to embed"))?;
+    let mut builder = EmbeddingRequestBuilder::default();
+    builder.input(text);
+    if let Some(model) = args.model {
+        builder.model(model);
+    }
+    if let Some(dimensions) = args.dimensions {
+        builder.dimensions(dimensions);
+    }
+    let res = sdk.create_embedding(builder.build()?).await?;
+    let embedding = res.data.into_iter().next().map(|e| e.embedding).unwrap_or_default();
+    if json {
+        print_json(&json!({"model": res.model.as_str(), "embedding": embedding, "total_tokens": res.usage.total_tokens}))
+    } else {
+        println!("{}", serde_json::to_string(&embedding)?);
+        Ok(())
+    }
+}
+
+async fn transcribe(sdk: &OpenaiSdk, args: TranscribeArgs, json: bool) -> Result<()> {
+    let mut builder = TranscriptionRequestBuilder::default();
+    builder.filename(file_name(&args.file)?).content(tokio::fs::read(&args.file).await?);
+    if let Some(model) = args.model {
+        builder.model(model);
+    }
+    if let Some(language) = args.language {
+        builder.language(language);
+    }
+    if let Some(prompt) = args.prompt {
+        builder.prompt(prompt);
+    }
+    let res = sdk.create_transcription(builder.build()?).await?;
+    if json {
+        print_json(&json!({"text": res.text}))
+    } else {
+        println!("{}", res.text);
+        Ok(())
+    }
+}
+
+async fn batch(sdk: &OpenaiSdk, command: BatchCommand, json: bool) -> Result<()> {
+    let batch = match command {
+        BatchCommand::Submit { file, endpoint } => {
+            let upload = UploadFileRequest::from_path(&file, FilePurpose::Batch).await?;
+            let input = sdk.upload_file(upload).await?;
+            sdk.create_batch(CreateBatchRequest::new(input.id, endpoint)).await?
+        }
+        BatchCommand::Status { id } => sdk.retrieve_batch(&id).await?,
+        BatchCommand::Download { id, output, errors } => {
+            let batch = sdk.retrieve_batch(&id).await?;
+            let file_id = if errors { batch.error_file_id } else { batch.output_file_id };
+            let Some(file_id) = file_id else {
+                bail!("batch {id} has no {} file (status: {:?})", if errors { "error" } else { "output" }, batch.status);
+            };
+            let content = sdk.file_content(&file_id).await?;
+            match output {
+                Some(path) => std::fs::write(path, &content)?,
+                None => std::io::stdout().write_all(&content)?,
+            }
+            return Ok(());
+        }
+    };
+    if json {
+        print_json(&json!({
+            "id": batch.id,
+            "status": batch.status,
+            "endpoint": batch.endpoint,
+            "input_file_id": batch.input_file_id,
+            "output_file_id": batch.output_file_id,
+            "error_file_id": batch.error_file_id,
+            "request_counts": {
+                "total": batch.request_counts.total,
+                "completed": batch.request_counts.completed,
+                "failed": batch.request_counts.failed,
+            },
+        }))
+    } else {
+        let counts = &batch.request_counts;
+        println!("{}\t{:?}\t{}/{} completed, {} failed", batch.id, batch.status, counts.completed, counts.total, counts.failed);
+        Ok(())
+    }
+}
+
+async fn list_models(sdk: &OpenaiSdk, json: bool) -> Result<()> {
+    let mut models = sdk.list_models().await?.data;
+    models.sort_by(|a, b| a.id.cmp(&b.id));
+    if json {
+        let models: Vec<_> = models.iter().map(|m| json!({"id": m.id, "created": m.created, "owned_by": m.owned_by})).collect();
+        print_json(&models)
+    } else {
+        models.iter().for_each(|m| println!("{}", m.id));
+        Ok(())
+    }
+}
+
+
+///
+/// 读取输入文本: 命令行参数(`-` 表示标准输入)、`--file` 指定的文件，
+/// 都没有传入时，如果标准输入不是终端(管道或重定向)则读取标准输入，否则返回 None
+///
+fn read_input(input: &Input) -> Result<Option<String>> {
+    let text = match (&input.text, &input.file) {
+        (Some(text), _) if text != "-" => text.clone(),
+        (_, Some(file)) => std::fs::read_to_string(file)?,
+        (Some(_), None) => read_stdin()?,
+        (None, None) if !std::io::stdin().is_terminal() => read_stdin()?,
+        (None, None) => return Ok(None),
+    };
+    let text = text.trim();
+    Ok((!text.is_empty()).then(|| text.to_string()))
+}
+
+fn read_stdin() -> Result<String> {
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text)?;
+    Ok(text)
+}
+
+fn file_name(path: &Path) -> Result<String> {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(String::from)
+        .ok_or_else(|| anyhow!("invalid file name: {}", path.display()))
+}
+
+fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// 使用 serde 将命令行参数解析为 SDK 中的枚举，例如 `dall-e-3`、`1024x1024`
+fn parse<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_string())).map_err(|e| e.to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+    use super::*;
+
+    #[test]
+    fn cli_should_parse_arguments() {
+        Cli::command().debug_assert();
+        let cli = Cli::try_parse_from([
+            "openai", "--api-key", "sk-test", "image", "a cat", "--size", "1792x1024", "--model", "dall-e-2", "--json",
+        ]).unwrap();
+        assert!(cli.json);
+        let Command::Image(args) = cli.command else { panic!("expected image command") };
+        assert_eq!(args.input.text.as_deref(), Some("a cat"));
+        assert_eq!(args.size, Some(ImageSize::LargeWide));
+        assert_eq!(args.model, Some(ImageModel::DallE2));
+        assert!(Cli::try_parse_from(["openai", "--api-key", "k", "image", "--size", "10x10"]).is_err());
+    }
+
+    #[test]
+    fn session_should_round_trip() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("openai-cli-session-{}.json", std::process::id()));
+        let mut session = Session::default();
+        session.push("system", "be brief");
+        session.push("user", "hi");
+        session.push("assistant", "hello");
+        session.save(&path)?;
+
+        let loaded = Session::load(&path)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(loaded.messages, session.messages);
+        let messages = serde_json::to_value(loaded.chat_messages()?)?;
+        assert_eq!(messages[2], json!({"role": "assistant", "content": "hello"}));
+        assert_eq!(numbered_path(Path::new("out/cat.png"), 1, 2), PathBuf::from("out/cat-2.png"));
+        Ok(())
+    }
+}
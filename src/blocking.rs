@@ -0,0 +1,372 @@
+//!
+//! 同步(阻塞)客户端
+//! 在内部的单线程 tokio 运行时上执行异步的 `crate::OpenaiSdk`，请求体与响应体类型完全相同，
+//! 适用于不使用异步运行时的命令行工具等场景;不能在异步上下文中调用(会 panic)。
+//! Realtime API 基于 WebSocket 长连接，只提供异步版本。
+//!
+
+use std::{path::Path, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+use tokio::runtime::Runtime;
+
+use crate::api::*;
+use crate::azure::AzureConfig;
+use crate::cache::ResponseCache;
+use crate::cassette::CassetteMode;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::cost::CostTracker;
+use crate::credential::CredentialProvider;
+use crate::key_pool::KeyPool;
+use crate::meta::WithMeta;
+use crate::middleware::Middleware;
+use crate::rate_limit::RateLimiter;
+use crate::semantic_cache::SemanticCache;
+use crate::stream::EventStream;
+use crate::transport::Transport;
+use crate::TypedRequest;
+
+///
+/// 同步版本的 SDK，方法与 `crate::OpenaiSdk` 一一对应，流式接口返回迭代器
+///
+#[derive(Debug, Clone)]
+pub struct OpenaiSdk{
+    inner: crate::OpenaiSdk,
+    runtime: Arc<Runtime>,
+}
+
+/// 生成配置方法: 修改内部的异步 SDK 后返回自身
+macro_rules! with_methods {
+    ($( $(#[$attr:meta])* fn $name:ident($($arg:ident: $ty:ty),*); )*) => {
+        $(
+            $(#[$attr])*
+            pub fn $name(mut self $(, $arg: $ty)*) -> Self{
+                self.inner = self.inner.$name($($arg),*);
+                self
+            }
+        )*
+    };
+}
+
+/// 生成接口方法: 在内部运行时上阻塞等待异步方法的结果
+macro_rules! blocking_methods {
+    ($( $(#[$attr:meta])* fn $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty; )*) => {
+        $(
+            $(#[$attr])*
+            pub fn $name(&self $(, $arg: $ty)*) -> Result<$ret>{
+                self.runtime.block_on(self.inner.$name($($arg),*))
+            }
+        )*
+    };
+}
+
+impl OpenaiSdk {
+
+    ///
+    /// 传入openai的apikey，并且初始化网络请求客户端与内部运行时
+    ///
+    pub fn new(token: String) -> Self{
+        crate::OpenaiSdk::new(token).into()
+    }
+
+    ///
+    /// 使用 Azure OpenAI 资源初始化，认证信息由配置提供
+    ///
+    pub fn azure(config: AzureConfig) -> Self{
+        crate::OpenaiSdk::azure(config).into()
+    }
+
+    ///
+    /// 开启录制/回放模式，`path` 为录制文件的路径
+    ///
+    pub fn with_cassette(mut self, path: impl AsRef<Path>, mode: CassetteMode) -> Result<Self>{
+        self.inner = self.inner.with_cassette(path, mode)?;
+        Ok(self)
+    }
+
+    with_methods! {
+        /// 设置接口的基础地址，例如 `http://localhost:8080/v1`
+        fn with_base_url(base_url: impl Into<String>);
+        /// 设置凭证提供者，每次构建请求时获取 api key
+        fn with_credentials(provider: impl CredentialProvider + 'static);
+        /// 设置发送请求的传输层，例如测试中使用 `MockTransport`
+        fn with_transport(transport: impl Transport + 'static);
+        /// 使用多个 API key 轮换发送请求，key 被限流或认证失败时自动切换到下一个 key
+        fn with_key_pool(pool: KeyPool);
+        /// 注册中间件，可以修改类型化的请求、添加请求头、查看响应或者直接返回响应
+        fn with_middleware(middleware: impl Middleware + 'static);
+        /// 开启熔断，接口持续失败时直接返回 `CircuitOpen` 错误，不再等待超时
+        fn with_circuit_breaker(breaker: CircuitBreaker);
+        /// 开启客户端限流，按模型跟踪每分钟请求数与令牌数，额度不足时排队等待
+        fn with_rate_limiter(limiter: RateLimiter);
+        /// 开启响应缓存，相同的 `chat_completion`/`create_image` 请求直接返回缓存的响应
+        fn with_cache(cache: ResponseCache);
+        /// 开启语义缓存，`chat_completion` 的问题与已缓存的问题足够相似时直接返回缓存的响应
+        fn with_semantic_cache(cache: SemanticCache);
+        /// 开启成本统计，按照价格表累计花费，并在请求会超出预算时返回 `BudgetExceeded` 错误
+        fn with_cost_tracker(tracker: CostTracker);
+        /// 设置成本统计使用的标签(例如租户ID)
+        fn with_cost_label(label: impl Into<String>);
+    }
+
+    ///
+    /// 在 tracing span 中记录请求体与响应体(提示词与生成内容)，默认不记录
+    ///
+    #[cfg(feature = "tracing")]
+    pub fn with_trace_bodies(mut self, enabled: bool) -> Self{
+        self.inner = self.inner.with_trace_bodies(enabled);
+        self
+    }
+
+    ///
+    /// 获取成本统计器，用于查询花费
+    ///
+    pub fn cost_tracker(&self) -> Option<&CostTracker>{
+        self.inner.cost_tracker()
+    }
+
+    ///
+    /// 获取熔断器，用于查询各个接口的状态
+    ///
+    pub fn circuit_breaker(&self) -> Option<&CircuitBreaker>{
+        self.inner.circuit_breaker()
+    }
+
+    ///
+    /// 获取 API key 池，用于查询各个 key 的状态与使用量
+    ///
+    pub fn key_pool(&self) -> Option<&KeyPool>{
+        self.inner.key_pool()
+    }
+
+    ///
+    /// 内部的异步 SDK
+    ///
+    pub fn inner(&self) -> &crate::OpenaiSdk{
+        &self.inner
+    }
+
+    blocking_methods! {
+        /// 文字聊天类型 api请求发送
+        fn chat_completion(req: ChatCompletionRequest) -> ChatCompletionResponse;
+        /// 生成图片 api 请求发送
+        fn create_image(req: CreateImageRequest) -> CreateImageResponse;
+        /// 向量嵌入 api 请求发送
+        fn create_embedding(req: EmbeddingRequest) -> EmbeddingResponse;
+        /// 文本补全(instruct模型) api 请求发送
+        fn completion(req: CompletionRequest) -> CompletionResponse;
+        /// 创建模型响应(Responses API)
+        fn create_response(req: ResponsesRequest) -> Response;
+
+        /// 语音转文字
+        fn create_transcription(req: TranscriptionRequest) -> Transcription;
+
+        /// 创建批处理
+        fn create_batch(req: CreateBatchRequest) -> Batch;
+        /// 获取批处理
+        fn retrieve_batch(batch_id: &str) -> Batch;
+        /// 取消批处理
+        fn cancel_batch(batch_id: &str) -> Batch;
+        /// 列出批处理
+        fn list_batches(query: ListQuery) -> ListResponse<Batch>;
+        /// 轮询批处理直到结束
+        fn poll_batch(batch_id: &str, interval: Duration) -> Batch;
+
+        /// 上传文件
+        fn upload_file(req: UploadFileRequest) -> FileObject;
+        /// 列出已上传的文件
+        fn list_files() -> ListResponse<FileObject>;
+        /// 获取文件信息
+        fn retrieve_file(file_id: &str) -> FileObject;
+        /// 删除文件
+        fn delete_file(file_id: &str) -> DeleteResponse;
+        /// 下载文件内容
+        fn file_content(file_id: &str) -> Bytes;
+
+        /// 列出可用的模型
+        fn list_models() -> ListResponse<ModelObject>;
+        /// 获取模型信息
+        fn retrieve_model(model: &str) -> ModelObject;
+
+        /// 创建助手
+        fn create_assistant(req: CreateAssistantRequest) -> Assistant;
+        /// 获取助手
+        fn retrieve_assistant(assistant_id: &str) -> Assistant;
+        /// 修改助手
+        fn update_assistant(assistant_id: &str, req: ModifyAssistantRequest) -> Assistant;
+        /// 列出助手
+        fn list_assistants(query: ListQuery) -> ListResponse<Assistant>;
+        /// 删除助手
+        fn delete_assistant(assistant_id: &str) -> DeleteResponse;
+        /// 创建线程
+        fn create_thread(req: CreateThreadRequest) -> Thread;
+        /// 获取线程
+        fn retrieve_thread(thread_id: &str) -> Thread;
+        /// 删除线程
+        fn delete_thread(thread_id: &str) -> DeleteResponse;
+        /// 在线程中添加消息
+        fn create_message(thread_id: &str, req: CreateMessageRequest) -> ThreadMessage;
+        /// 列出线程中的消息
+        fn list_messages(thread_id: &str, query: ListQuery) -> ListResponse<ThreadMessage>;
+        /// 创建运行
+        fn create_run(thread_id: &str, req: CreateRunRequest) -> Run;
+        /// 获取运行
+        fn retrieve_run(thread_id: &str, run_id: &str) -> Run;
+        /// 取消运行
+        fn cancel_run(thread_id: &str, run_id: &str) -> Run;
+        /// 提交工具调用的输出
+        fn submit_tool_outputs(thread_id: &str, run_id: &str, req: SubmitToolOutputsRequest) -> Run;
+        /// 列出运行的步骤
+        fn list_run_steps(thread_id: &str, run_id: &str, query: ListQuery) -> ListResponse<RunStep>;
+        /// 轮询运行直到结束或者需要调用工具
+        fn poll_run(thread_id: &str, run_id: &str, options: impl Into<PollOptions>) -> Run;
+
+        /// 创建向量存储
+        fn create_vector_store(req: CreateVectorStoreRequest) -> VectorStore;
+        /// 获取向量存储
+        fn retrieve_vector_store(vector_store_id: &str) -> VectorStore;
+        /// 修改向量存储
+        fn update_vector_store(vector_store_id: &str, req: ModifyVectorStoreRequest) -> VectorStore;
+        /// 列出向量存储
+        fn list_vector_stores(query: ListQuery) -> ListResponse<VectorStore>;
+        /// 删除向量存储
+        fn delete_vector_store(vector_store_id: &str) -> DeleteResponse;
+        /// 向向量存储添加文件
+        fn create_vector_store_file(vector_store_id: &str, req: CreateVectorStoreFileRequest) -> VectorStoreFile;
+        /// 获取向量存储中的文件
+        fn retrieve_vector_store_file(vector_store_id: &str, file_id: &str) -> VectorStoreFile;
+        /// 列出向量存储中的文件
+        fn list_vector_store_files(vector_store_id: &str, query: ListQuery) -> ListResponse<VectorStoreFile>;
+        /// 从向量存储中删除文件
+        fn delete_vector_store_file(vector_store_id: &str, file_id: &str) -> DeleteResponse;
+        /// 批量向向量存储添加文件
+        fn create_vector_store_file_batch(vector_store_id: &str, req: CreateFileBatchRequest) -> VectorStoreFileBatch;
+        /// 获取文件批次
+        fn retrieve_vector_store_file_batch(vector_store_id: &str, batch_id: &str) -> VectorStoreFileBatch;
+        /// 取消文件批次
+        fn cancel_vector_store_file_batch(vector_store_id: &str, batch_id: &str) -> VectorStoreFileBatch;
+        /// 轮询文件批次直到处理结束
+        fn poll_vector_store_file_batch(vector_store_id: &str, batch_id: &str, options: impl Into<PollOptions>) -> VectorStoreFileBatch;
+        /// 在向量存储中检索
+        fn search_vector_store(vector_store_id: &str, req: VectorStoreSearchRequest) -> VectorStoreSearchResponse;
+        /// 上传目录下的所有文件并添加到向量存储
+        fn upload_directory_to_vector_store(vector_store_id: &str, dir: impl AsRef<Path>) -> Vec<VectorStoreFileBatch>;
+    }
+
+    ///
+    /// 创建运行并等待其结束，运行需要调用函数时使用 `handler` 执行工具调用并提交输出，超过 `poll.max_wait` 时返回 `PollTimeout` 错误
+    ///
+    pub fn run_with_tools<F>(&self, thread_id: &str, req: CreateRunRequest, poll: impl Into<PollOptions>, mut handler: F) -> Result<Run>
+    where
+        F: FnMut(ToolCall) -> Result<String>,
+    {
+        self.runtime.block_on(self.inner.run_with_tools(thread_id, req, poll, |call| std::future::ready(handler(call))))
+    }
+
+    ///
+    /// 文字聊天类型 api请求发送，以迭代器逐块返回消息内容
+    ///
+    pub fn chat_completion_stream(&self, req: ChatCompletionRequest) -> Result<BlockingStream<ChatCompletionChunk>>{
+        let stream = self.runtime.block_on(self.inner.chat_completion_stream(req))?;
+        Ok(BlockingStream { stream, runtime: self.runtime.clone() })
+    }
+
+    ///
+    /// 文本补全(instruct模型) api 请求发送，以迭代器逐块返回补全结果
+    ///
+    pub fn completion_stream(&self, req: CompletionRequest) -> Result<BlockingStream<CompletionResponse>>{
+        let stream = self.runtime.block_on(self.inner.completion_stream(req))?;
+        Ok(BlockingStream { stream, runtime: self.runtime.clone() })
+    }
+
+    ///
+    /// 创建模型响应(Responses API)，以迭代器逐个返回事件
+    ///
+    pub fn create_response_stream(&self, req: ResponsesRequest) -> Result<BlockingStream<ResponseStreamEvent>>{
+        let stream = self.runtime.block_on(self.inner.create_response_stream(req))?;
+        Ok(BlockingStream { stream, runtime: self.runtime.clone() })
+    }
+
+    ///
+    /// 发送请求，返回解析后的响应体以及响应头、耗时、重试次数等元数据
+    ///
+    pub fn send_with_meta<R: TypedRequest + 'static>(&self, req: R) -> Result<WithMeta<R::Response>>{
+        self.runtime.block_on(self.inner.send_with_meta(req))
+    }
+
+    ///
+    /// 调用任意接口方法，返回其结果以及最后一次请求的响应元数据，见 `crate::OpenaiSdk::with_meta`
+    ///
+    pub fn with_meta<T>(&self, call: impl FnOnce(&OpenaiSdk) -> Result<T>) -> Result<WithMeta<T>>{
+        let sink = Arc::new(std::sync::Mutex::new(None));
+        let sdk = OpenaiSdk { inner: crate::OpenaiSdk { meta_sink: Some(sink.clone()), ..self.inner.clone() }, runtime: self.runtime.clone() };
+        let data = call(&sdk)?;
+        let meta = sink.lock().unwrap().take().ok_or_else(|| anyhow::anyhow!("no request was sent, response meta is unavailable"))?;
+        Ok(WithMeta { data, meta })
+    }
+}
+
+/// 基于已配置好的异步 SDK 创建同步版本
+impl From<crate::OpenaiSdk> for OpenaiSdk {
+    fn from(inner: crate::OpenaiSdk) -> Self {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to build tokio runtime for blocking client");
+        Self { inner, runtime: Arc::new(runtime) }
+    }
+}
+
+
+///
+/// 同步版本的事件数据流，每次迭代阻塞等待下一个数据块
+///
+pub struct BlockingStream<T>{
+    stream: EventStream<T>,
+    runtime: Arc<Runtime>,
+}
+
+impl<T: DeserializeOwned> Iterator for BlockingStream<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.stream.next())
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use reqwest::Method;
+    use serde_json::json;
+    use crate::transport::{MockTransport, MockResponse};
+    use super::*;
+
+    #[test]
+    fn blocking_client_should_call_without_runtime() -> Result<()>{
+        let mock = MockTransport::new()
+            .on(Method::POST, "chat/completions", MockResponse::sse(vec![
+                json!({"id": "chatcmpl-1", "object": "chat.completion.chunk", "created": 1, "model": "gpt-3.5-turbo-1106",
+                    "choices": [{"index": 0, "delta": {"role": "assistant", "content": "Hello"}, "finish_reason": null}]}),
+                json!({"id": "chatcmpl-1", "object": "chat.completion.chunk", "created": 1, "model": "gpt-3.5-turbo-1106",
+                    "choices": [{"index": 0, "delta": {"content": " world"}, "finish_reason": "stop"}]}),
+            ]))
+            .on(Method::GET, "models", MockResponse::json(json!({
+                "object": "list", "data": [{"id": "gpt-4o", "object": "model", "created": 1, "owned_by": "openai"}]
+            })));
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock);
+
+        let models = sdk.list_models()?;
+        assert_eq!(models.data[0].id, "gpt-4o");
+
+        let req = ChatCompletionRequestBuilder::default().messages(vec![ChatMessage::new_user("hi", "")]).build()?;
+        let content = sdk.chat_completion_stream(req)?
+            .map(|chunk| Ok(chunk?.choices[0].delta.content.clone().unwrap_or_default()))
+            .collect::<Result<String>>()?;
+        assert_eq!(content, "Hello world");
+        Ok(())
+    }
+}
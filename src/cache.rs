@@ -0,0 +1,311 @@
+//!
+//! 响应缓存
+//! 对相同的请求(例如固定 `seed` 且 `temperature` 为 0 的评测请求)直接返回缓存的响应，不再请求接口;
+//! 缓存键为实际请求的地址(包括自定义的基础地址与 Azure 部署)与规范化(字段按字母排序)后的请求体的 SHA-256 摘要。
+//! 图片生成返回的地址一段时间后失效，以地址形式返回的图片最多缓存 50 分钟。
+//! 目前缓存 `chat_completion` 与 `create_image` 的响应，通过 `OpenaiSdk::with_cache` 开启，
+//! 单个请求可以通过请求体的 `bypass_cache` 跳过缓存。
+//!
+
+use std::{fmt::Debug, num::NonZeroUsize, path::PathBuf, sync::{Mutex, atomic::{AtomicU64, Ordering}}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use lru::LruCache;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// 图片地址的缓存有效期，图片地址在生成一小时后失效
+pub(crate) const IMAGE_URL_TTL: Duration = Duration::from_secs(50 * 60);
+
+/// 磁盘缓存临时文件的序号
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// 缓存的响应
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct CacheEntry{
+    /// 写入缓存的时间(Unix 时间戳，秒)
+    pub stored_at: u64,
+    /// 该响应的过期时间(Unix 时间戳，秒)，例如包含临时地址的响应，未设置时只受缓存的有效期限制
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// 响应体
+    pub body: Value,
+}
+
+///
+/// 缓存的存储后端
+///
+pub trait CacheStore: Debug + Send + Sync {
+    /// 读取缓存，不存在时返回 None
+    fn get(&self, key: &str) -> BoxFuture<'_, Result<Option<CacheEntry>>>;
+    /// 写入缓存
+    fn put(&self, key: &str, entry: CacheEntry) -> BoxFuture<'_, Result<()>>;
+}
+
+
+///
+/// 内存中的 LRU 缓存，超出容量时淘汰最久未使用的响应
+///
+#[derive(Debug)]
+pub struct MemoryStore{
+    entries: Mutex<LruCache<String, CacheEntry>>,
+}
+
+impl MemoryStore {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self { entries: Mutex::new(LruCache::new(capacity)) }
+    }
+}
+
+impl CacheStore for MemoryStore {
+    fn get(&self, key: &str) -> BoxFuture<'_, Result<Option<CacheEntry>>> {
+        let entry = self.entries.lock().unwrap().get(key).cloned();
+        Box::pin(async move { Ok(entry) })
+    }
+
+    fn put(&self, key: &str, entry: CacheEntry) -> BoxFuture<'_, Result<()>> {
+        self.entries.lock().unwrap().put(key.to_string(), entry);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+
+///
+/// 磁盘缓存，每个响应保存为目录下的一个 Json 文件(`<key>.json`)，进程重启后仍然有效
+///
+#[derive(Debug)]
+pub struct DiskStore{
+    dir: PathBuf,
+}
+
+impl DiskStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+}
+
+impl CacheStore for DiskStore {
+    fn get(&self, key: &str) -> BoxFuture<'_, Result<Option<CacheEntry>>> {
+        let path = self.path(key);
+        Box::pin(async move {
+            match tokio::fs::read(&path).await {
+                Ok(content) => Ok(Some(serde_json::from_slice(&content)?)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+
+    fn put(&self, key: &str, entry: CacheEntry) -> BoxFuture<'_, Result<()>> {
+        let path = self.path(key);
+        Box::pin(async move {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            // 先写入临时文件再重命名，避免并发读取到不完整的文件;临时文件名包含进程ID与序号，并发写入同一个键时互不影响
+            let tmp = path.with_extension(format!("json.{}-{}.tmp", std::process::id(), TMP_COUNTER.fetch_add(1, Ordering::Relaxed)));
+            tokio::fs::write(&tmp, serde_json::to_vec(&entry)?).await?;
+            tokio::fs::rename(&tmp, &path).await?;
+            Ok(())
+        })
+    }
+}
+
+
+///
+/// 响应缓存，通过 `OpenaiSdk::with_cache` 开启
+///
+#[derive(Debug)]
+pub struct ResponseCache{
+    store: Box<dyn CacheStore>,
+    /// 缓存的有效期，未设置时永久有效
+    ttl: Option<Duration>,
+}
+
+impl ResponseCache {
+
+    /// 使用自定义的存储后端
+    pub fn new(store: impl CacheStore + 'static) -> Self {
+        Self { store: Box::new(store), ttl: None }
+    }
+
+    /// 内存中的 LRU 缓存，最多保存 `capacity` 个响应
+    pub fn memory(capacity: usize) -> Self {
+        Self::new(MemoryStore::new(capacity))
+    }
+
+    /// 保存在指定目录下的磁盘缓存
+    pub fn disk(dir: impl Into<PathBuf>) -> Self {
+        Self::new(DiskStore::new(dir))
+    }
+
+    /// 设置缓存的有效期
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// 读取未过期的缓存，读取失败(例如缓存文件损坏)时视为未命中
+    pub(crate) async fn get(&self, key: &str) -> Option<Value> {
+        let entry = match self.store.get(key).await {
+            Ok(entry) => entry?,
+            Err(error) => {
+                log_error("read", &error);
+                return None;
+            }
+        };
+        let now = now();
+        let expired = self.ttl.is_some_and(|ttl| now.saturating_sub(entry.stored_at) >= ttl.as_secs())
+            || entry.expires_at.is_some_and(|expires_at| now >= expires_at);
+        (!expired).then_some(entry.body)
+    }
+
+    /// 写入缓存，`max_ttl` 为该响应的最长有效期;写入失败时只记录日志，不影响已经收到的响应
+    pub(crate) async fn put(&self, key: &str, body: Value, max_ttl: Option<Duration>) {
+        let stored_at = now();
+        let expires_at = max_ttl.map(|ttl| stored_at + ttl.as_secs());
+        if let Err(error) = self.store.put(key, CacheEntry { stored_at, expires_at, body }).await {
+            log_error("write", &error);
+        }
+    }
+}
+
+/// 开启 `tracing` 特性时记录缓存读写失败
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+fn log_error(action: &str, error: &anyhow::Error) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(%error, "failed to {action} response cache");
+}
+
+///
+/// 计算缓存键: 实际请求的地址与规范化后的请求体的 SHA-256 摘要(十六进制)
+/// `serde_json::Value` 中的对象按照字段名排序，因此字段顺序不同的相同请求得到相同的键
+///
+pub(crate) fn cache_key(endpoint: &str, body: &impl Serialize) -> Result<String> {
+    let body = serde_json::to_vec(&serde_json::to_value(body)?)?;
+    let mut hasher = Sha256::new();
+    hasher.update(endpoint.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(&body);
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// 当前的 Unix 时间戳(秒)
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+
+#[cfg(test)]
+mod tests{
+    use anyhow::Result;
+    use reqwest::Method;
+    use serde_json::json;
+    use crate::OpenaiSdk;
+    use crate::api::*;
+    use crate::transport::{MockTransport, MockResponse};
+    use super::*;
+
+    fn chat_mock() -> MockTransport {
+        MockTransport::new().on(Method::POST, "chat/completions", MockResponse::json(json!({
+            "id": "chatcmpl-1", "object": "chat.completion", "created": 1, "model": "gpt-3.5-turbo-1106",
+            "choices": [{"index": 0, "message": {"role": "assistant", "content": "4"}, "finish_reason": "stop"}]
+        })))
+    }
+
+    fn chat_request(bypass: bool) -> Result<ChatCompletionRequest> {
+        Ok(ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatMessage::new_user("2 + 2 = ?", "")])
+            .seed(42)
+            .temperature(0.0)
+            .bypass_cache(bypass)
+            .build()?)
+    }
+
+    #[tokio::test]
+    async fn memory_cache_should_serve_repeated_requests() -> Result<()>{
+        let mock = chat_mock();
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock.clone()).with_cache(ResponseCache::memory(16));
+        for _ in 0..3 {
+            let res = sdk.chat_completion(chat_request(false)?).await?;
+            assert_eq!(res.choices[0].message.content.as_deref(), Some("4"));
+        }
+        assert_eq!(mock.requests().len(), 1);
+
+        sdk.chat_completion(chat_request(true)?).await?;
+        assert_eq!(mock.requests().len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn disk_cache_should_persist_and_expire() -> Result<()>{
+        let dir = std::env::temp_dir().join(format!("openai-sdk-cache-{}", std::process::id()));
+        let mock = chat_mock();
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock.clone()).with_cache(ResponseCache::disk(&dir));
+        sdk.chat_completion(chat_request(false)?).await?;
+
+        // 新的 SDK 实例读取同一个目录
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock.clone()).with_cache(ResponseCache::disk(&dir));
+        sdk.chat_completion(chat_request(false)?).await?;
+        assert_eq!(mock.requests().len(), 1);
+
+        let sdk = sdk.with_cache(ResponseCache::disk(&dir).ttl(Duration::ZERO));
+        sdk.chat_completion(chat_request(false)?).await?;
+        assert_eq!(mock.requests().len(), 2);
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn disk_cache_errors_should_not_fail_requests() -> Result<()>{
+        let dir = std::env::temp_dir().join(format!("openai-sdk-cache-broken-{}", std::process::id()));
+        let mock = chat_mock();
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock.clone()).with_cache(ResponseCache::disk(&dir));
+        sdk.chat_completion(chat_request(false)?).await?;
+
+        // 损坏的缓存文件视为未命中
+        for entry in std::fs::read_dir(&dir)? {
+            std::fs::write(entry?.path(), b"not json")?;
+        }
+        sdk.chat_completion(chat_request(false)?).await?;
+        assert_eq!(mock.requests().len(), 2);
+
+        // 缓存目录无法创建时仍然返回响应
+        std::fs::remove_dir_all(&dir)?;
+        std::fs::write(&dir, b"")?;
+        sdk.chat_completion(chat_request(false)?).await?;
+        assert_eq!(mock.requests().len(), 3);
+        std::fs::remove_file(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cache_should_key_by_endpoint_and_expire_image_urls() -> Result<()>{
+        let dir = std::env::temp_dir().join(format!("openai-sdk-cache-endpoint-{}", std::process::id()));
+        let mock = chat_mock().on(Method::POST, "images/generations", MockResponse::json(json!({
+            "created": 1, "data": [{"url": "https://example.com/1.png"}]
+        })));
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock.clone()).with_cache(ResponseCache::disk(&dir));
+        sdk.chat_completion(chat_request(false)?).await?;
+        // 其他服务上的相同请求不会命中缓存
+        let other = sdk.clone().with_base_url("http://localhost:8080/v1");
+        other.chat_completion(chat_request(false)?).await?;
+        assert_eq!(mock.requests().len(), 2);
+
+        sdk.create_image(CreateImageRequest::new("a cat")).await?;
+        let mut expiring = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let entry: CacheEntry = serde_json::from_slice(&std::fs::read(entry?.path())?)?;
+            expiring.push(entry.expires_at.map(|at| at - entry.stored_at));
+        }
+        std::fs::remove_dir_all(&dir)?;
+        expiring.sort();
+        assert_eq!(expiring, [None, None, Some(IMAGE_URL_TTL.as_secs())]);
+        Ok(())
+    }
+}
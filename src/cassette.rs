@@ -0,0 +1,300 @@
+//!
+//! 录制与回放(cassette)测试模式
+//! 录制模式下将真实的请求/响应对写入 Json 文件(认证信息已脱敏)，回放模式下从文件中按照
+//! 请求方法、接口路径以及规范化后的请求体匹配并返回录制的响应，使测试可以在没有网络的环境中运行。
+//!
+
+use std::{collections::BTreeMap, path::{Path, PathBuf}, sync::{Arc, Mutex}};
+
+use anyhow::{Result, anyhow, bail};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use reqwest::{Request, Response, StatusCode};
+use serde::{Serialize, Deserialize};
+
+use crate::{OpenaiSdk, api_path, transport::Transport};
+
+/// 选择录制模式的环境变量，值为 `record` 时录制，否则回放
+pub const CASSETTE_ENV: &str = "OPENAI_CASSETTE";
+
+/// 需要脱敏的请求头
+const REDACTED_HEADERS: [&str; 2] = ["authorization", "api-key"];
+const REDACTED: &str = "[REDACTED]";
+
+/// 录制的响应头，其余响应头(例如 `openai-organization`、`set-cookie`)可能包含账号信息，不写入文件
+const RECORDED_RESPONSE_HEADERS: [&str; 6] = ["content-type", "x-request-id", "openai-model", "openai-processing-ms", "openai-version", "retry-after"];
+
+
+/// 录制/回放模式
+#[derive(Debug,Clone,Copy,PartialEq, Eq)]
+pub enum CassetteMode{
+    /// 发送真实请求，并将请求/响应对追加到文件中
+    Record,
+    /// 只从文件中读取录制的响应，不访问网络
+    Replay,
+}
+
+impl CassetteMode {
+    /// 根据 `OPENAI_CASSETTE` 环境变量选择模式，默认为回放
+    pub fn from_env() -> Self {
+        match std::env::var(CASSETTE_ENV).as_deref() {
+            Ok("record") => CassetteMode::Record,
+            _ => CassetteMode::Replay,
+        }
+    }
+}
+
+
+/// 录制文件的内容
+#[derive(Debug,Clone,Default,Serialize,Deserialize)]
+pub struct Cassette{
+    /// 按照发送顺序排列的请求/响应对
+    pub interactions: Vec<Interaction>,
+}
+
+/// 一次请求/响应
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct Interaction{
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// 录制的请求
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct RecordedRequest{
+    /// 请求方法
+    pub method: String,
+    /// 接口路径(包含查询参数)，例如 `chat/completions`
+    pub path: String,
+    /// 请求头，认证信息已替换为 `[REDACTED]`
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// 请求体，Json 请求体解析后保存，其他请求体保存为文本
+    #[serde(default)]
+    pub body: Option<serde_json::Value>,
+}
+
+/// 录制的响应
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct RecordedResponse{
+    /// 状态码
+    pub status: u16,
+    /// 响应头
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Json 响应体
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json: Option<serde_json::Value>,
+    /// 非 Json 的响应体，例如流式返回的事件流
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+impl RecordedResponse {
+    fn body(&self) -> Bytes {
+        match (&self.json, &self.text) {
+            (Some(json), _) => json.to_string().into(),
+            (None, Some(text)) => text.clone().into(),
+            (None, None) => Bytes::new(),
+        }
+    }
+}
+
+
+///
+/// 录制/回放传输层，包装实际发送请求的传输层
+///
+#[derive(Debug)]
+pub struct CassetteTransport{
+    inner: Arc<dyn Transport>,
+    path: PathBuf,
+    mode: CassetteMode,
+    state: Mutex<CassetteState>,
+    /// 录制时按顺序写入文件，保证最后写入的是最新的内容
+    write: tokio::sync::Mutex<()>,
+}
+
+#[derive(Debug)]
+struct CassetteState{
+    cassette: Cassette,
+    /// 回放模式下已经使用过的请求/响应对
+    used: Vec<bool>,
+}
+
+impl CassetteTransport {
+    ///
+    /// 创建录制/回放传输层;
+    /// 录制模式下从空文件开始录制，回放模式下文件必须存在
+    ///
+    pub fn new(inner: Arc<dyn Transport>, path: impl AsRef<Path>, mode: CassetteMode) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let cassette = match mode {
+            CassetteMode::Record => Cassette::default(),
+            CassetteMode::Replay => {
+                let content = std::fs::read(&path)
+                    .map_err(|e| anyhow!("failed to read cassette {}: {e}", path.display()))?;
+                serde_json::from_slice(&content)?
+            }
+        };
+        let used = vec![false; cassette.interactions.len()];
+        Ok(Self { inner, path, mode, state: Mutex::new(CassetteState { cassette, used }), write: tokio::sync::Mutex::new(()) })
+    }
+
+    /// 发送真实请求并录制
+    async fn record(&self, req: Request) -> Result<Response> {
+        let request = record_request(&req);
+        let res = self.inner.execute(req).await?;
+        let status = res.status();
+        let headers = response_headers(res.headers());
+        let body = res.bytes().await?;
+        let (json, text) = match serde_json::from_slice(&body) {
+            Ok(json) => (Some(json), None),
+            Err(_) => (None, Some(String::from_utf8_lossy(&body).into_owned())),
+        };
+        let response = RecordedResponse { status: status.as_u16(), headers, json, text };
+        let res = build_response(&response, body)?;
+        {
+            let mut state = self.state.lock().unwrap();
+            state.cassette.interactions.push(Interaction { request, response });
+            state.used.push(true);
+        }
+        // 在锁外写入文件，不在异步任务中阻塞线程
+        let _write = self.write.lock().await;
+        let content = serde_json::to_vec_pretty(&self.state.lock().unwrap().cassette)?;
+        if let Some(dir) = self.path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        tokio::fs::write(&self.path, content).await?;
+        Ok(res)
+    }
+
+    ///
+    /// 查找匹配的录制响应: 优先使用第一个未使用过的匹配项，全部使用过时重复使用最后一个匹配项
+    ///
+    fn replay(&self, req: &Request) -> Result<Response> {
+        let request = record_request(req);
+        let mut state = self.state.lock().unwrap();
+        let matches: Vec<usize> = state.cassette.interactions.iter()
+            .enumerate()
+            .filter(|(_, i)| i.request.method == request.method && i.request.path == request.path && i.request.body == request.body)
+            .map(|(index, _)| index)
+            .collect();
+        let Some(&index) = matches.iter().find(|&&i| !state.used[i]).or(matches.last()) else {
+            bail!("no recorded interaction for {} {} in cassette {}", request.method, request.path, self.path.display());
+        };
+        state.used[index] = true;
+        let response = &state.cassette.interactions[index].response;
+        build_response(response, response.body())
+    }
+}
+
+impl Transport for CassetteTransport {
+    fn execute(&self, req: Request) -> BoxFuture<'_, Result<Response>> {
+        Box::pin(async move {
+            match self.mode {
+                CassetteMode::Record => self.record(req).await,
+                CassetteMode::Replay => self.replay(&req),
+            }
+        })
+    }
+}
+
+
+/// 录制请求，认证请求头脱敏，Json 请求体解析为 `Value`(键有序)以便与回放时的请求比较
+fn record_request(req: &Request) -> RecordedRequest {
+    let url = req.url();
+    let path = match url.query() {
+        Some(query) => format!("{}?{query}", api_path(url)),
+        None => api_path(url).to_string(),
+    };
+    let body = req.body().and_then(|b| b.as_bytes()).map(|bytes| {
+        serde_json::from_slice(bytes).unwrap_or_else(|_| String::from_utf8_lossy(bytes).into_owned().into())
+    });
+    RecordedRequest {
+        method: req.method().to_string(),
+        path,
+        headers: header_map(req.headers(), &REDACTED_HEADERS),
+        body,
+    }
+}
+
+/// 请求头转换为有序的键值对，`redacted` 中的请求头的值替换为 `[REDACTED]`
+fn header_map(headers: &reqwest::header::HeaderMap, redacted: &[&str]) -> BTreeMap<String, String> {
+    headers.iter()
+        .map(|(name, value)| {
+            let value = if redacted.contains(&name.as_str()) {
+                REDACTED.to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+/// 只保留允许录制的响应头，以及 `x-ratelimit-*` 限流响应头
+fn response_headers(headers: &reqwest::header::HeaderMap) -> BTreeMap<String, String> {
+    header_map(headers, &[])
+        .into_iter()
+        .filter(|(name, _)| RECORDED_RESPONSE_HEADERS.contains(&name.as_str()) || name.starts_with("x-ratelimit-"))
+        .collect()
+}
+
+fn build_response(response: &RecordedResponse, body: Bytes) -> Result<Response> {
+    let mut builder = http::Response::builder().status(StatusCode::from_u16(response.status)?);
+    for (name, value) in &response.headers {
+        // 响应体已经解压并重新编码，不再保留与长度、编码相关的响应头
+        if matches!(name.as_str(), "content-length" | "content-encoding" | "transfer-encoding") {
+            continue;
+        }
+        builder = builder.header(name, value);
+    }
+    Ok(Response::from(builder.body(body)?))
+}
+
+
+impl OpenaiSdk {
+
+    ///
+    /// 开启录制/回放模式，`path` 为录制文件的路径
+    ///
+    pub fn with_cassette(self, path: impl AsRef<Path>, mode: CassetteMode) -> Result<Self>{
+        let transport = CassetteTransport::new(self.transport.clone(), path, mode)?;
+        Ok(self.with_transport(transport))
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use reqwest::Method;
+    use serde_json::json;
+    use crate::api::CreateImageRequest;
+    use crate::transport::{MockTransport, MockResponse};
+    use super::*;
+
+    #[tokio::test]
+    async fn cassette_should_record_then_replay() -> Result<()>{
+        let path = std::env::temp_dir().join(format!("openai-cassette-{}.json", std::process::id()));
+        let mock = MockTransport::new().on(Method::POST, "images/generations", MockResponse::json(json!({
+            "created": 1700000000,
+            "data": [{"url": "https://example.com/cat.png"}]
+        })).header("openai-organization", "org-secret").header("x-request-id", "req_1"));
+
+        let sdk = OpenaiSdk::new("sk-secret".into()).with_transport(mock).with_cassette(&path, CassetteMode::Record)?;
+        sdk.create_image(CreateImageRequest::new("a cat")).await?;
+        let content = std::fs::read_to_string(&path)?;
+        assert!(!content.contains("sk-secret"));
+        assert!(content.contains(REDACTED));
+        assert!(!content.contains("org-secret"));
+        assert!(content.contains("req_1"));
+
+        // 回放时不访问网络，只返回录制的响应
+        let sdk = OpenaiSdk::new("sk-other".into()).with_cassette(&path, CassetteMode::Replay)?;
+        let res = sdk.create_image(CreateImageRequest::new("a cat")).await?;
+        assert_eq!(res.data[0].url.as_deref(), Some("https://example.com/cat.png"));
+        assert!(sdk.create_image(CreateImageRequest::new("a dog")).await.is_err());
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
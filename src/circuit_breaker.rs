@@ -0,0 +1,387 @@
+//!
+//! 熔断器
+//! 按接口统计最近若干次请求的失败率(5xx、408 以及网络错误、超时)，失败率达到阈值时熔断(打开)，
+//! 熔断期间直接返回 `CircuitOpen` 错误而不发送请求;熔断时间结束后进入半开状态，放行少量探测请求，
+//! 探测成功则恢复(关闭)，失败则再次熔断。
+//! 通过 `OpenaiSdk::with_circuit_breaker` 开启，`Router` 也使用它对后端熔断。
+//!
+
+use std::{collections::{HashMap, VecDeque}, sync::Mutex, time::Duration};
+
+use reqwest::{StatusCode, Url};
+use tokio::time::Instant;
+
+use crate::error::CircuitOpen;
+
+/// 默认的失败率阈值
+const DEFAULT_FAILURE_RATE: f64 = 0.5;
+/// 默认统计的最近请求数
+const DEFAULT_WINDOW_SIZE: usize = 20;
+/// 默认的最少请求数，请求数不足时不熔断
+const DEFAULT_MINIMUM_REQUESTS: usize = 10;
+/// 默认的熔断时间
+const DEFAULT_OPEN_DURATION: Duration = Duration::from_secs(30);
+
+
+/// 熔断器的状态
+#[derive(Debug,Clone,Copy,PartialEq, Eq)]
+pub enum CircuitState{
+    /// 正常放行请求
+    Closed,
+    /// 熔断中，在 `remaining` 之后进入半开状态
+    Open{ remaining: Duration },
+    /// 半开，只放行探测请求
+    HalfOpen,
+}
+
+/// 单个接口的统计信息
+#[derive(Debug,Clone,PartialEq)]
+pub struct CircuitStats{
+    /// 当前状态
+    pub state: CircuitState,
+    /// 统计窗口内的请求数
+    pub requests: usize,
+    /// 统计窗口内的失败数
+    pub failures: usize,
+    /// 连续失败的次数
+    pub consecutive_failures: u32,
+    /// 熔断(打开)的总次数
+    pub opened: u64,
+}
+
+impl CircuitStats {
+    /// 统计窗口内的失败率
+    pub fn failure_rate(&self) -> f64 {
+        match self.requests {
+            0 => 0.0,
+            n => self.failures as f64 / n as f64,
+        }
+    }
+}
+
+
+///
+/// 按接口统计失败率的熔断器
+///
+#[derive(Debug)]
+pub struct CircuitBreaker{
+    /// 失败率阈值，取值 0~1
+    failure_rate: f64,
+    /// 统计的最近请求数
+    window_size: usize,
+    /// 窗口内请求数达到该值后才会熔断
+    minimum_requests: usize,
+    /// 熔断的时间
+    open_duration: Duration,
+    /// 半开状态下允许同时进行的探测请求数，全部成功后恢复
+    half_open_probes: u32,
+    circuits: Mutex<HashMap<String, Circuit>>,
+}
+
+#[derive(Debug,Default)]
+struct Circuit{
+    /// 最近请求的结果，true 表示失败
+    outcomes: VecDeque<bool>,
+    consecutive_failures: u32,
+    opened: u64,
+    open_until: Option<Instant>,
+    /// 半开状态
+    half_open: Option<HalfOpen>,
+}
+
+#[derive(Debug,Default)]
+struct HalfOpen{
+    in_flight: u32,
+    succeeded: u32,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CircuitBreaker {
+
+    ///
+    /// 创建熔断器，默认最近 20 次请求中至少 10 次、失败率达到 50% 时熔断 30 秒
+    ///
+    pub fn new() -> Self {
+        Self {
+            failure_rate: DEFAULT_FAILURE_RATE,
+            window_size: DEFAULT_WINDOW_SIZE,
+            minimum_requests: DEFAULT_MINIMUM_REQUESTS,
+            open_duration: DEFAULT_OPEN_DURATION,
+            half_open_probes: 1,
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 设置失败率阈值，取值 0~1，默认为 0.5
+    pub fn failure_rate(mut self, rate: f64) -> Self {
+        self.failure_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// 设置统计的最近请求数，默认为 20
+    pub fn window_size(mut self, size: usize) -> Self {
+        self.window_size = size.max(1);
+        self
+    }
+
+    /// 设置熔断所需的最少请求数，默认为 10
+    pub fn minimum_requests(mut self, requests: usize) -> Self {
+        self.minimum_requests = requests.max(1);
+        self
+    }
+
+    /// 设置熔断的时间，默认为 30 秒
+    pub fn open_duration(mut self, duration: Duration) -> Self {
+        self.open_duration = duration;
+        self
+    }
+
+    /// 设置半开状态下的探测请求数，默认为 1
+    pub fn half_open_probes(mut self, probes: u32) -> Self {
+        self.half_open_probes = probes.max(1);
+        self
+    }
+
+    /// 指定接口的状态，没有请求过的接口为 `Closed`
+    pub fn state(&self, endpoint: &str) -> CircuitState {
+        self.stats(endpoint).map_or(CircuitState::Closed, |s| s.state)
+    }
+
+    /// 指定接口的统计信息
+    pub fn stats(&self, endpoint: &str) -> Option<CircuitStats> {
+        let circuits = self.circuits.lock().unwrap();
+        circuits.get(endpoint).map(|c| c.stats(Instant::now()))
+    }
+
+    /// 所有接口的统计信息，用于上报监控指标
+    pub fn all_stats(&self) -> HashMap<String, CircuitStats> {
+        let now = Instant::now();
+        let circuits = self.circuits.lock().unwrap();
+        circuits.iter().map(|(endpoint, c)| (endpoint.clone(), c.stats(now))).collect()
+    }
+
+    /// 恢复所有接口的状态
+    pub fn reset(&self) {
+        self.circuits.lock().unwrap().clear();
+    }
+
+    ///
+    /// 请求前调用，熔断中返回 `CircuitOpen` 错误;放行时返回许可，请求结束后调用 `record` 记录结果
+    ///
+    pub(crate) fn acquire(&self, endpoint: &str) -> Result<CircuitPermit<'_>, CircuitOpen> {
+        let now = Instant::now();
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(endpoint.to_string()).or_default();
+        if let Some(until) = circuit.open_until {
+            if until > now {
+                return Err(CircuitOpen { endpoint: endpoint.to_string(), retry_after: until - now });
+            }
+            circuit.open_until = None;
+            circuit.half_open = Some(HalfOpen::default());
+        }
+        let probe = match &mut circuit.half_open {
+            Some(half_open) if half_open.in_flight + half_open.succeeded >= self.half_open_probes => {
+                // 探测请求尚未结束，其余请求继续快速失败
+                return Err(CircuitOpen { endpoint: endpoint.to_string(), retry_after: Duration::ZERO });
+            }
+            Some(half_open) => {
+                half_open.in_flight += 1;
+                true
+            }
+            None => false,
+        };
+        Ok(CircuitPermit { breaker: self, endpoint: endpoint.to_string(), probe, recorded: false })
+    }
+
+    /// 记录请求的结果
+    fn record(&self, endpoint: &str, probe: bool, failed: bool) {
+        let now = Instant::now();
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(endpoint.to_string()).or_default();
+        circuit.outcomes.push_back(failed);
+        while circuit.outcomes.len() > self.window_size {
+            circuit.outcomes.pop_front();
+        }
+        circuit.consecutive_failures = if failed { circuit.consecutive_failures + 1 } else { 0 };
+
+        if probe {
+            let Some(half_open) = circuit.half_open.as_mut() else { return };
+            half_open.in_flight = half_open.in_flight.saturating_sub(1);
+            if failed {
+                self.open(circuit, now);
+            } else {
+                half_open.succeeded += 1;
+                if half_open.succeeded >= self.half_open_probes {
+                    // 探测全部成功，清空统计后恢复
+                    circuit.half_open = None;
+                    circuit.outcomes.clear();
+                }
+            }
+            return;
+        }
+        let failures = circuit.outcomes.iter().filter(|failed| **failed).count();
+        let requests = circuit.outcomes.len();
+        if circuit.half_open.is_none() && failures > 0 && requests >= self.minimum_requests && failures as f64 >= self.failure_rate * requests as f64 {
+            self.open(circuit, now);
+        }
+    }
+
+    /// 放弃未完成的探测请求(例如请求被取消)
+    fn release(&self, endpoint: &str) {
+        let mut circuits = self.circuits.lock().unwrap();
+        if let Some(half_open) = circuits.get_mut(endpoint).and_then(|c| c.half_open.as_mut()) {
+            half_open.in_flight = half_open.in_flight.saturating_sub(1);
+        }
+    }
+
+    fn open(&self, circuit: &mut Circuit, now: Instant) {
+        circuit.open_until = Some(now + self.open_duration);
+        circuit.half_open = None;
+        circuit.opened += 1;
+    }
+}
+
+impl Circuit {
+    fn stats(&self, now: Instant) -> CircuitStats {
+        let state = match (self.open_until, &self.half_open) {
+            (Some(until), _) if until > now => CircuitState::Open { remaining: until - now },
+            // 熔断时间已经结束，下一个请求将作为探测请求
+            (Some(_), _) | (None, Some(_)) => CircuitState::HalfOpen,
+            (None, None) => CircuitState::Closed,
+        };
+        CircuitStats {
+            state,
+            requests: self.outcomes.len(),
+            failures: self.outcomes.iter().filter(|failed| **failed).count(),
+            consecutive_failures: self.consecutive_failures,
+            opened: self.opened,
+        }
+    }
+}
+
+
+/// 熔断器放行的许可，请求结束后记录结果;未记录就被丢弃时(请求被取消)不计入统计
+#[derive(Debug)]
+pub(crate) struct CircuitPermit<'a>{
+    breaker: &'a CircuitBreaker,
+    endpoint: String,
+    probe: bool,
+    recorded: bool,
+}
+
+impl CircuitPermit<'_> {
+    /// 记录请求是否失败
+    pub(crate) fn record(mut self, failed: bool) {
+        self.recorded = true;
+        self.breaker.record(&self.endpoint, self.probe, failed);
+    }
+
+    /// 根据响应状态码记录结果，`None` 表示网络错误或者超时
+    pub(crate) fn record_status(self, status: Option<StatusCode>) {
+        self.record(status.is_none_or(is_failure));
+    }
+}
+
+impl Drop for CircuitPermit<'_> {
+    fn drop(&mut self) {
+        if !self.recorded && self.probe {
+            self.breaker.release(&self.endpoint);
+        }
+    }
+}
+
+/// 计入失败的状态码: 服务端错误与请求超时;429 由限流器处理，不计入失败
+fn is_failure(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT
+}
+
+/// 对象集合的路径，其后的路径段为对象ID(例如 `files/file-abc`)
+const COLLECTIONS: &[&str] = &[
+    "assistants", "batches", "checkpoints", "deployments", "file_batches", "files", "jobs",
+    "messages", "models", "responses", "runs", "steps", "threads", "uploads", "vector_stores",
+];
+
+/// 统计使用的接口名称，路径中的对象ID(例如 `files/file-abc`、`runs/run_123`)替换为 `{id}`
+pub(crate) fn endpoint(url: &Url) -> String {
+    let mut previous = "";
+    crate::api_path(url)
+        .split('/')
+        .map(|segment| {
+            // 集合后面紧跟的另一个集合(例如 `threads/runs`)不是对象ID
+            let id = COLLECTIONS.contains(&previous) && !COLLECTIONS.contains(&segment);
+            previous = segment;
+            if id { "{id}" } else { segment }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+
+#[cfg(test)]
+mod tests{
+    use reqwest::Method;
+    use serde_json::json;
+    use crate::OpenaiSdk;
+    use crate::api::*;
+    use crate::transport::{MockTransport, MockResponse};
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn circuit_breaker_should_open_and_probe() -> anyhow::Result<()>{
+        let embedding = json!({
+            "object": "list", "model": "text-embedding-3-small", "usage": {"prompt_tokens": 1, "total_tokens": 1},
+            "data": [{"object": "embedding", "index": 0, "embedding": [1.0]}]
+        });
+        let mock = MockTransport::new()
+            .once(Method::POST, "embeddings", MockResponse::error(StatusCode::BAD_GATEWAY, "down"))
+            .once(Method::POST, "embeddings", MockResponse::error(StatusCode::BAD_GATEWAY, "down"))
+            .once(Method::POST, "embeddings", MockResponse::error(StatusCode::BAD_GATEWAY, "still down"))
+            .on(Method::POST, "embeddings", MockResponse::json(embedding));
+        let breaker = CircuitBreaker::new().minimum_requests(2).open_duration(Duration::from_secs(10));
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock.clone()).with_circuit_breaker(breaker);
+        let breaker = sdk.circuit_breaker().unwrap();
+
+        for _ in 0..2 {
+            assert!(sdk.create_embedding(EmbeddingRequest::new("hi")).await.is_err());
+        }
+        // 熔断后快速失败，不再发送请求
+        let err = sdk.create_embedding(EmbeddingRequest::new("hi")).await.unwrap_err();
+        let open = err.downcast_ref::<CircuitOpen>().unwrap();
+        assert_eq!((open.endpoint.as_str(), open.retry_after), ("embeddings", Duration::from_secs(10)));
+        assert_eq!(mock.requests().len(), 2);
+
+        // 半开状态的探测请求失败，再次熔断
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert_eq!(breaker.state("embeddings"), CircuitState::HalfOpen);
+        assert!(sdk.create_embedding(EmbeddingRequest::new("hi")).await.unwrap_err().downcast_ref::<CircuitOpen>().is_none());
+        assert!(matches!(breaker.state("embeddings"), CircuitState::Open { .. }));
+
+        // 探测请求成功后恢复
+        tokio::time::advance(Duration::from_secs(10)).await;
+        sdk.create_embedding(EmbeddingRequest::new("hi")).await?;
+        let stats = breaker.stats("embeddings").unwrap();
+        assert_eq!((stats.state, stats.opened, stats.requests), (CircuitState::Closed, 2, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn endpoint_should_replace_ids() -> anyhow::Result<()>{
+        let url = Url::parse("https://api.openai.com/v1/threads/thread_abc/runs/run_1")?;
+        assert_eq!(endpoint(&url), "threads/{id}/runs/{id}");
+        assert_eq!(endpoint(&Url::parse("https://api.openai.com/v1/chat/completions")?), "chat/completions");
+        let url = Url::parse("https://api.openai.com/v1/threads/thread_abc/runs/run_1/submit_tool_outputs")?;
+        assert_eq!(endpoint(&url), "threads/{id}/runs/{id}/submit_tool_outputs");
+        assert_eq!(endpoint(&Url::parse("https://api.openai.com/v1/threads/runs")?), "threads/runs");
+        let url = Url::parse("https://api.openai.com/v1/vector_stores/vs_1/file_batches/vsfb_2")?;
+        assert_eq!(endpoint(&url), "vector_stores/{id}/file_batches/{id}");
+        assert_eq!(endpoint(&Url::parse("https://api.openai.com/v1/fine_tuning/jobs")?), "fine_tuning/jobs");
+        let url = Url::parse("https://api.openai.com/v1/fine_tuning/jobs/ftjob-1/cancel")?;
+        assert_eq!(endpoint(&url), "fine_tuning/jobs/{id}/cancel");
+        Ok(())
+    }
+}
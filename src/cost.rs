@@ -0,0 +1,551 @@
+//!
+//! 成本统计与预算控制
+//! `PricingTable` 记录各模型的单价，可以从 Json 配置文件加载;`CostTracker` 通过 `OpenaiSdk::with_cost_tracker` 开启后，
+//! 根据响应中的令牌使用统计与生成的图像数量累计花费，并按照标签(`OpenaiSdk::with_cost_label` 或者请求体中的 `user` 字段)分别统计。
+//! 设置预算后，发送请求之前会估算本次花费，超出当天总预算或者租户预算时返回 `BudgetExceeded` 错误。
+//!
+
+use std::{collections::HashMap, path::Path, sync::{Arc, Mutex}, time::{SystemTime, UNIX_EPOCH}};
+
+use anyhow::{Result, bail};
+use reqwest::Request;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::{api::{ImageModel, ImageQuality, ImageSize, Model}, error::{BudgetExceeded, BudgetScope}, rate_limit::estimate_prompt_and_output};
+
+/// 没有自定义标签、请求体中也没有 `user` 字段时使用的标签
+pub const DEFAULT_LABEL: &str = "default";
+
+
+/// 文本模型的单价，单位为美元/百万令牌
+#[derive(Debug,Clone,Copy,PartialEq,Default,Serialize,Deserialize)]
+pub struct TokenPrice{
+    /// 输入令牌的单价
+    pub prompt: f64,
+    /// 输出令牌的单价，向量嵌入模型为 0
+    #[serde(default)]
+    pub completion: f64,
+}
+
+impl TokenPrice {
+    pub fn new(prompt: f64, completion: f64) -> Self {
+        Self { prompt, completion }
+    }
+
+    /// 按照令牌数计算花费
+    pub fn cost(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        (prompt_tokens as f64 * self.prompt + completion_tokens as f64 * self.completion) / 1_000_000.0
+    }
+}
+
+
+///
+/// 价格表: 文本模型按令牌计价，图像按模型、分辨率与质量计价
+/// 默认值为编写时的官方价格，价格变动或者使用兼容服务时，通过配置文件或者 `model`/`image` 方法覆盖
+///
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(from = "PricingFile", into = "PricingFile")]
+pub struct PricingTable{
+    /// 模型ID -> 单价
+    models: HashMap<String, TokenPrice>,
+    /// (模型, 分辨率, 质量) -> 每张图像的价格(美元)
+    images: HashMap<(ImageModel, ImageSize, ImageQuality), f64>,
+}
+
+/// 价格表的配置文件格式
+/// ```json
+/// {
+///   "models": {"gpt-4-1106-preview": {"prompt": 10.0, "completion": 30.0}},
+///   "images": [{"model": "dall-e-3", "size": "1024x1024", "quality": "hd", "price": 0.08}]
+/// }
+/// ```
+#[derive(Clone,Default,Serialize,Deserialize)]
+struct PricingFile{
+    #[serde(default)]
+    models: HashMap<String, TokenPrice>,
+    #[serde(default)]
+    images: Vec<ImagePrice>,
+}
+
+#[derive(Clone,Serialize,Deserialize)]
+struct ImagePrice{
+    model: ImageModel,
+    size: ImageSize,
+    quality: ImageQuality,
+    price: f64,
+}
+
+impl From<PricingFile> for PricingTable {
+    fn from(file: PricingFile) -> Self {
+        let images = file.images.into_iter().map(|i| ((i.model, i.size, i.quality), i.price)).collect();
+        Self { models: file.models, images }
+    }
+}
+
+impl From<PricingTable> for PricingFile {
+    fn from(table: PricingTable) -> Self {
+        let images = table.images.into_iter()
+            .map(|((model, size, quality), price)| ImagePrice { model, size, quality, price })
+            .collect();
+        Self { models: table.models, images }
+    }
+}
+
+impl Default for PricingTable {
+    fn default() -> Self {
+        use ImageModel::*;
+        use ImageQuality::*;
+        use ImageSize::*;
+        let models = [
+            ("gpt-3.5-turbo-1106", TokenPrice::new(1.0, 2.0)),
+            ("gpt-3.5-turbo-instruct", TokenPrice::new(1.5, 2.0)),
+            ("gpt-4-1106-preview", TokenPrice::new(10.0, 30.0)),
+            ("gpt-4-1106-vision-preview", TokenPrice::new(10.0, 30.0)),
+            ("text-embedding-3-small", TokenPrice::new(0.02, 0.0)),
+            ("text-embedding-3-large", TokenPrice::new(0.13, 0.0)),
+            ("text-embedding-ada-002", TokenPrice::new(0.10, 0.0)),
+        ];
+        let images = [
+            ((DallE2, Small, Standard), 0.016),
+            ((DallE2, Small, Hd), 0.016),
+            ((DallE2, Medium, Standard), 0.018),
+            ((DallE2, Medium, Hd), 0.018),
+            ((DallE2, Large, Standard), 0.02),
+            ((DallE2, Large, Hd), 0.02),
+            ((DallE3, Large, Standard), 0.04),
+            ((DallE3, LargeWide, Standard), 0.08),
+            ((DallE3, LargeTall, Standard), 0.08),
+            ((DallE3, Large, Hd), 0.08),
+            ((DallE3, LargeWide, Hd), 0.12),
+            ((DallE3, LargeTall, Hd), 0.12),
+        ];
+        Self {
+            models: models.into_iter().map(|(id, price)| (id.to_string(), price)).collect(),
+            images: images.into_iter().collect(),
+        }
+    }
+}
+
+impl PricingTable {
+
+    /// 从 Json 配置文件加载价格表，文件中没有的模型不计价
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let content = std::fs::read(path)?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    /// 设置文本模型的单价
+    pub fn model(mut self, model: &Model, price: TokenPrice) -> Self {
+        self.models.insert(model.as_str().to_string(), price);
+        self
+    }
+
+    /// 设置每张图像的价格
+    pub fn image(mut self, model: ImageModel, size: ImageSize, quality: ImageQuality, price: f64) -> Self {
+        self.images.insert((model, size, quality), price);
+        self
+    }
+
+    /// 查询文本模型的单价
+    pub fn model_price(&self, model: &str) -> Option<TokenPrice> {
+        self.models.get(model).copied()
+    }
+
+    /// 查询每张图像的价格
+    pub fn image_price(&self, model: ImageModel, size: ImageSize, quality: ImageQuality) -> Option<f64> {
+        self.images.get(&(model, size, quality)).copied()
+    }
+}
+
+
+///
+/// 成本统计器，通过 `OpenaiSdk::with_cost_tracker` 开启
+/// 只统计成功的请求，流式请求按照用量数据块记录，流结束时仍然没有收到用量则按照发送前的估算记录;
+/// 价格表中没有的模型或者图像规格无法计价，设置了预算时直接返回错误，避免绕过预算，没有设置预算时花费计为 0
+/// 预算检查发生在发送之前，同时进行中的请求不会互相预留额度，因此并发时实际花费可能略微超出预算
+///
+#[derive(Debug)]
+pub struct CostTracker{
+    pricing: PricingTable,
+    /// 当天(UTC)所有请求的总预算
+    daily_budget: Option<f64>,
+    /// 各租户(标签)的总预算
+    tenant_budgets: HashMap<String, f64>,
+    state: Mutex<CostState>,
+}
+
+#[derive(Debug,Default)]
+struct CostState{
+    /// 各标签累计的花费
+    by_label: HashMap<String, f64>,
+    /// 当天的日期(自 1970-01-01 起的天数)
+    day: u64,
+    /// 当天的花费
+    today: f64,
+}
+
+/// 一次请求的计费信息，附加在响应的扩展中，读取响应体后计算实际花费
+#[derive(Debug,Clone)]
+pub(crate) struct Charge{
+    label: String,
+    item: ChargeItem,
+    /// 发送前估算的花费，无法计价时为 0
+    estimated: f64,
+}
+
+///
+/// 流式请求的计费信息，由 `EventStream` 持有: 收到用量数据块时按照实际用量记录，
+/// 没有收到用量就被丢弃时(未开启 `include_usage`、中途断开等)按照发送前的估算记录
+///
+#[derive(Debug)]
+pub(crate) struct StreamCharge{
+    tracker: Arc<CostTracker>,
+    charge: Option<Charge>,
+}
+
+impl StreamCharge {
+    pub(crate) fn new(tracker: Arc<CostTracker>, charge: Charge) -> Self {
+        Self { tracker, charge: Some(charge) }
+    }
+
+    /// 根据数据块中的 `usage` 对象记录实际花费
+    pub(crate) fn record_usage(&mut self, usage: &Value) {
+        let Some(charge) = self.charge.take() else { return };
+        let usage = Usage::deserialize(usage).unwrap_or_default();
+        let cost = match &charge.item {
+            ChargeItem::Tokens(model) => self.tracker.pricing.model_price(model)
+                .map_or(0.0, |price| price.cost(usage.prompt_tokens, usage.completion_tokens)),
+            ChargeItem::Image(..) => charge.estimated,
+        };
+        self.tracker.add(&charge.label, cost);
+    }
+}
+
+impl Drop for StreamCharge {
+    fn drop(&mut self) {
+        if let Some(charge) = self.charge.take() {
+            self.tracker.add(&charge.label, charge.estimated);
+        }
+    }
+}
+
+/// 响应或者数据块中的令牌使用统计，兼容 Responses 接口的字段名
+#[derive(Debug,Default,Deserialize)]
+struct Usage{
+    #[serde(default, alias = "input_tokens")]
+    prompt_tokens: u64,
+    #[serde(default, alias = "output_tokens")]
+    completion_tokens: u64,
+}
+
+#[derive(Debug,Clone)]
+enum ChargeItem{
+    /// 按令牌计价的模型
+    Tokens(String),
+    /// 按张计价的图像
+    Image(ImageModel, ImageSize, ImageQuality),
+}
+
+impl std::fmt::Display for ChargeItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChargeItem::Tokens(model) => write!(f, "model {model:?}"),
+            ChargeItem::Image(model, size, quality) => write!(f, "image {model:?} {size:?} {quality:?}"),
+        }
+    }
+}
+
+impl CostTracker {
+
+    pub fn new(pricing: PricingTable) -> Self {
+        Self { pricing, daily_budget: None, tenant_budgets: HashMap::new(), state: Mutex::new(CostState::default()) }
+    }
+
+    /// 设置当天(UTC)所有请求的总预算(美元)
+    pub fn daily_budget(mut self, limit: f64) -> Self {
+        self.daily_budget = Some(limit);
+        self
+    }
+
+    /// 设置单个租户(标签)的总预算(美元)
+    pub fn tenant_budget(mut self, label: impl Into<String>, limit: f64) -> Self {
+        self.tenant_budgets.insert(label.into(), limit);
+        self
+    }
+
+    /// 累计的总花费
+    pub fn total(&self) -> f64 {
+        self.state.lock().unwrap().by_label.values().sum()
+    }
+
+    /// 指定标签累计的花费
+    pub fn spent(&self, label: &str) -> f64 {
+        self.state.lock().unwrap().by_label.get(label).copied().unwrap_or_default()
+    }
+
+    /// 各标签累计的花费
+    pub fn by_label(&self) -> HashMap<String, f64> {
+        self.state.lock().unwrap().by_label.clone()
+    }
+
+    /// 当天(UTC)的花费
+    pub fn today(&self) -> f64 {
+        let mut state = self.state.lock().unwrap();
+        state.roll_over(today());
+        state.today
+    }
+
+    /// 清空统计数据
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = CostState::default();
+    }
+
+    ///
+    /// 估算请求的花费并检查预算，返回用于记录实际花费的计费信息
+    /// `path` 为改写为自定义地址或者 Azure 部署之前的接口路径，例如 `images/generations`;
+    /// 标签优先使用 `label`，其次使用请求体中的 `user` 字段
+    ///
+    pub(crate) fn admit(&self, req: &Request, path: &str, label: Option<&str>) -> Result<Charge> {
+        let body = req.body().and_then(|b| b.as_bytes())
+            .and_then(|b| serde_json::from_slice::<Value>(b).ok())
+            .unwrap_or_default();
+        let label = label.or(body["user"].as_str()).unwrap_or(DEFAULT_LABEL).to_string();
+        let (item, estimated) = if path == "images/generations" {
+            // 未传入的参数使用服务端的默认值
+            let model = field(&body, "model").unwrap_or(ImageModel::DallE2);
+            let size = field(&body, "size").unwrap_or(ImageSize::Large);
+            let quality = field(&body, "quality").unwrap_or(ImageQuality::Standard);
+            let n = body["n"].as_u64().unwrap_or(1) as f64;
+            let price = self.pricing.image_price(model, size, quality);
+            (ChargeItem::Image(model, size, quality), price.map(|price| price * n))
+        } else {
+            // 输入按照输入单价、请求的最大输出令牌数按照输出单价估算
+            let model = body["model"].as_str().unwrap_or_default().to_string();
+            let price = self.pricing.model_price(&model);
+            let (prompt, output) = estimate_prompt_and_output(&body);
+            (ChargeItem::Tokens(model), price.map(|price| price.cost(prompt, output)))
+        };
+        let Some(estimated) = estimated else {
+            if self.has_budget(&label) {
+                bail!("no price for {item} in the pricing table, refusing to send a request that cannot be counted against the budget");
+            }
+            return Ok(Charge { label, item, estimated: 0.0 });
+        };
+        self.check(&label, estimated)?;
+        Ok(Charge { label, item, estimated })
+    }
+
+    /// 是否有适用于该标签的预算
+    fn has_budget(&self, label: &str) -> bool {
+        self.daily_budget.is_some() || self.tenant_budgets.contains_key(label)
+    }
+
+    /// 检查加上本次预估花费后是否超出预算
+    fn check(&self, label: &str, estimated: f64) -> Result<(), BudgetExceeded> {
+        let mut state = self.state.lock().unwrap();
+        state.roll_over(today());
+        if let Some(limit) = self.daily_budget {
+            if state.today + estimated > limit {
+                return Err(BudgetExceeded { scope: BudgetScope::Daily, limit, spent: state.today, estimated });
+            }
+        }
+        if let Some(&limit) = self.tenant_budgets.get(label) {
+            let spent = state.by_label.get(label).copied().unwrap_or_default();
+            if spent + estimated > limit {
+                return Err(BudgetExceeded { scope: BudgetScope::Tenant(label.to_string()), limit, spent, estimated });
+            }
+        }
+        Ok(())
+    }
+
+    /// 根据响应体中的令牌使用统计或者图像数量，记录实际花费
+    pub(crate) fn record(&self, charge: &Charge, body: &[u8]) {
+        #[derive(Deserialize)]
+        struct Body{
+            usage: Option<Usage>,
+            #[serde(default)]
+            data: Vec<Value>,
+        }
+        let Some(body) = serde_json::from_slice::<Body>(body).ok() else { return };
+        let cost = match &charge.item {
+            ChargeItem::Tokens(model) => match (self.pricing.model_price(model), body.usage) {
+                (Some(price), Some(usage)) => price.cost(usage.prompt_tokens, usage.completion_tokens),
+                _ => 0.0,
+            },
+            ChargeItem::Image(model, size, quality) => {
+                self.pricing.image_price(*model, *size, *quality).unwrap_or_default() * body.data.len() as f64
+            }
+        };
+        self.add(&charge.label, cost);
+    }
+
+    /// 累计花费
+    fn add(&self, label: &str, cost: f64) {
+        let mut state = self.state.lock().unwrap();
+        state.roll_over(today());
+        state.today += cost;
+        *state.by_label.entry(label.to_string()).or_default() += cost;
+    }
+}
+
+impl CostState {
+    /// 日期变化时清零当天的花费
+    fn roll_over(&mut self, day: u64) {
+        if self.day != day {
+            self.day = day;
+            self.today = 0.0;
+        }
+    }
+}
+
+/// 读取并反序列化 Json 请求体中的字段
+fn field<T: serde::de::DeserializeOwned>(body: &Value, key: &str) -> Option<T> {
+    serde_json::from_value(body[key].clone()).ok()
+}
+
+/// 当前的 UTC 日期(自 1970-01-01 起的天数)
+fn today() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / 86_400
+}
+
+
+#[cfg(test)]
+mod tests{
+    use anyhow::Result;
+    use reqwest::Method;
+    use serde_json::json;
+    use crate::OpenaiSdk;
+    use crate::api::*;
+    use crate::azure::{AzureAuth, AzureConfig};
+    use crate::transport::{MockTransport, MockResponse};
+    use super::*;
+
+    #[test]
+    fn pricing_table_should_load_from_json() -> Result<()>{
+        let table: PricingTable = serde_json::from_value(json!({
+            "models": {"gpt-4o": {"prompt": 2.5, "completion": 10.0}},
+            "images": [{"model": "dall-e-3", "size": "1024x1024", "quality": "hd", "price": 0.08}]
+        }))?;
+        assert_eq!(table.model_price("gpt-4o"), Some(TokenPrice::new(2.5, 10.0)));
+        assert_eq!(table.model_price("gpt-3.5-turbo-1106"), None);
+        assert_eq!(table.image_price(ImageModel::DallE3, ImageSize::Large, ImageQuality::Hd), Some(0.08));
+        assert_eq!(serde_json::from_value::<PricingTable>(serde_json::to_value(&table)?)?, table);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cost_tracker_should_accumulate_and_enforce_budgets() -> Result<()>{
+        let mock = MockTransport::new()
+            .on(Method::POST, "chat/completions", MockResponse::json(json!({
+                "id": "chatcmpl-1", "object": "chat.completion", "created": 1, "model": "gpt-3.5-turbo-1106",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": "hi"}, "finish_reason": "stop"}],
+                "usage": {"prompt_tokens": 1000, "completion_tokens": 500, "total_tokens": 1500}
+            })))
+            .on(Method::POST, "images/generations", MockResponse::json(json!({
+                "created": 1, "data": [{"url": "https://example.com/1.png"}, {"url": "https://example.com/2.png"}]
+            })));
+        let tracker = CostTracker::new(PricingTable::default()).tenant_budget("tenant-a", 0.1);
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock).with_cost_tracker(tracker);
+
+        let req = ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatMessage::new_user("hello", "")])
+            .user("alice".to_string())
+            .build()?;
+        sdk.chat_completion(req).await?;
+        let tracker = sdk.cost_tracker().unwrap();
+        assert!((tracker.spent("alice") - 0.002).abs() < 1e-9);
+
+        // dall-e-3 hd 1024x1024 每张 0.08，两张共 0.16
+        let tenant = sdk.clone().with_cost_label("tenant-a");
+        let req = CreateImageRequestBuilder::default().prompt("a cat").quality(ImageQuality::Hd).n(1).build()?;
+        tenant.create_image(req.clone()).await?;
+        assert!((tracker.spent("tenant-a") - 0.16).abs() < 1e-9);
+        assert!((tracker.today() - 0.162).abs() < 1e-9);
+
+        let err = tenant.create_image(req).await.unwrap_err();
+        let err = err.downcast_ref::<BudgetExceeded>().unwrap();
+        assert_eq!(err.scope, BudgetScope::Tenant("tenant-a".into()));
+        assert!((err.estimated - 0.08).abs() < 1e-9);
+        Ok(())
+    }
+
+    async fn admit(sdk: &OpenaiSdk, tracker: &CostTracker, req: impl crate::IntoRequest) -> Result<Charge> {
+        let (path, req) = sdk.prepare(req).await?;
+        tracker.admit(&req, &path, None)
+    }
+
+    #[tokio::test]
+    async fn cost_tracker_should_estimate_output_and_reject_unpriced() -> Result<()>{
+        let sdk = OpenaiSdk::new("sk-test".into());
+        let pricing = PricingTable::default().model(&Model::Other("gpt-4o".into()), TokenPrice::new(2.5, 10.0));
+        let tracker = CostTracker::new(pricing).daily_budget(0.5);
+
+        // 最大输出令牌数按照输出单价估算: 80000 * 10 / 1000000 = 0.8
+        let req = ChatCompletionRequestBuilder::default()
+            .model(Model::Other("gpt-4o".into()))
+            .messages(vec![ChatMessage::new_user("hi", "")])
+            .max_tokens(80_000)
+            .build()?;
+        let err = admit(&sdk, &tracker, req).await.unwrap_err();
+        let estimated = err.downcast_ref::<BudgetExceeded>().unwrap().estimated;
+        assert!((0.8..0.81).contains(&estimated));
+
+        let req = CreateImageRequestBuilder::default().prompt("a cat").model(ImageModel::DallE2).size(ImageSize::Small).build()?;
+        admit(&sdk, &tracker, req).await?;
+        assert_eq!(tracker.pricing.image_price(ImageModel::DallE2, ImageSize::Medium, ImageQuality::Standard), Some(0.018));
+
+        // 有预算时拒绝无法计价的请求
+        let req = ChatCompletionRequestBuilder::default()
+            .model(Model::Other("my-model".into()))
+            .messages(vec![ChatMessage::new_user("hi", "")])
+            .build()?;
+        let err = admit(&sdk, &tracker, req).await.unwrap_err();
+        assert!(err.to_string().contains("no price for model \"my-model\""));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cost_tracker_should_price_azure_images() -> Result<()>{
+        let mock = MockTransport::new().on(Method::POST, "images/generations", MockResponse::json(json!({
+            "created": 1, "data": [{"url": "https://example.com/1.png"}]
+        })));
+        let azure = AzureConfig::new("contoso", AzureAuth::ApiKey("azure-key".into()));
+        let tracker = CostTracker::new(PricingTable::default()).daily_budget(1.0);
+        let sdk = OpenaiSdk::azure(azure).with_transport(mock.clone()).with_cost_tracker(tracker);
+
+        // Azure 的地址为 openai/deployments/dall-e-3/images/generations，仍然按照图像计价
+        sdk.create_image(CreateImageRequest::new("a cat")).await?;
+        assert_eq!(mock.requests()[0].path, "openai/deployments/dall-e-3/images/generations");
+        assert!((sdk.cost_tracker().unwrap().today() - 0.04).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cost_tracker_should_record_streams() -> Result<()>{
+        let chunk = |usage: Value| json!({"id": "chatcmpl-1", "object": "chat.completion.chunk", "created": 1,
+            "model": "gpt-3.5-turbo-1106", "choices": [], "usage": usage});
+        let mock = MockTransport::new()
+            .once(Method::POST, "chat/completions", MockResponse::sse([chunk(json!({"prompt_tokens": 1000, "completion_tokens": 500, "total_tokens": 1500}))]))
+            .on(Method::POST, "chat/completions", MockResponse::sse([chunk(Value::Null)]));
+        let tracker = CostTracker::new(PricingTable::default()).tenant_budget("tenant-a", 0.005);
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock).with_cost_tracker(tracker).with_cost_label("tenant-a");
+        let req = || ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatMessage::new_user("hello", "")])
+            .max_tokens(1000)
+            .build();
+
+        // 按照用量数据块记录实际花费
+        let mut stream = sdk.chat_completion_stream(req()?).await?;
+        while futures::StreamExt::next(&mut stream).await.transpose()?.is_some() {}
+        let tracker = sdk.cost_tracker().unwrap();
+        assert!((tracker.spent("tenant-a") - 0.002).abs() < 1e-9);
+
+        // 没有用量数据块时按照发送前的估算记录，之后的流式请求同样受预算限制
+        drop(sdk.chat_completion_stream(req()?).await?);
+        assert!((0.004..0.0041).contains(&tracker.spent("tenant-a")));
+        let err = sdk.chat_completion_stream(req()?).await.err().unwrap();
+        assert!(err.downcast_ref::<BudgetExceeded>().is_some());
+        Ok(())
+    }
+}
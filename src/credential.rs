@@ -0,0 +1,341 @@
+//!
+//! API key 的安全保存与获取
+//! `SecretString` 保存 key 等敏感信息，`Debug`/`Display` 输出时隐藏内容，释放时清零内存;
+//! `CredentialProvider` 在每次构建请求时提供 key，内置环境变量、文件、命令输出以及自动刷新的短期令牌等实现。
+//!
+
+use std::{fmt, future::Future, path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::{Result, anyhow, bail};
+use futures::{FutureExt, future::{self, BoxFuture}};
+use reqwest::header::HeaderValue;
+use tokio::{sync::Mutex, time::Instant};
+use zeroize::{Zeroize, Zeroizing};
+
+/// 隐藏敏感信息时输出的内容
+const REDACTED: &str = "[REDACTED]";
+
+/// `CommandCredentials` 默认缓存命令输出的时间
+const DEFAULT_COMMAND_TTL: Duration = Duration::from_secs(300);
+
+
+///
+/// 敏感字符串，格式化输出时隐藏内容，释放时清零内存
+///
+#[derive(Clone,Default,PartialEq, Eq)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    /// 获取原始内容，只在需要发送时调用
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+
+///
+/// 凭证提供者，发送每个请求前调用以获取 API key;
+/// 在异步的发送流程中调用，获取过程中不能阻塞线程(例如执行命令或者请求认证服务)
+///
+pub trait CredentialProvider: fmt::Debug + Send + Sync {
+    /// 获取当前的 API key
+    fn token(&self) -> BoxFuture<'_, Result<SecretString>>;
+}
+
+/// 固定的 API key，`OpenaiSdk::new` 使用该实现
+#[derive(Debug,Clone)]
+pub struct StaticCredentials(pub SecretString);
+
+impl CredentialProvider for StaticCredentials {
+    fn token(&self) -> BoxFuture<'_, Result<SecretString>> {
+        future::ready(Ok(self.0.clone())).boxed()
+    }
+}
+
+/// 从环境变量读取 API key，每次请求时重新读取
+#[derive(Debug,Clone)]
+pub struct EnvCredentials{
+    var: String,
+}
+
+impl EnvCredentials {
+    /// 指定环境变量名称，例如 `OPENAI_API_KEY`
+    pub fn new(var: impl Into<String>) -> Self {
+        Self { var: var.into() }
+    }
+}
+
+impl CredentialProvider for EnvCredentials {
+    fn token(&self) -> BoxFuture<'_, Result<SecretString>> {
+        let token = std::env::var(&self.var)
+            .map(SecretString::from)
+            .map_err(|e| anyhow!("failed to read credential from env var {}: {e}", self.var));
+        future::ready(token).boxed()
+    }
+}
+
+/// 从文件读取 API key(去掉首尾空白)，每次请求时重新读取，适用于挂载的密钥文件
+#[derive(Debug,Clone)]
+pub struct FileCredentials{
+    path: PathBuf,
+}
+
+impl FileCredentials {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl CredentialProvider for FileCredentials {
+    fn token(&self) -> BoxFuture<'_, Result<SecretString>> {
+        Box::pin(async move {
+            let mut content = tokio::fs::read_to_string(&self.path).await
+                .map_err(|e| anyhow!("failed to read credential file {}: {e}", self.path.display()))?;
+            let token = SecretString::from(content.trim());
+            content.zeroize();
+            Ok(token)
+        })
+    }
+}
+
+///
+/// 执行命令，使用标准输出(去掉首尾空白)作为 API key，例如从密码管理器读取;
+/// 命令的输出默认缓存 5 分钟，通过 `ttl` 修改，设置为 0 时每次请求都会执行命令
+///
+#[derive(Debug)]
+pub struct CommandCredentials{
+    program: String,
+    args: Vec<String>,
+    ttl: Duration,
+    cache: TokenCache,
+}
+
+impl CommandCredentials {
+    pub fn new(program: impl Into<String>) -> Self {
+        Self { program: program.into(), args: Vec::new(), ttl: DEFAULT_COMMAND_TTL, cache: TokenCache::new(Duration::ZERO) }
+    }
+
+    /// 添加命令参数
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// 设置缓存命令输出的时间，默认为 5 分钟
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    async fn run(&self) -> Result<ExpiringToken> {
+        let output = tokio::process::Command::new(&self.program).args(&self.args).kill_on_drop(true).output().await
+            .map_err(|e| anyhow!("failed to run credential command {}: {e}", self.program))?;
+        if !output.status.success() {
+            // 错误输出可能包含敏感信息，只返回退出状态
+            bail!("credential command {} exited with {}", self.program, output.status);
+        }
+        let mut stdout = String::from_utf8(output.stdout)?;
+        let token = SecretString::from(stdout.trim());
+        stdout.zeroize();
+        if token.is_empty() {
+            bail!("credential command {} printed an empty token", self.program);
+        }
+        Ok(ExpiringToken { token, expires_in: self.ttl })
+    }
+}
+
+impl CredentialProvider for CommandCredentials {
+    fn token(&self) -> BoxFuture<'_, Result<SecretString>> {
+        Box::pin(self.cache.get_or_fetch(|| self.run()))
+    }
+}
+
+
+/// 短期令牌及其有效期
+#[derive(Debug,Clone)]
+pub struct ExpiringToken{
+    pub token: SecretString,
+    /// 从获取时起的有效时长
+    pub expires_in: Duration,
+}
+
+/// 缓存令牌直到过期前 `refresh_before`;获取新令牌期间持有异步锁，并发的请求等待同一次获取的结果
+#[derive(Debug)]
+struct TokenCache{
+    refresh_before: Duration,
+    cached: Mutex<Option<(SecretString, Instant)>>,
+}
+
+impl TokenCache {
+    fn new(refresh_before: Duration) -> Self {
+        Self { refresh_before, cached: Mutex::new(None) }
+    }
+
+    async fn get_or_fetch<Fut>(&self, fetch: impl FnOnce() -> Fut) -> Result<SecretString>
+    where
+        Fut: Future<Output = Result<ExpiringToken>>,
+    {
+        let mut cached = self.cached.lock().await;
+        if let Some((token, refresh_at)) = cached.as_ref() {
+            if Instant::now() < *refresh_at {
+                return Ok(token.clone());
+            }
+        }
+        let now = Instant::now();
+        let ExpiringToken { token, expires_in } = fetch().await?;
+        *cached = Some((token.clone(), now + expires_in.saturating_sub(self.refresh_before)));
+        Ok(token)
+    }
+}
+
+type FetchToken = dyn Fn() -> BoxFuture<'static, Result<ExpiringToken>> + Send + Sync;
+
+///
+/// 自动刷新的短期令牌，缓存获取到的令牌，在过期前(默认提前 60 秒)重新获取
+///
+pub struct RefreshingCredentials{
+    fetch: Box<FetchToken>,
+    cache: TokenCache,
+}
+
+impl fmt::Debug for RefreshingCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RefreshingCredentials")
+            .field("refresh_before", &self.cache.refresh_before)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RefreshingCredentials {
+    /// 使用获取令牌的异步函数创建，例如向认证服务换取短期令牌
+    pub fn new<F, Fut>(fetch: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<ExpiringToken>> + Send + 'static,
+    {
+        Self { fetch: Box::new(move || fetch().boxed()), cache: TokenCache::new(Duration::from_secs(60)) }
+    }
+
+    /// 缓存其他提供者返回的 key，每隔 `ttl` 重新获取一次
+    pub fn cached(provider: impl CredentialProvider + 'static, ttl: Duration) -> Self {
+        let provider = Arc::new(provider);
+        Self::new(move || {
+            let provider = provider.clone();
+            async move { Ok(ExpiringToken { token: provider.token().await?, expires_in: ttl }) }
+        }).refresh_before(Duration::ZERO)
+    }
+
+    /// 设置在过期前多久刷新令牌
+    pub fn refresh_before(mut self, margin: Duration) -> Self {
+        self.cache.refresh_before = margin;
+        self
+    }
+}
+
+impl CredentialProvider for RefreshingCredentials {
+    fn token(&self) -> BoxFuture<'_, Result<SecretString>> {
+        Box::pin(self.cache.get_or_fetch(|| (self.fetch)()))
+    }
+}
+
+
+/// 构建 `Authorization: Bearer` 请求头，拼接过程中的副本在使用后清零
+pub(crate) fn bearer(token: &SecretString) -> Result<HeaderValue> {
+    let value = Zeroizing::new(format!("Bearer {}", token.expose_secret()));
+    let mut value = HeaderValue::from_str(&value)?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+
+#[cfg(test)]
+mod tests{
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use super::*;
+
+    #[test]
+    fn secret_string_should_be_redacted() {
+        let sdk = crate::OpenaiSdk::new("sk-secret".into());
+        assert!(!format!("{sdk:?}").contains("sk-secret"));
+        let secret = SecretString::from("sk-secret");
+        assert_eq!(format!("{secret:?} {secret}"), "[REDACTED] [REDACTED]");
+        assert_eq!(secret.expose_secret(), "sk-secret");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refreshing_credentials_should_refresh_before_expiry() -> Result<()> {
+        let fetched = Arc::new(AtomicU32::new(0));
+        let counter = fetched.clone();
+        let provider = RefreshingCredentials::new(move || {
+            let n = counter.fetch_add(1, Ordering::Relaxed);
+            async move { Ok(ExpiringToken { token: format!("token-{n}").into(), expires_in: Duration::from_secs(300) }) }
+        });
+        assert_eq!(provider.token().await?.expose_secret(), "token-0");
+        tokio::time::advance(Duration::from_secs(200)).await;
+        assert_eq!(provider.token().await?.expose_secret(), "token-0");
+        tokio::time::advance(Duration::from_secs(50)).await;
+        assert_eq!(provider.token().await?.expose_secret(), "token-1");
+        assert_eq!(fetched.load(Ordering::Relaxed), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn command_credentials_should_use_stdout_and_cache() -> Result<()> {
+        let provider = CommandCredentials::new("echo").arg("  sk-from-command ");
+        assert_eq!(provider.token().await?.expose_secret(), "sk-from-command");
+        assert!(CommandCredentials::new("false").token().await.is_err());
+
+        // 缓存期间不再执行命令
+        let path = std::env::temp_dir().join(format!("openai-sdk-command-{}", std::process::id()));
+        std::fs::write(&path, "sk-first")?;
+        let provider = CommandCredentials::new("cat").arg(path.to_string_lossy());
+        assert_eq!(provider.token().await?.expose_secret(), "sk-first");
+        std::fs::write(&path, "sk-second")?;
+        assert_eq!(provider.token().await?.expose_secret(), "sk-first");
+        let provider = CommandCredentials::new("cat").arg(path.to_string_lossy()).ttl(Duration::ZERO);
+        assert_eq!(provider.token().await?.expose_secret(), "sk-second");
+        std::fs::write(&path, "sk-third")?;
+        assert_eq!(provider.token().await?.expose_secret(), "sk-third");
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
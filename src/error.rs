@@ -0,0 +1,229 @@
+//!
+//! SDK 的错误类型
+//! 所有接口都返回 `anyhow::Result`，需要区分错误类型时可以使用 `err.downcast_ref::<ApiError>()`。
+//!
+
+use std::{fmt, time::Duration};
+
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::api::VectorStoreFileBatch;
+
+///
+/// 接口返回的错误响应(非 2xx 状态码)
+///
+#[derive(Debug,Clone)]
+pub struct ApiError{
+    /// HTTP 状态码
+    pub status: StatusCode,
+    /// 错误信息
+    pub message: String,
+    /// 错误类型，例如 `invalid_request_error`
+    pub r#type: Option<String>,
+    /// 导致错误的参数名称
+    pub param: Option<String>,
+    /// 错误码，例如 `rate_limit_exceeded`
+    pub code: Option<String>,
+    /// 服务端的请求ID(`x-request-id` 响应头)
+    pub request_id: Option<String>,
+}
+
+/// 错误响应体: `{"error": {...}}`
+#[derive(Deserialize)]
+struct ErrorBody{
+    error: ErrorDetail,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail{
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    r#type: Option<String>,
+    #[serde(default)]
+    param: Option<String>,
+    #[serde(default, deserialize_with = "string_or_number")]
+    code: Option<String>,
+}
+
+/// 错误码可能是字符串，也可能是数字
+fn string_or_number<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(match value {
+        Some(serde_json::Value::String(s)) => Some(s),
+        Some(serde_json::Value::Null) | None => None,
+        Some(other) => Some(other.to_string()),
+    })
+}
+
+impl ApiError {
+
+    /// 根据状态码和响应体构建错误，响应体不是标准错误格式时，使用原始文本作为错误信息
+    pub fn from_response(status: StatusCode, body: &[u8]) -> Self {
+        match serde_json::from_slice::<ErrorBody>(body) {
+            Ok(ErrorBody { error }) => Self {
+                status,
+                message: error.message.unwrap_or_default(),
+                r#type: error.r#type,
+                param: error.param,
+                code: error.code,
+                request_id: None,
+            },
+            Err(_) => Self {
+                status,
+                message: String::from_utf8_lossy(body).into_owned(),
+                r#type: None,
+                param: None,
+                code: None,
+                request_id: None,
+            },
+        }
+    }
+
+    /// 是否为限流错误(429)
+    pub fn is_rate_limited(&self) -> bool {
+        self.status == StatusCode::TOO_MANY_REQUESTS
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "openai api error ({})", self.status)?;
+        if let Some(code) = &self.code {
+            write!(f, " [{code}]")?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(request_id) = &self.request_id {
+            write!(f, " (request id: {request_id})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+
+///
+/// 请求会超出成本预算时，在发送之前返回该错误(见 `CostTracker`)
+///
+#[derive(Debug,Clone,PartialEq)]
+pub struct BudgetExceeded{
+    /// 超出的预算
+    pub scope: BudgetScope,
+    /// 预算上限(美元)
+    pub limit: f64,
+    /// 已经花费的金额(美元)
+    pub spent: f64,
+    /// 本次请求的预估花费(美元)
+    pub estimated: f64,
+}
+
+/// 预算的范围
+#[derive(Debug,Clone,PartialEq, Eq)]
+pub enum BudgetScope{
+    /// 当天(UTC)所有请求的总预算
+    Daily,
+    /// 单个租户(标签)的预算
+    Tenant(String),
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.scope {
+            BudgetScope::Daily => write!(f, "daily budget exceeded")?,
+            BudgetScope::Tenant(label) => write!(f, "budget for tenant '{label}' exceeded")?,
+        }
+        write!(f, ": spent ${:.4} of ${:.4}, request needs about ${:.4}", self.spent, self.limit, self.estimated)
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+
+///
+/// 轮询异步任务(运行、文件批次等)超过最长等待时间仍未结束(见 `PollOptions`)
+///
+#[derive(Debug,Clone,PartialEq, Eq)]
+pub struct PollTimeout{
+    /// 对象ID，例如 `run_abc123`
+    pub id: String,
+    /// 最后一次获取到的状态，例如 `in_progress`
+    pub status: String,
+    /// 已经等待的时间
+    pub waited: Duration,
+}
+
+impl fmt::Display for PollTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "gave up polling {} after {:?}, last status: {}", self.id, self.waited, self.status)
+    }
+}
+
+impl std::error::Error for PollTimeout {}
+
+
+///
+/// 向量库文件批次处理结束，但是没有全部成功: 批次失败、被取消或者存在处理失败的文件
+///
+#[derive(Debug,Clone)]
+pub struct FileBatchFailed{
+    /// 所有批次的最终状态，包括成功的批次
+    pub batches: Vec<VectorStoreFileBatch>,
+}
+
+impl fmt::Display for FileBatchFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self.batches.iter().filter(|b| !b.is_success()).count();
+        let (files, cancelled) = self.batches.iter()
+            .fold((0, 0), |(files, cancelled), b| (files + b.file_counts.failed, cancelled + b.file_counts.cancelled));
+        write!(f, "{failed} of {} vector store file batches did not complete: {files} files failed, {cancelled} cancelled", self.batches.len())
+    }
+}
+
+impl std::error::Error for FileBatchFailed {}
+
+
+///
+/// 接口处于熔断状态时，在发送之前返回该错误(见 `CircuitBreaker`)
+///
+#[derive(Debug,Clone,PartialEq, Eq)]
+pub struct CircuitOpen{
+    /// 熔断的接口，例如 `chat/completions`
+    pub endpoint: String,
+    /// 距离下一次探测的时间，半开状态下探测请求尚未结束时为 0
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "circuit open for endpoint {}, retry after {:?}", self.endpoint, self.retry_after)
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn api_error_should_parse_error_body(){
+        let body = br#"{"error":{"message":"Rate limit reached","type":"requests","param":null,"code":"rate_limit_exceeded"}}"#;
+        let err = ApiError::from_response(StatusCode::TOO_MANY_REQUESTS, body);
+        assert!(err.is_rate_limited());
+        assert_eq!(err.code.as_deref(), Some("rate_limit_exceeded"));
+        assert_eq!(err.to_string(), "openai api error (429 Too Many Requests) [rate_limit_exceeded]: Rate limit reached");
+
+        // Azure 的错误码为数字
+        let err = ApiError::from_response(StatusCode::UNAUTHORIZED, br#"{"error":{"code":401,"message":"Access denied"}}"#);
+        assert_eq!(err.code.as_deref(), Some("401"));
+
+        let err = ApiError::from_response(StatusCode::BAD_GATEWAY, b"upstream unavailable");
+        assert_eq!(err.message, "upstream unavailable");
+    }
+}
@@ -0,0 +1,353 @@
+//!
+//! 多个 API key 的轮换与故障转移
+//! 通过 `OpenaiSdk::with_key_pool` 开启后，每个请求从池中选择一个可用的 key(轮询或者最少进行中请求)，
+//! 收到 429(限流或额度不足)时暂时移出该 key，收到 401 时永久禁用该 key，并使用下一个可用的 key 重试;
+//! 每个 key 分别统计请求数、失败数与令牌使用量。只对使用 `Authorization: Bearer` 认证的接口生效(Azure 除外)。
+//!
+
+use std::{fmt, sync::{Mutex, atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering}}, time::Duration};
+
+use anyhow::{Result, bail};
+use futures::future::BoxFuture;
+use reqwest::{Request, Response, StatusCode, header::AUTHORIZATION};
+use tokio::time::Instant;
+
+use crate::{credential::{self, SecretString}, error::ApiError, rate_limit::retry_after, transport::Transport};
+
+/// 额度不足(insufficient_quota)时默认的移出时间
+const DEFAULT_QUOTA_COOLDOWN: Duration = Duration::from_secs(600);
+
+
+/// 选择 key 的策略
+#[derive(Debug,Clone,Copy,Default,PartialEq, Eq)]
+pub enum SelectionStrategy{
+    /// 依次轮流使用
+    #[default]
+    RoundRobin,
+    /// 使用进行中请求最少的 key
+    LeastLoaded,
+}
+
+/// key 的当前状态
+#[derive(Debug,Clone,Copy,PartialEq, Eq)]
+pub enum KeyStatus{
+    /// 可用
+    Active,
+    /// 暂时移出，在指定的时间后恢复
+    Ejected{ remaining: Duration },
+    /// 认证失败，永久禁用
+    Disabled,
+}
+
+/// 单个 key 的使用统计
+#[derive(Debug,Clone,PartialEq, Eq)]
+pub struct KeyMetrics{
+    /// key 在池中的索引
+    pub index: usize,
+    /// key 的末尾 4 个字符，用于在日志中区分 key
+    pub hint: String,
+    /// 当前状态
+    pub status: KeyStatus,
+    /// 进行中的请求数
+    pub in_flight: u32,
+    /// 发送的请求总数
+    pub requests: u64,
+    /// 收到 429 的次数
+    pub rate_limited: u64,
+    /// 其他失败(网络错误、5xx 等)的次数
+    pub failures: u64,
+    /// 响应中统计的令牌总数
+    pub total_tokens: u64,
+}
+
+
+///
+/// API key 池
+///
+pub struct KeyPool{
+    keys: Vec<PooledKey>,
+    strategy: SelectionStrategy,
+    /// 额度不足时的移出时间
+    quota_cooldown: Duration,
+    /// 轮询的位置
+    cursor: AtomicUsize,
+}
+
+struct PooledKey{
+    token: SecretString,
+    state: Mutex<KeyState>,
+    in_flight: AtomicU32,
+    requests: AtomicU64,
+    rate_limited: AtomicU64,
+    failures: AtomicU64,
+    total_tokens: AtomicU64,
+}
+
+#[derive(Default)]
+struct KeyState{
+    ejected_until: Option<Instant>,
+    disabled: bool,
+}
+
+/// 发送请求使用的 key，附加在响应的扩展中，读取响应体后统计令牌数
+#[derive(Debug,Clone,Copy)]
+pub(crate) struct KeyUsage{
+    pub(crate) index: usize,
+    /// 换用其他 key 重试的次数
+    pub(crate) failovers: u32,
+}
+
+// 不输出 key 的内容
+impl fmt::Debug for KeyPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyPool")
+            .field("keys", &self.keys.len())
+            .field("strategy", &self.strategy)
+            .field("quota_cooldown", &self.quota_cooldown)
+            .finish()
+    }
+}
+
+impl KeyPool {
+
+    ///
+    /// 使用多个 API key 创建 key 池，默认轮询使用
+    ///
+    pub fn new<I, S>(tokens: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<SecretString>,
+    {
+        let keys = tokens.into_iter().map(|token| PooledKey {
+            token: token.into(),
+            state: Mutex::new(KeyState::default()),
+            in_flight: AtomicU32::new(0),
+            requests: AtomicU64::new(0),
+            rate_limited: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            total_tokens: AtomicU64::new(0),
+        }).collect();
+        Self { keys, strategy: SelectionStrategy::default(), quota_cooldown: DEFAULT_QUOTA_COOLDOWN, cursor: AtomicUsize::new(0) }
+    }
+
+    /// 设置选择 key 的策略
+    pub fn strategy(mut self, strategy: SelectionStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// 设置额度不足(insufficient_quota)时移出 key 的时间，默认为 10 分钟
+    pub fn quota_cooldown(mut self, cooldown: Duration) -> Self {
+        self.quota_cooldown = cooldown;
+        self
+    }
+
+    /// 各个 key 的使用统计
+    pub fn metrics(&self) -> Vec<KeyMetrics> {
+        let now = Instant::now();
+        self.keys.iter().enumerate().map(|(index, key)| {
+            let state = key.state.lock().unwrap();
+            let status = match state.ejected_until {
+                _ if state.disabled => KeyStatus::Disabled,
+                Some(until) if until > now => KeyStatus::Ejected { remaining: until - now },
+                _ => KeyStatus::Active,
+            };
+            let hint = key.token.expose_secret().chars().rev().take(4).collect::<Vec<_>>().into_iter().rev().collect();
+            KeyMetrics {
+                index,
+                hint,
+                status,
+                in_flight: key.in_flight.load(Ordering::Relaxed),
+                requests: key.requests.load(Ordering::Relaxed),
+                rate_limited: key.rate_limited.load(Ordering::Relaxed),
+                failures: key.failures.load(Ordering::Relaxed),
+                total_tokens: key.total_tokens.load(Ordering::Relaxed),
+            }
+        }).collect()
+    }
+
+    /// 选择一个可用的 key
+    fn select(&self) -> Option<usize> {
+        let now = Instant::now();
+        let available = |index: &usize| {
+            let state = self.keys[*index].state.lock().unwrap();
+            !state.disabled && state.ejected_until.is_none_or(|until| until <= now)
+        };
+        let len = self.keys.len();
+        match self.strategy {
+            SelectionStrategy::RoundRobin => {
+                let start = self.cursor.fetch_add(1, Ordering::Relaxed);
+                (0..len).map(|offset| (start + offset) % len).find(available)
+            }
+            SelectionStrategy::LeastLoaded => (0..len)
+                .filter(available)
+                .min_by_key(|index| self.keys[*index].in_flight.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// 记录响应中的令牌使用量
+    pub(crate) fn record_tokens(&self, usage: KeyUsage, tokens: u64) {
+        self.keys[usage.index].total_tokens.fetch_add(tokens, Ordering::Relaxed);
+    }
+
+    ///
+    /// 使用池中的 key 发送请求，key 被限流或认证失败时换用下一个可用的 key 重试;
+    /// 无法克隆的请求(例如 multipart 上传)不会重试
+    ///
+    async fn execute(&self, inner: &dyn Transport, req: Request) -> Result<Response> {
+        let mut req = Some(req);
+        let mut last = None;
+        for failovers in 0..self.keys.len() as u32 {
+            let Some(index) = self.select() else { break };
+            let key = &self.keys[index];
+            let mut current = req.take().expect("request is available for each attempt");
+            current.headers_mut().insert(AUTHORIZATION, credential::bearer(&key.token)?);
+            let retry = current.try_clone();
+
+            key.requests.fetch_add(1, Ordering::Relaxed);
+            let in_flight = InFlight::new(&key.in_flight);
+            let res = inner.execute(current).await;
+            drop(in_flight);
+            let mut res = res.inspect_err(|_| { key.failures.fetch_add(1, Ordering::Relaxed); })?;
+
+            match res.status() {
+                StatusCode::TOO_MANY_REQUESTS => {
+                    key.rate_limited.fetch_add(1, Ordering::Relaxed);
+                    // 读取响应体判断是否为额度不足，之后重新构建响应
+                    let (rebuilt, quota) = inspect_quota(res).await?;
+                    res = rebuilt;
+                    let cooldown = if quota { self.quota_cooldown } else { retry_after(res.headers()) };
+                    key.state.lock().unwrap().ejected_until = Some(Instant::now() + cooldown);
+                }
+                StatusCode::UNAUTHORIZED => key.state.lock().unwrap().disabled = true,
+                status if status.is_server_error() => { key.failures.fetch_add(1, Ordering::Relaxed); }
+                _ => {}
+            }
+            res.extensions_mut().insert(KeyUsage { index, failovers });
+            match retry {
+                Some(retry) if matches!(res.status(), StatusCode::TOO_MANY_REQUESTS | StatusCode::UNAUTHORIZED) => {
+                    req = Some(retry);
+                    last = Some(res);
+                }
+                _ => return Ok(res),
+            }
+        }
+        // 没有可用的 key 时返回最后一次的失败响应
+        match last {
+            Some(res) => Ok(res),
+            None => bail!("no API key available in the key pool ({} keys)", self.keys.len()),
+        }
+    }
+}
+
+/// 进行中请求的计数，请求结束或者被取消(future 被丢弃)时减一
+struct InFlight<'a>(&'a AtomicU32);
+
+impl<'a> InFlight<'a> {
+    fn new(counter: &'a AtomicU32) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// 读取 429 响应的响应体，返回重新构建的响应以及是否为额度不足
+async fn inspect_quota(res: Response) -> Result<(Response, bool)> {
+    let status = res.status();
+    let headers = res.headers().clone();
+    let body = res.bytes().await?;
+    let quota = ApiError::from_response(status, &body).code.as_deref() == Some("insufficient_quota");
+    let mut builder = http::Response::builder().status(status);
+    if let Some(map) = builder.headers_mut() {
+        *map = headers;
+    }
+    Ok((Response::from(builder.body(body)?), quota))
+}
+
+
+/// 使用 key 池认证的传输层，包装实际的传输层
+#[derive(Debug)]
+pub(crate) struct PooledTransport<'a>{
+    pub(crate) pool: &'a KeyPool,
+    pub(crate) inner: &'a dyn Transport,
+}
+
+impl Transport for PooledTransport<'_> {
+    fn execute(&self, req: Request) -> BoxFuture<'_, Result<Response>> {
+        Box::pin(self.pool.execute(self.inner, req))
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use reqwest::Method;
+    use serde_json::json;
+    use crate::OpenaiSdk;
+    use crate::api::*;
+    use crate::transport::{MockTransport, MockResponse};
+    use super::*;
+
+    fn authorization(mock: &MockTransport) -> Vec<String> {
+        mock.requests().iter().map(|r| r.headers[AUTHORIZATION].to_str().unwrap().to_string()).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn key_pool_should_rotate_and_fail_over() -> Result<()>{
+        let mock = MockTransport::new()
+            .once(Method::POST, "images/generations", MockResponse::error(StatusCode::UNAUTHORIZED, "invalid key"))
+            .once(Method::POST, "images/generations", MockResponse::json(json!({
+                "error": {"message": "quota exceeded", "code": "insufficient_quota"}
+            })).status(StatusCode::TOO_MANY_REQUESTS))
+            .on(Method::POST, "images/generations", MockResponse::json(json!({"created": 1, "data": []})));
+        let pool = KeyPool::new(["sk-aaaa", "sk-bbbb", "sk-cccc"]).quota_cooldown(Duration::from_secs(60));
+        let sdk = OpenaiSdk::new(String::new()).with_transport(mock.clone()).with_key_pool(pool);
+
+        // 第一个 key 401 被禁用，第二个 key 额度不足被移出，第三个 key 成功
+        sdk.create_image(CreateImageRequest::new("a cat")).await?;
+        assert_eq!(authorization(&mock), ["Bearer sk-aaaa", "Bearer sk-bbbb", "Bearer sk-cccc"]);
+        let metrics = sdk.key_pool().unwrap().metrics();
+        assert_eq!(metrics[0].status, KeyStatus::Disabled);
+        assert_eq!(metrics[1].status, KeyStatus::Ejected { remaining: Duration::from_secs(60) });
+        assert_eq!((metrics[2].status, metrics[2].requests, metrics[2].hint.as_str()), (KeyStatus::Active, 1, "cccc"));
+
+        sdk.create_image(CreateImageRequest::new("a cat")).await?;
+        tokio::time::advance(Duration::from_secs(61)).await;
+        sdk.create_image(CreateImageRequest::new("a cat")).await?;
+        sdk.create_image(CreateImageRequest::new("a cat")).await?;
+        assert_eq!(authorization(&mock)[3..], ["Bearer sk-cccc", "Bearer sk-bbbb", "Bearer sk-cccc"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn key_pool_should_track_usage_and_prefer_least_loaded() -> Result<()>{
+        let mock = MockTransport::new().on(Method::POST, "embeddings", MockResponse::json(json!({
+            "object": "list", "data": [], "model": "text-embedding-3-small", "usage": {"prompt_tokens": 5, "total_tokens": 5}
+        })));
+        let pool = KeyPool::new(["sk-1", "sk-2"]).strategy(SelectionStrategy::LeastLoaded);
+        let sdk = OpenaiSdk::new(String::new()).with_transport(mock).with_key_pool(pool);
+        sdk.create_embedding(EmbeddingRequest::new("hello")).await?;
+        sdk.create_embedding(EmbeddingRequest::new("hello")).await?;
+        let metrics = sdk.key_pool().unwrap().metrics();
+        assert_eq!((metrics[0].requests, metrics[0].total_tokens, metrics[0].in_flight), (2, 10, 0));
+        assert_eq!(metrics[1].requests, 0);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn key_pool_should_release_in_flight_on_cancel() -> Result<()>{
+        let mock = MockTransport::new().on(Method::POST, "embeddings", MockResponse::json(json!({
+            "object": "list", "data": [], "model": "text-embedding-3-small", "usage": {"prompt_tokens": 5, "total_tokens": 5}
+        })).delay(Duration::from_secs(60)));
+        let sdk = OpenaiSdk::new(String::new()).with_transport(mock).with_key_pool(KeyPool::new(["sk-1"]));
+        let res = tokio::time::timeout(Duration::from_secs(1), sdk.create_embedding(EmbeddingRequest::new("hello"))).await;
+        assert!(res.is_err());
+        assert_eq!(sdk.key_pool().unwrap().metrics()[0].in_flight, 0);
+        Ok(())
+    }
+}
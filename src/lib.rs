@@ -1,75 +1,646 @@
-//! 
+//!
 //! 使用 Rust语言封装的 OpenAI-SDK 工具包
-//! 
+//!
 
-use std::time::Duration;
+use std::{future::Future, sync::{Arc, Mutex}, time::{Duration, Instant}};
 use anyhow::{Result, Ok};
-use reqwest::{Client, RequestBuilder};
+use reqwest::{Client, RequestBuilder, Request, Response, Url};
+use serde::{Serialize, de::DeserializeOwned};
 
 // 使用api模块，并且对外暴露
 pub mod api;
+pub mod azure;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod cache;
+pub mod cassette;
+pub mod circuit_breaker;
+pub mod cost;
+pub mod credential;
+pub mod error;
+pub mod key_pool;
+pub mod meta;
+pub mod middleware;
+pub mod rate_limit;
+pub mod router;
+pub mod semantic_cache;
+pub mod stream;
+mod telemetry;
+pub mod transport;
+#[cfg(feature = "realtime")]
+pub mod realtime;
+#[cfg(feature = "mock-server")]
+pub mod mock_server;
 use api::*;
+use azure::AzureConfig;
+use cache::{ResponseCache, cache_key};
+use circuit_breaker::CircuitBreaker;
+use cost::{Charge, CostTracker, StreamCharge};
+use credential::{CredentialProvider, StaticCredentials};
+use error::ApiError;
+use key_pool::{KeyPool, KeyUsage, PooledTransport};
+use meta::{ResponseMeta, WithMeta};
+use middleware::Middleware;
+use rate_limit::{RateLimiter, Reservation};
+use semantic_cache::SemanticCache;
+use stream::EventStream;
+use telemetry::CallSpan;
+use transport::{Transport, ReqwestTransport};
+
+/// OpenAI 官方接口的基础地址，各请求类型均基于该地址构建
+pub const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// 建立连接的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 
 ///
 /// 核心 SDK 结构体
-/// 
+///
 #[derive(Debug, Clone)]
 pub struct OpenaiSdk{
-    /// 要使用的openai账号的 api key
-    pub(crate) token: String,
-    /// 网络请求客户端
-    pub(crate) client: Client
+    /// 提供要使用的openai账号的 api key，未设置时不发送认证请求头
+    pub(crate) credentials: Option<Arc<dyn CredentialProvider>>,
+    /// 网络请求客户端，用于构建请求
+    pub(crate) client: Client,
+    /// 发送请求的传输层，默认使用 reqwest 客户端
+    pub(crate) transport: Arc<dyn Transport>,
+    /// 接口的基础地址，默认为 OpenAI 官方地址，也可以指向兼容 OpenAI 接口的服务
+    pub(crate) base_url: String,
+    /// Azure OpenAI 配置，设置后所有请求都发送到对应的 Azure 部署
+    pub(crate) azure: Option<AzureConfig>,
+    /// API key 池，设置后轮换使用池中的 key，替代 `token`
+    pub(crate) key_pool: Option<Arc<KeyPool>>,
+    /// 熔断器，未设置时不熔断
+    pub(crate) circuit_breaker: Option<Arc<CircuitBreaker>>,
+    /// 请求/响应中间件，按注册顺序处理请求
+    pub(crate) middleware: Vec<Arc<dyn Middleware>>,
+    /// 客户端限流器，未设置时不限流
+    pub(crate) rate_limiter: Option<Arc<RateLimiter>>,
+    /// 响应缓存，未设置时不缓存
+    pub(crate) cache: Option<Arc<ResponseCache>>,
+    /// 语义缓存，未设置时不使用
+    pub(crate) semantic_cache: Option<Arc<SemanticCache>>,
+    /// 成本统计器，未设置时不统计花费
+    pub(crate) cost_tracker: Option<Arc<CostTracker>>,
+    /// 成本统计使用的标签，未设置时使用请求体中的 `user` 字段
+    pub(crate) cost_label: Option<String>,
+    /// 是否在 tracing span 中记录请求体与响应体
+    #[cfg(feature = "tracing")]
+    pub(crate) trace_bodies: bool,
+    /// `with_meta` 期间记录最后一次请求的响应元数据
+    pub(crate) meta_sink: Option<Arc<Mutex<Option<ResponseMeta>>>>,
 }
 
 
 
 // SDK 实现块
 impl OpenaiSdk {
-    
+
     ///
     /// 传入openai的apikey，并且初始化网络请求客户端
-    /// 
+    ///
     pub fn new(token: String) -> Self{
-        Self { token: token, client: Client::new() }
+        let client = Client::builder().connect_timeout(CONNECT_TIMEOUT).build().expect("failed to build http client");
+        let transport = Arc::new(ReqwestTransport::new(client.clone()));
+        let credentials = (!token.is_empty()).then(|| Arc::new(StaticCredentials(token.into())) as Arc<dyn CredentialProvider>);
+        Self {
+            credentials, client, transport, base_url: OPENAI_BASE_URL.into(), azure: None, key_pool: None, middleware: Vec::new(), circuit_breaker: None, rate_limiter: None,
+            cache: None, semantic_cache: None, cost_tracker: None, cost_label: None,
+            #[cfg(feature = "tracing")]
+            trace_bodies: false,
+            meta_sink: None,
+        }
+    }
+
+    ///
+    /// 使用 Azure OpenAI 资源初始化，认证信息由配置提供
+    ///
+    pub fn azure(config: AzureConfig) -> Self{
+        Self { credentials: Some(config.auth.provider()), azure: Some(config), ..Self::new(String::new()) }
+    }
+
+    ///
+    /// 设置接口的基础地址，例如 `http://localhost:8080/v1`
+    ///
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self{
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    ///
+    /// 设置凭证提供者，每次构建请求时获取 api key，例如从环境变量、文件或者命令输出中读取;
+    /// Azure 模式下替换配置中的认证信息，仍按照 `AzureAuth` 的方式发送
+    ///
+    pub fn with_credentials(mut self, provider: impl CredentialProvider + 'static) -> Self{
+        self.credentials = Some(Arc::new(provider));
+        self
+    }
+
+    ///
+    /// 设置发送请求的传输层，例如测试中使用 `MockTransport`
+    ///
+    pub fn with_transport(mut self, transport: impl Transport + 'static) -> Self{
+        self.transport = Arc::new(transport);
+        self
+    }
+
+    ///
+    /// 使用多个 API key 轮换发送请求，key 被限流或认证失败时自动切换到下一个 key(Azure 配置不使用 key 池)
+    ///
+    pub fn with_key_pool(mut self, pool: KeyPool) -> Self{
+        self.key_pool = Some(Arc::new(pool));
+        self
+    }
+
+    ///
+    /// 获取 API key 池，用于查询各个 key 的状态与使用量
+    ///
+    pub fn key_pool(&self) -> Option<&KeyPool>{
+        self.key_pool.as_deref()
+    }
+
+    ///
+    /// 注册中间件，可以修改类型化的请求、添加请求头、查看响应或者直接返回响应，多个中间件按注册顺序处理请求
+    ///
+    pub fn with_middleware(mut self, middleware: impl Middleware + 'static) -> Self{
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    ///
+    /// 开启熔断，接口持续失败时直接返回 `CircuitOpen` 错误，不再等待超时
+    ///
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self{
+        self.circuit_breaker = Some(Arc::new(breaker));
+        self
+    }
+
+    ///
+    /// 获取熔断器，用于查询各个接口的状态
+    ///
+    pub fn circuit_breaker(&self) -> Option<&CircuitBreaker>{
+        self.circuit_breaker.as_deref()
+    }
+
+    ///
+    /// 开启客户端限流，按模型跟踪每分钟请求数与令牌数，额度不足时排队等待
+    ///
+    pub fn with_rate_limiter(mut self, limiter: RateLimiter) -> Self{
+        self.rate_limiter = Some(Arc::new(limiter));
+        self
+    }
+
+    ///
+    /// 开启响应缓存，相同的 `chat_completion`/`create_image` 请求直接返回缓存的响应
+    ///
+    pub fn with_cache(mut self, cache: ResponseCache) -> Self{
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    ///
+    /// 开启语义缓存，`chat_completion` 的问题与已缓存的问题足够相似时直接返回缓存的响应
+    ///
+    pub fn with_semantic_cache(mut self, cache: SemanticCache) -> Self{
+        self.semantic_cache = Some(Arc::new(cache));
+        self
+    }
+
+    ///
+    /// 开启成本统计，按照价格表累计花费，并在请求会超出预算时返回 `BudgetExceeded` 错误
+    ///
+    pub fn with_cost_tracker(mut self, tracker: CostTracker) -> Self{
+        self.cost_tracker = Some(Arc::new(tracker));
+        self
+    }
+
+    ///
+    /// 设置成本统计使用的标签(例如租户ID)，通常对克隆的 SDK 设置，与原 SDK 共享同一个统计器
+    ///
+    pub fn with_cost_label(mut self, label: impl Into<String>) -> Self{
+        self.cost_label = Some(label.into());
+        self
+    }
+
+    ///
+    /// 获取成本统计器，用于查询花费
+    ///
+    pub fn cost_tracker(&self) -> Option<&CostTracker>{
+        self.cost_tracker.as_deref()
+    }
+
+    ///
+    /// 在 tracing span 中记录请求体与响应体(提示词与生成内容)，默认不记录
+    ///
+    #[cfg(feature = "tracing")]
+    pub fn with_trace_bodies(mut self, enabled: bool) -> Self{
+        self.trace_bodies = enabled;
+        self
     }
 
     ///
     /// 文字聊天类型 api请求发送
-    /// 
+    ///
     pub async fn chat_completion(&self, req: ChatCompletionRequest) -> Result<ChatCompletionResponse>{
-        // 构建请求
-        let req = self.prepare_request(req);
-        // 发送请求
-        let res = req.send().await?;
-        Ok(res.json::<ChatCompletionResponse>().await?)
+        let bypass = req.bypass_cache();
+        if let Some(cache) = self.semantic_cache.as_ref().filter(|_| !bypass) {
+            return cache.complete(self, req).await;
+        }
+        self.send_json_cached(req, bypass, None).await
+    }
+
+    ///
+    /// 文字聊天类型 api请求发送，以流式方式逐块返回消息内容
+    ///
+    pub async fn chat_completion_stream(&self, req: ChatCompletionRequest) -> Result<EventStream<ChatCompletionChunk>>{
+        let res = self.send(req.streaming()).await?;
+        self.event_stream(res).await
     }
 
     ///
     /// 生成图片 api 请求发送
-    /// 
+    ///
     pub async fn create_image(&self,req: CreateImageRequest) -> Result<CreateImageResponse>{
-        let req = self.prepare_request(req);
-        let res = req.send().await?;
-        Ok(res.json::<CreateImageResponse>().await?)
+        let bypass = req.bypass_cache;
+        // 图片地址在一段时间后失效，缓存的有效期不能超过图片地址的有效期
+        let max_ttl = (req.response_format != Some(ImageResponseFormat::B64Json)).then_some(cache::IMAGE_URL_TTL);
+        self.send_json_cached(req, bypass, max_ttl).await
     }
 
-    /// 将IntoRequest实现的结构体，统一转为为 `reqwest::RequestBuilder`,并且设置通用参数: token、timeout等
-    fn prepare_request(&self,req: impl IntoRequest) -> RequestBuilder{
+    ///
+    /// 向量嵌入 api 请求发送
+    ///
+    pub async fn create_embedding(&self, req: EmbeddingRequest) -> Result<EmbeddingResponse>{
+        self.send_json(req).await
+    }
+
+    ///
+    /// 文本补全(instruct模型) api 请求发送
+    ///
+    pub async fn completion(&self, req: CompletionRequest) -> Result<CompletionResponse>{
+        self.send_json(req).await
+    }
+
+    ///
+    /// 文本补全(instruct模型) api 请求发送，以流式方式逐块返回补全结果
+    ///
+    pub async fn completion_stream(&self, req: CompletionRequest) -> Result<EventStream<CompletionResponse>>{
+        let res = self.send(req.streaming()?).await?;
+        self.event_stream(res).await
+    }
+
+    ///
+    /// 发送请求，返回解析后的响应体以及响应头、耗时、重试次数等元数据
+    ///
+    pub async fn send_with_meta<R: TypedRequest + 'static>(&self, req: R) -> Result<WithMeta<R::Response>>{
+        self.send_json_with_meta(req).await
+    }
+
+    ///
+    /// 调用任意接口方法，返回其结果以及最后一次请求的响应元数据，适用于没有 `TypedRequest` 的接口(例如助手、线程、向量库、文件):
+    /// `sdk.with_meta(|sdk| async move { sdk.retrieve_run(thread_id, run_id).await }).await?`;
+    /// 方法内发送多个请求(例如轮询)时返回最后一次请求的元数据，没有发送请求(例如命中缓存)时返回错误
+    ///
+    pub async fn with_meta<T, F, Fut>(&self, call: F) -> Result<WithMeta<T>>
+    where
+        F: FnOnce(OpenaiSdk) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let sink = Arc::new(Mutex::new(None));
+        let data = call(Self { meta_sink: Some(sink.clone()), ..self.clone() }).await?;
+        let meta = sink.lock().unwrap().take().ok_or_else(|| anyhow::anyhow!("no request was sent, response meta is unavailable"))?;
+        Ok(WithMeta { data, meta })
+    }
+
+    /// 发送请求，并且将响应体反序列化为指定类型
+    pub(crate) async fn send_json<T: DeserializeOwned + 'static>(&self, req: impl IntoRequest + 'static) -> Result<T>{
+        Ok(self.send_json_with_meta(req).await?.data)
+    }
+
+    /// 先经过请求中间件，再查询响应缓存，未命中时发送请求，并且将响应体写入缓存，`max_ttl` 限制该响应的有效期
+    async fn send_json_cached<R: TypedRequest + Serialize + Clone + 'static>(&self, mut req: R, bypass: bool, max_ttl: Option<Duration>) -> Result<R::Response>{
+        if let Some(res) = self.on_request(&mut req)? {
+            return self.read_json(res).await;
+        }
+        let (data, _) = self.send_raw_cached(req, bypass, max_ttl).await?;
+        self.on_data(data)
+    }
+
+    ///
+    /// 发送已经经过请求中间件的请求，开启响应缓存时先查询缓存，返回反序列化后的响应体以及原始响应体;
+    /// 不会调用中间件的 `on_data`，由调用方对每个返回的值调用一次
+    ///
+    pub(crate) async fn send_raw_cached<R: TypedRequest + Serialize + Clone + 'static>(&self, req: R, bypass: bool, max_ttl: Option<Duration>) -> Result<(R::Response, serde_json::Value)>{
+        let Some(cache) = self.cache.as_ref().filter(|_| !bypass) else {
+            let (body, _) = self.read_body(self.send_screened(req).await?).await?;
+            let body: serde_json::Value = serde_json::from_slice(&body)?;
+            return Ok((serde_json::from_value(body.clone())?, body));
+        };
+        // 缓存键包含实际请求的地址，不同的服务或者 Azure 部署不会共用缓存
+        let endpoint = self.resolve_url(&req.clone().into_request(self.client.clone()).build()?, req.model_id())?;
+        let key = cache_key(endpoint.as_str(), &req)?;
+        // 缓存读写失败不影响请求: 无法解析的缓存视为未命中，写入失败时仍然返回响应
+        if let Some(hit) = cache.get(&key).await.and_then(|body| Some((serde_json::from_value(body.clone()).ok()?, body))) {
+            return Ok(hit);
+        }
+        let (body, _) = self.read_body(self.send_screened(req).await?).await?;
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        let data = serde_json::from_value(body.clone())?;
+        cache.put(&key, body.clone(), max_ttl).await;
+        Ok((data, body))
+    }
+
+    /// 检查状态码并构建事件数据流，限流预留与计费信息随数据流一起保留，收到用量数据块时修正
+    pub(crate) async fn event_stream<T: DeserializeOwned>(&self, res: Response) -> Result<EventStream<T>>{
+        let mut res = error_for_status(res).await?;
+        let charge = res.extensions_mut().remove::<Charge>()
+            .zip(self.cost_tracker.clone())
+            .map(|(charge, tracker)| StreamCharge::new(tracker, charge));
+        Ok(EventStream::from_response(res, charge))
+    }
+
+    /// 读取中间件直接返回的响应，反序列化后交给中间件处理
+    pub(crate) async fn read_json<T: DeserializeOwned + 'static>(&self, res: Response) -> Result<T>{
+        let (body, _) = self.read_body(res).await?;
+        self.on_data(serde_json::from_slice(&body)?)
+    }
+
+    /// 发送请求，将响应体反序列化为指定类型，并且附带响应元数据
+    async fn send_json_with_meta<T: DeserializeOwned + 'static>(&self, req: impl IntoRequest + 'static) -> Result<WithMeta<T>>{
+        let (body, meta) = self.send_body(req).await?;
+        Ok(WithMeta { data: self.on_data(serde_json::from_slice::<T>(&body)?)?, meta })
+    }
+
+    /// 将反序列化后的响应体交给各个中间件处理
+    pub(crate) fn on_data<T: 'static>(&self, mut data: T) -> Result<T>{
+        for middleware in self.middleware.iter().rev() {
+            middleware.on_data(&mut data)?;
+        }
+        Ok(data)
+    }
+
+    /// 发送请求并读取响应体
+    async fn send_body(&self, req: impl IntoRequest + 'static) -> Result<(bytes::Bytes, ResponseMeta)>{
+        self.read_body(self.send(req).await?).await
+    }
+
+    /// 读取响应体，读取后统计花费、修正限流额度
+    async fn read_body(&self, res: Response) -> Result<(bytes::Bytes, ResponseMeta)>{
+        let mut res = error_for_status(res).await?;
+        let meta = res.extensions_mut().remove::<ResponseMeta>().expect("send attaches response meta");
+        let reservation = res.extensions_mut().remove::<Reservation>();
+        let key_usage = res.extensions().get::<KeyUsage>().copied();
+        let span = res.extensions_mut().remove::<CallSpan>();
+        let charge = res.extensions_mut().remove::<Charge>();
+        let body = res.bytes().await?;
+        if let (Some(tracker), Some(charge)) = (&self.cost_tracker, charge) {
+            tracker.record(&charge, &body);
+        }
+        if let Some(span) = span {
+            span.record_body(&body);
+        }
+        let usage = serde_json::from_slice::<UsageBody>(&body).ok().and_then(|b| b.usage);
+        if let (Some(pool), Some(key_usage), Some(usage)) = (&self.key_pool, key_usage, &usage) {
+            pool.record_tokens(key_usage, usage.total_tokens as u64);
+        }
+        // 开启限流时，使用响应中实际消耗的令牌数修正预留的额度，没有用量的响应丢弃预留时只保留输入部分
+        if let (Some(reservation), Some(usage)) = (reservation, &usage) {
+            reservation.settle(usage.total_tokens);
+        }
+        Ok((body, meta))
+    }
+
+    /// 构建并发送请求
+    async fn send<R: IntoRequest + 'static>(&self, mut req: R) -> Result<Response>{
+        if let Some(res) = self.on_request(&mut req)? {
+            return Ok(res);
+        }
+        self.send_screened(req).await
+    }
+
+    /// 中间件可以修改类型化的请求，或者直接返回响应而不发送请求
+    pub(crate) fn on_request<R: 'static>(&self, req: &mut R) -> Result<Option<Response>>{
+        for middleware in &self.middleware {
+            if let Some(mut res) = middleware.on_request(req)? {
+                for middleware in self.middleware.iter().rev() {
+                    middleware.on_response(&mut res)?;
+                }
+                let meta = ResponseMeta { status: res.status(), headers: res.headers().clone(), latency: Duration::ZERO, retries: 0 };
+                self.record_meta(&meta);
+                res.extensions_mut().insert(meta);
+                return Ok(Some(res));
+            }
+        }
+        Ok(None)
+    }
+
+    /// 构建并发送已经经过中间件 `on_request` 的请求
+    async fn send_screened<R: IntoRequest + 'static>(&self, req: R) -> Result<Response>{
+        let (path, mut req) = self.prepare(req).await?;
+        for middleware in &self.middleware {
+            middleware.on_http_request(&mut req)?;
+        }
+        let span = CallSpan::start(self, &req);
+        // 接口熔断时直接返回错误，不再排队等待
+        let permit = match &self.circuit_breaker {
+            Some(breaker) => Some(breaker.acquire(&circuit_breaker::endpoint(req.url())).inspect_err(|e| span.record_error(e))?),
+            None => None,
+        };
+        let charge = match &self.cost_tracker {
+            Some(tracker) => Some(tracker.admit(&req, &path, self.cost_label.as_deref()).inspect_err(|e| span.record_error(e))?),
+            None => None,
+        };
+        let started = Instant::now();
+        let res = span.instrument(async {
+            // 设置了 key 池时，由 key 池选择 key 并在失败时切换
+            let pooled;
+            let transport: &dyn Transport = match (&self.key_pool, &self.azure) {
+                (Some(pool), None) => {
+                    pooled = PooledTransport { pool, inner: self.transport.as_ref() };
+                    &pooled
+                }
+                _ => self.transport.as_ref(),
+            };
+            match &self.rate_limiter {
+                Some(limiter) => limiter.execute(transport, req).await,
+                None => transport.execute(req).await,
+            }
+        }).await;
+        if let Some(permit) = permit {
+            permit.record_status(res.as_ref().ok().map(|r| r.status()));
+        }
+        let mut res = res.inspect_err(|e| span.record_error(e))?;
+        for middleware in self.middleware.iter().rev() {
+            middleware.on_response(&mut res)?;
+        }
+        // 在响应的扩展中附加元数据，读取响应体之后仍然可以获取
+        let meta = ResponseMeta {
+            status: res.status(),
+            headers: res.headers().clone(),
+            latency: started.elapsed(),
+            retries: res.extensions().get::<Reservation>().map_or(0, |r| r.retries)
+                + res.extensions().get::<KeyUsage>().map_or(0, |u| u.failovers),
+        };
+        span.record_response(&meta);
+        self.record_meta(&meta);
+        res.extensions_mut().insert(meta);
+        res.extensions_mut().insert(span);
+        if let Some(charge) = charge {
+            res.extensions_mut().insert(charge);
+        }
+        Ok(res)
+    }
+
+    /// `with_meta` 期间记录响应元数据
+    fn record_meta(&self, meta: &ResponseMeta) {
+        if let Some(sink) = &self.meta_sink {
+            *sink.lock().unwrap() = Some(meta.clone());
+        }
+    }
+
+    /// 将IntoRequest实现的结构体，统一转为为 `reqwest::Request`,并且设置通用参数: 地址、token、timeout等
+    #[cfg(test)]
+    pub(crate) async fn prepare_request(&self,req: impl IntoRequest) -> Result<Request>{
+        Ok(self.prepare(req).await?.1)
+    }
+
+    /// 构建网络请求并设置通用参数，同时返回改写地址之前的接口路径(`/v1/` 之后的部分，例如 `images/generations`)，用于按照接口计费
+    pub(crate) async fn prepare(&self,req: impl IntoRequest) -> Result<(String, Request)>{
+        let model = req.model_id();
+        let stream = req.is_stream();
         // 使用网络请求客户端Clinet，构建出一个网络请求
         let req = req.into_request(self.client.clone());
-        // 设置令牌(api-key)
-        let req = if self.token.is_empty(){
-            req
-        }else{
-            req.bearer_auth(&self.token)
-        };
-        // 设置超时请求超时时间
-        req.timeout(Duration::from_secs(30))
+        let mut req = req.build()?;
+        // 设置超时请求超时时间，请求类型自行设置的(例如上传文件)以及流式请求除外
+        if !stream {
+            req.timeout_mut().get_or_insert(Duration::from_secs(30));
+        }
+        let path = api_path(req.url()).to_string();
+        *req.url_mut() = self.resolve_url(&req, model)?;
+        match &self.azure {
+            Some(azure) => {
+                if let Some(credentials) = &self.credentials {
+                    azure.authorize(&mut req, &credentials.token().await?)?;
+                }
+            }
+            None => {
+                // 设置令牌(api-key)
+                if let Some(credentials) = &self.credentials {
+                    let token = credentials.token().await?;
+                    if !token.is_empty() {
+                        req.headers_mut().insert(reqwest::header::AUTHORIZATION, credential::bearer(&token)?);
+                    }
+                }
+            }
+        }
+        Ok((path, req))
     }
 
+    /// 各请求类型都基于 OpenAI 官方地址构建，这里改写为实际要请求的地址: 自定义的基础地址或者 Azure 的部署
+    fn resolve_url(&self, req: &Request, model: Option<String>) -> Result<Url>{
+        let path = api_path(req.url());
+        let query = req.url().query();
+        match &self.azure {
+            Some(azure) => {
+                let model = model.or_else(|| request_model(req));
+                azure.rewrite_url(path, query, model.as_deref())
+            }
+            None if self.base_url != OPENAI_BASE_URL => {
+                let mut url = Url::parse(&format!("{}/{}", self.base_url, path))?;
+                url.set_query(query);
+                Ok(url)
+            }
+            None => Ok(req.url().clone()),
+        }
+    }
+
+}
+
+/// 非 2xx 响应转换为 `ApiError`
+async fn error_for_status(mut res: Response) -> Result<Response> {
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res);
+    }
+    // 失败的请求不消耗令牌，退回预留的全部额度
+    if let Some(reservation) = res.extensions_mut().remove::<Reservation>() {
+        reservation.settle(0);
+    }
+    let request_id = res.headers().get("x-request-id").and_then(|v| v.to_str().ok()).map(String::from);
+    let span = res.extensions().get::<CallSpan>().cloned();
+    let body = res.bytes().await?;
+    let error = ApiError { request_id, ..ApiError::from_response(status, &body) };
+    if let Some(span) = span {
+        span.record_error(&error);
+    }
+    Err(error.into())
+}
+
+/// 获取接口路径中 `/v1/` 之后的部分，例如 `chat/completions`
+pub(crate) fn api_path(url: &Url) -> &str {
+    let path = url.path().trim_start_matches('/');
+    path.strip_prefix("v1/").unwrap_or(path)
+}
+
+/// 从Json请求体中读取 `model` 字段
+pub(crate) fn request_model(req: &Request) -> Option<String> {
+    let body = req.body()?.as_bytes()?;
+    let value: serde_json::Value = serde_json::from_slice(body).ok()?;
+    value.get("model")?.as_str().map(String::from)
+}
+
+/// 响应体中的令牌使用统计，只读取 `usage.total_tokens`
+#[derive(serde::Deserialize)]
+struct UsageBody{
+    usage: Option<UsageTokens>,
+}
+
+#[derive(serde::Deserialize)]
+struct UsageTokens{
+    total_tokens: u32,
 }
 
 /// 创建一个Request特征，让所有类型的自定义Request，都可以构建为RequestBuilder
 pub trait IntoRequest {
     fn into_request(self,client: Client) -> RequestBuilder;
-}
\ No newline at end of file
+
+    /// 请求使用的模型ID，用于确定 Azure 的部署;默认从 Json 请求体中读取，请求体不是 Json 时(例如 multipart 表单)需要实现该方法
+    fn model_id(&self) -> Option<String> {
+        None
+    }
+
+    /// 是否为流式请求，流式请求不设置总的超时时间，改为由 `EventStream` 限制相邻数据块之间的间隔
+    fn is_stream(&self) -> bool {
+        false
+    }
+}
+
+/// 响应体类型确定的请求，用于 `OpenaiSdk::send_with_meta` 推断响应类型
+pub trait TypedRequest: IntoRequest {
+    type Response: DeserializeOwned;
+}
+
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[tokio::test]
+    async fn prepare_request_should_use_base_url_and_bearer_token() -> Result<()>{
+        let sdk = OpenaiSdk::new("sk-test".into());
+        let req = sdk.prepare_request(CreateImageRequest::new("a cat")).await?;
+        assert_eq!(req.url().as_str(), "https://api.openai.com/v1/images/generations");
+        assert_eq!(req.headers()[reqwest::header::AUTHORIZATION], "Bearer sk-test");
+
+        let sdk = OpenaiSdk::new(String::new()).with_base_url("http://localhost:8080/v1/");
+        let req = sdk.prepare_request(CreateImageRequest::new("a cat")).await?;
+        assert_eq!(req.url().as_str(), "http://localhost:8080/v1/images/generations");
+        assert!(req.headers().get(reqwest::header::AUTHORIZATION).is_none());
+        assert_eq!(req.timeout(), Some(&Duration::from_secs(30)));
+
+        // 流式请求不设置总的超时时间
+        let req = sdk.prepare_request(CompletionRequest::new("a").streaming()?).await?;
+        assert!(req.timeout().is_none());
+        Ok(())
+    }
+}
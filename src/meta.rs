@@ -0,0 +1,157 @@
+//!
+//! 响应元数据
+//! 普通的接口方法只返回解析后的响应体，需要请求ID、限流信息、耗时等元数据时，
+//! 使用 `OpenaiSdk::send_with_meta` 获取 `WithMeta<T>`，其他接口方法通过 `OpenaiSdk::with_meta` 获取。
+//!
+
+use std::{ops::Deref, time::Duration};
+
+use reqwest::{StatusCode, header::HeaderMap};
+
+use crate::rate_limit::parse_duration;
+
+///
+/// 一次请求的响应元数据
+///
+#[derive(Debug,Clone)]
+pub struct ResponseMeta{
+    /// HTTP 状态码
+    pub status: StatusCode,
+    /// 响应头
+    pub headers: HeaderMap,
+    /// 从发送请求到收到响应头的耗时，包括限流排队与重试的时间
+    pub latency: Duration,
+    /// 重试次数: 限流器收到 429 后的重试，以及 key 池换用其他 key 的重试
+    pub retries: u32,
+}
+
+impl ResponseMeta {
+    /// 读取响应头的值
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)?.to_str().ok()
+    }
+
+    /// 服务端的请求ID(`x-request-id`)，向服务商反馈问题时需要提供
+    pub fn request_id(&self) -> Option<&str> {
+        self.header("x-request-id")
+    }
+
+    /// 服务端的处理耗时(`openai-processing-ms`)
+    pub fn processing_time(&self) -> Option<Duration> {
+        self.header("openai-processing-ms")?.parse().ok().map(Duration::from_millis)
+    }
+
+    /// 限流相关的响应头(`x-ratelimit-*`)
+    pub fn rate_limit(&self) -> RateLimitInfo {
+        let number = |name: &str| self.header(name)?.parse().ok();
+        let duration = |name: &str| parse_duration(self.header(name)?);
+        RateLimitInfo {
+            limit_requests: number("x-ratelimit-limit-requests"),
+            remaining_requests: number("x-ratelimit-remaining-requests"),
+            reset_requests: duration("x-ratelimit-reset-requests"),
+            limit_tokens: number("x-ratelimit-limit-tokens"),
+            remaining_tokens: number("x-ratelimit-remaining-tokens"),
+            reset_tokens: duration("x-ratelimit-reset-tokens"),
+        }
+    }
+}
+
+/// 限流信息，服务端未返回的字段为 None
+#[derive(Debug,Clone,Copy,Default,PartialEq, Eq)]
+pub struct RateLimitInfo{
+    /// 每分钟请求数上限
+    pub limit_requests: Option<u32>,
+    /// 剩余请求数
+    pub remaining_requests: Option<u32>,
+    /// 请求数额度恢复的时间
+    pub reset_requests: Option<Duration>,
+    /// 每分钟令牌数上限
+    pub limit_tokens: Option<u32>,
+    /// 剩余令牌数
+    pub remaining_tokens: Option<u32>,
+    /// 令牌数额度恢复的时间
+    pub reset_tokens: Option<Duration>,
+}
+
+
+///
+/// 解析后的响应体以及响应元数据，可以通过 `Deref` 直接访问响应体
+///
+#[derive(Debug,Clone)]
+pub struct WithMeta<T>{
+    /// 解析后的响应体
+    pub data: T,
+    /// 响应元数据
+    pub meta: ResponseMeta,
+}
+
+impl<T> WithMeta<T> {
+    /// 取出响应体
+    pub fn into_inner(self) -> T {
+        self.data
+    }
+}
+
+impl<T> Deref for WithMeta<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use anyhow::Result;
+    use reqwest::Method;
+    use serde_json::json;
+    use crate::OpenaiSdk;
+    use crate::api::CreateImageRequest;
+    use crate::error::ApiError;
+    use crate::transport::{MockTransport, MockResponse};
+    use super::*;
+
+    #[tokio::test]
+    async fn send_with_meta_should_expose_headers() -> Result<()>{
+        let mock = MockTransport::new()
+            .once(Method::POST, "images/generations", MockResponse::error(StatusCode::BAD_REQUEST, "bad prompt").header("x-request-id", "req_1"))
+            .on(Method::POST, "images/generations", MockResponse::json(json!({"created": 1, "data": [{"url": "https://example.com/1.png"}]}))
+                .header("x-request-id", "req_2")
+                .header("openai-processing-ms", "1250")
+                .header("x-ratelimit-remaining-requests", "49")
+                .header("x-ratelimit-reset-tokens", "1m30s"));
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock);
+
+        let err = sdk.send_with_meta(CreateImageRequest::new("?")).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<ApiError>().unwrap().request_id.as_deref(), Some("req_1"));
+
+        let res = sdk.send_with_meta(CreateImageRequest::new("a cat")).await?;
+        assert_eq!(res.created, 1);
+        assert_eq!(res.meta.status, StatusCode::OK);
+        assert_eq!(res.meta.request_id(), Some("req_2"));
+        assert_eq!(res.meta.processing_time(), Some(Duration::from_millis(1250)));
+        assert_eq!(res.meta.retries, 0);
+        let rate_limit = res.meta.rate_limit();
+        assert_eq!(rate_limit.remaining_requests, Some(49));
+        assert_eq!(rate_limit.reset_tokens, Some(Duration::from_secs(90)));
+        assert_eq!(rate_limit.limit_tokens, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn with_meta_should_cover_resource_calls_and_key_failovers() -> Result<()>{
+        let mock = MockTransport::new()
+            .once(Method::GET, "files/file-1", MockResponse::error(StatusCode::TOO_MANY_REQUESTS, "slow down"))
+            .on(Method::GET, "files/file-1", MockResponse::json(json!({
+                "id": "file-1", "object": "file", "bytes": 1, "created_at": 1, "filename": "a.md", "purpose": "assistants"
+            })).header("x-request-id", "req_file"));
+        let pool = crate::key_pool::KeyPool::new(["sk-1", "sk-2"]);
+        let sdk = OpenaiSdk::new(String::new()).with_transport(mock).with_key_pool(pool);
+        let file = sdk.with_meta(|sdk| async move { sdk.retrieve_file("file-1").await }).await?;
+        assert_eq!(file.filename, "a.md");
+        assert_eq!(file.meta.request_id(), Some("req_file"));
+        assert_eq!(file.meta.retries, 1);
+        Ok(())
+    }
+}
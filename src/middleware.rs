@@ -0,0 +1,169 @@
+//!
+//! 请求/响应中间件
+//! 通过 `OpenaiSdk::with_middleware` 注册，按注册顺序处理请求，按相反顺序处理响应:
+//! 可以在构建请求前修改类型化的请求体(例如脱敏用户消息、注入系统提示词)，为请求添加请求头，
+//! 记录审计日志，或者直接返回响应而不发送请求。
+//! 类型化的请求体与响应体以 `&mut dyn Any` 传入，通过 `downcast_mut` 获取具体类型，例如 `ChatCompletionRequest`。
+//!
+
+use std::{any::Any, fmt::Debug, sync::Arc};
+
+use anyhow::Result;
+use reqwest::{Request, Response, StatusCode};
+use serde::Serialize;
+
+
+///
+/// 中间件，各个方法默认不做任何处理
+///
+pub trait Middleware: Debug + Send + Sync {
+
+    /// 构建请求前调用，`req` 为类型化的请求体;
+    /// 返回 `Some(response)` 时不再发送请求(也不经过限流与成本统计)，后续中间件的 `on_request` 不会被调用
+    fn on_request(&self, req: &mut dyn Any) -> Result<Option<Response>> {
+        let _ = req;
+        Ok(None)
+    }
+
+    /// 构建出网络请求后、发送前调用，例如添加自定义请求头
+    fn on_http_request(&self, req: &mut Request) -> Result<()> {
+        let _ = req;
+        Ok(())
+    }
+
+    /// 收到响应后、读取响应体前调用，包括 `on_request` 直接返回的响应
+    fn on_response(&self, res: &mut Response) -> Result<()> {
+        let _ = res;
+        Ok(())
+    }
+
+    /// 非流式接口将响应体反序列化后调用，`data` 为类型化的响应体，例如 `ChatCompletionResponse`
+    fn on_data(&self, data: &mut dyn Any) -> Result<()> {
+        let _ = data;
+        Ok(())
+    }
+}
+
+
+// 共享的中间件，例如注册后仍然需要读取其中记录的审计日志
+impl<M: Middleware> Middleware for Arc<M> {
+    fn on_request(&self, req: &mut dyn Any) -> Result<Option<Response>> {
+        self.as_ref().on_request(req)
+    }
+
+    fn on_http_request(&self, req: &mut Request) -> Result<()> {
+        self.as_ref().on_http_request(req)
+    }
+
+    fn on_response(&self, res: &mut Response) -> Result<()> {
+        self.as_ref().on_response(res)
+    }
+
+    fn on_data(&self, data: &mut dyn Any) -> Result<()> {
+        self.as_ref().on_data(data)
+    }
+}
+
+
+/// 构建状态码为 200 的 Json 响应，用于在 `on_request` 中直接返回
+pub fn json_response(body: &impl Serialize) -> Result<Response> {
+    let res = http::Response::builder()
+        .status(StatusCode::OK)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(body)?)?;
+    Ok(Response::from(res))
+}
+
+
+#[cfg(test)]
+mod tests{
+    use std::sync::Mutex;
+    use reqwest::{Method, Response};
+    use serde_json::json;
+    use crate::OpenaiSdk;
+    use crate::api::*;
+    use crate::transport::{MockTransport, MockResponse};
+    use super::*;
+
+    /// 脱敏用户消息中的邮箱、注入系统提示词、添加请求头并记录审计日志
+    #[derive(Debug,Default)]
+    struct Compliance{
+        audit: Mutex<Vec<String>>,
+    }
+
+    impl Middleware for Compliance {
+        fn on_request(&self, req: &mut dyn Any) -> Result<Option<Response>> {
+            if let Some(req) = req.downcast_mut::<ChatCompletionRequest>() {
+                for message in req.messages_mut().iter_mut() {
+                    if let ChatMessage::User(_) = message {
+                        let content = message.content_mut().unwrap();
+                        *content = content.replace("alice@example.com", "[EMAIL]");
+                    }
+                }
+                req.messages_mut().insert(0, ChatMessage::new_system("company policy", ""));
+            }
+            Ok(None)
+        }
+
+        fn on_http_request(&self, req: &mut Request) -> Result<()> {
+            req.headers_mut().insert("x-team", "search".parse()?);
+            self.audit.lock().unwrap().push(format!("request {}", req.url().path()));
+            Ok(())
+        }
+
+        fn on_data(&self, data: &mut dyn Any) -> Result<()> {
+            if let Some(res) = data.downcast_ref::<ChatCompletionResponse>() {
+                self.audit.lock().unwrap().push(format!("response {}", res.id));
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn middleware_should_rewrite_request_and_see_response() -> Result<()>{
+        let mock = MockTransport::new().on(Method::POST, "chat/completions", MockResponse::json(json!({
+            "id": "chatcmpl-1", "object": "chat.completion", "created": 1, "model": "gpt-4o",
+            "choices": [{"index": 0, "message": {"role": "assistant", "content": "ok"}, "finish_reason": "stop"}]
+        })));
+        let compliance = Arc::new(Compliance::default());
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock.clone()).with_middleware(compliance.clone());
+        let req = ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatMessage::new_user("mail alice@example.com", "")])
+            .build()?;
+        sdk.chat_completion(req).await?;
+
+        let recorded = mock.requests().remove(0);
+        assert_eq!(recorded.headers["x-team"], "search");
+        assert_eq!(recorded.json()?["messages"], json!([
+            {"role": "system", "content": "company policy"},
+            {"role": "user", "content": "mail [EMAIL]"}
+        ]));
+        assert_eq!(*compliance.audit.lock().unwrap(), ["request /v1/chat/completions", "response chatcmpl-1"]);
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    struct Offline;
+
+    impl Middleware for Offline {
+        fn on_request(&self, req: &mut dyn Any) -> Result<Option<Response>> {
+            match req.is::<EmbeddingRequest>() {
+                true => Ok(Some(json_response(&json!({
+                    "object": "list", "model": "text-embedding-3-small", "usage": {"prompt_tokens": 1, "total_tokens": 1},
+                    "data": [{"object": "embedding", "index": 0, "embedding": [1.0]}]
+                }))?)),
+                false => Ok(None),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn middleware_should_short_circuit() -> Result<()>{
+        let mock = MockTransport::new();
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock.clone()).with_middleware(Offline);
+        let res = sdk.create_embedding(EmbeddingRequest::new("hello")).await?;
+        assert_eq!(res.data[0].embedding, [1.0]);
+        assert!(mock.requests().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,448 @@
+//!
+//! 可嵌入的本地模拟 OpenAI 服务，用于在没有网络的环境中进行端到端的集成测试
+//! 实现了 `/v1/chat/completions`(包括流式返回以及工具调用)、`/v1/images/generations`、
+//! `/v1/embeddings` 和 `/v1/models` 接口，可以预设回复、注入错误、模拟延迟以及限流。
+//! 需要开启 `mock-server` 特性。
+//!
+
+use std::{
+    collections::{HashMap, VecDeque, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    net::SocketAddr,
+    sync::{Arc, Mutex, atomic::{AtomicU64, Ordering}},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use serde_json::{Value, json};
+use tokio::{net::TcpListener, sync::oneshot};
+
+use crate::OpenaiSdk;
+
+/// 未指定 dimensions 时模拟嵌入向量的维度
+const DEFAULT_EMBEDDING_DIMENSIONS: usize = 256;
+
+/// `/v1/models` 返回的模型列表
+const MOCK_MODELS: [&str; 5] = ["gpt-3.5-turbo-1106", "gpt-4-1106-preview", "gpt-4o", "dall-e-3", "text-embedding-3-small"];
+
+
+/// 模拟服务实现的接口
+#[derive(Debug,Clone,Copy,PartialEq, Eq,Hash)]
+pub enum MockEndpoint{
+    ChatCompletions,
+    ImageGenerations,
+    Embeddings,
+    Models,
+}
+
+/// 预设的回复，按照预设的顺序依次使用，用完后恢复默认行为
+#[derive(Debug,Clone)]
+pub enum MockReply{
+    /// 聊天接口回复指定文本
+    Text(String),
+    /// 聊天接口回复工具调用，每一项为函数名称与参数
+    ToolCalls(Vec<(String, Value)>),
+    /// 返回指定状态码的 OpenAI 格式错误
+    Error{ status: u16, message: String },
+    /// 原样返回 Json 响应体
+    Json(Value),
+}
+
+/// 模拟服务收到的请求
+#[derive(Debug,Clone)]
+pub struct ReceivedRequest{
+    /// 请求的接口
+    pub endpoint: MockEndpoint,
+    /// 请求头
+    pub headers: HashMap<String, String>,
+    /// Json 请求体，GET 请求为 null
+    pub body: Value,
+}
+
+
+///
+/// 本地模拟服务，监听 127.0.0.1 上的随机端口，销毁时停止服务
+///
+#[derive(Debug)]
+pub struct MockServer{
+    addr: SocketAddr,
+    state: Arc<ServerState>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+#[derive(Debug,Default)]
+struct ServerState{
+    replies: Mutex<HashMap<MockEndpoint, VecDeque<MockReply>>>,
+    requests: Mutex<Vec<ReceivedRequest>>,
+    latency: Mutex<Duration>,
+    rate_limit: Mutex<Option<RateWindow>>,
+    next_id: AtomicU64,
+}
+
+/// 每分钟请求数限制的计数窗口
+#[derive(Debug)]
+struct RateWindow{
+    limit: u32,
+    started: Instant,
+    count: u32,
+}
+
+impl MockServer {
+
+    ///
+    /// 启动模拟服务
+    ///
+    pub async fn start() -> Result<Self> {
+        let state = Arc::new(ServerState::default());
+        let app = Router::new()
+            .route("/v1/chat/completions", post(chat_completions))
+            .route("/v1/images/generations", post(image_generations))
+            .route("/v1/embeddings", post(embeddings))
+            .route("/v1/models", get(models))
+            .with_state(state.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let (shutdown, signal) = oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app)
+                .with_graceful_shutdown(async { let _ = signal.await; })
+                .await;
+        });
+        Ok(Self { addr, state, shutdown: Some(shutdown) })
+    }
+
+    /// 服务的基础地址，例如 `http://127.0.0.1:12345/v1`
+    pub fn base_url(&self) -> String {
+        format!("http://{}/v1", self.addr)
+    }
+
+    /// 指向该服务的 SDK
+    pub fn sdk(&self) -> OpenaiSdk {
+        OpenaiSdk::new("sk-mock".into()).with_base_url(self.base_url())
+    }
+
+    /// 为指定接口追加一个预设回复
+    pub fn push_reply(&self, endpoint: MockEndpoint, reply: MockReply) {
+        self.state.replies.lock().unwrap().entry(endpoint).or_default().push_back(reply);
+    }
+
+    /// 指定接口的下一次请求返回错误
+    pub fn fail_next(&self, endpoint: MockEndpoint, status: u16, message: impl Into<String>) {
+        self.push_reply(endpoint, MockReply::Error { status, message: message.into() });
+    }
+
+    /// 设置每个请求的响应延迟
+    pub fn set_latency(&self, latency: Duration) {
+        *self.state.latency.lock().unwrap() = latency;
+    }
+
+    /// 设置每分钟的请求数限制，超出后返回 429，所有响应都会带上 `x-ratelimit-*` 响应头
+    pub fn set_rate_limit(&self, requests_per_minute: u32) {
+        *self.state.rate_limit.lock().unwrap() = Some(RateWindow { limit: requests_per_minute, started: Instant::now(), count: 0 });
+    }
+
+    /// 已收到的所有请求
+    pub fn requests(&self) -> Vec<ReceivedRequest> {
+        self.state.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+
+impl ServerState {
+
+    ///
+    /// 各接口的公共处理: 记录请求、模拟延迟、检查限流并取出预设回复;
+    /// 返回需要附加到响应上的响应头，被限流或者预设了错误时直接返回错误响应
+    ///
+    async fn begin(&self, endpoint: MockEndpoint, headers: &HeaderMap, body: &Value) -> std::result::Result<(HeaderMap, Option<MockReply>), Response> {
+        self.requests.lock().unwrap().push(ReceivedRequest {
+            endpoint,
+            headers: headers.iter()
+                .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).into_owned()))
+                .collect(),
+            body: body.clone(),
+        });
+        let latency = *self.latency.lock().unwrap();
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+
+        let mut headers = HeaderMap::new();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        headers.insert("x-request-id", header_value(format!("req_mock_{id}")));
+        headers.insert("openai-processing-ms", header_value(latency.as_millis().to_string()));
+        if let Some(window) = self.rate_limit.lock().unwrap().as_mut() {
+            if window.started.elapsed() >= Duration::from_secs(60) {
+                window.started = Instant::now();
+                window.count = 0;
+            }
+            let reset = Duration::from_secs(60).saturating_sub(window.started.elapsed());
+            headers.insert("x-ratelimit-limit-requests", header_value(window.limit.to_string()));
+            headers.insert("x-ratelimit-reset-requests", header_value(format!("{}ms", reset.as_millis())));
+            if window.count >= window.limit {
+                headers.insert("x-ratelimit-remaining-requests", header_value("0".into()));
+                headers.insert("retry-after", header_value(reset.as_secs().max(1).to_string()));
+                let message = format!("Rate limit reached: limit {} requests per minute", window.limit);
+                return Err(error_response(429, &message, "requests", headers));
+            }
+            window.count += 1;
+            headers.insert("x-ratelimit-remaining-requests", header_value((window.limit - window.count).to_string()));
+        }
+
+        let reply = self.replies.lock().unwrap().get_mut(&endpoint).and_then(VecDeque::pop_front);
+        match reply {
+            Some(MockReply::Error { status, message }) => Err(error_response(status, &message, "invalid_request_error", headers)),
+            Some(MockReply::Json(body)) => Err((headers, Json(body)).into_response()),
+            reply => Ok((headers, reply)),
+        }
+    }
+}
+
+
+async fn chat_completions(State(state): State<Arc<ServerState>>, headers: HeaderMap, Json(body): Json<Value>) -> Response {
+    let (headers, reply) = match state.begin(MockEndpoint::ChatCompletions, &headers, &body).await {
+        Ok(begin) => begin,
+        Err(res) => return res,
+    };
+    let model = body["model"].as_str().unwrap_or("gpt-3.5-turbo-1106").to_string();
+    let messages = body["messages"].as_array().cloned().unwrap_or_default();
+    let prompt_tokens: usize = messages.iter().map(|m| count_tokens(m["content"].as_str().unwrap_or_default())).sum();
+    let reply = reply.unwrap_or_else(|| {
+        let last = messages.iter().rev().find(|m| m["role"] == "user").and_then(|m| m["content"].as_str()).unwrap_or_default();
+        MockReply::Text(format!("This is a mock response to: {last}"))
+    });
+    let id = format!("chatcmpl-mock{}", state.next_id.load(Ordering::Relaxed));
+    let created = unix_time();
+
+    let (content, tool_calls) = match &reply {
+        MockReply::Text(text) => (Some(text.clone()), Vec::new()),
+        MockReply::ToolCalls(calls) => (None, calls.iter().enumerate().map(|(i, (name, args))| json!({
+            "id": format!("call_mock{i}"),
+            "type": "function",
+            "function": {"name": name, "arguments": args.to_string()}
+        })).collect()),
+        _ => unreachable!("errors and raw bodies are returned by begin"),
+    };
+    let finish_reason = if tool_calls.is_empty() { "stop" } else { "tool_calls" };
+    let completion_tokens = content.as_deref().map(count_tokens).unwrap_or(tool_calls.len() * 8);
+    let usage = json!({"prompt_tokens": prompt_tokens, "completion_tokens": completion_tokens, "total_tokens": prompt_tokens + completion_tokens});
+
+    if body["stream"] != true {
+        let mut message = json!({"role": "assistant", "content": content});
+        if !tool_calls.is_empty() {
+            message["tool_calls"] = Value::Array(tool_calls);
+        }
+        let res = json!({
+            "id": id, "object": "chat.completion", "created": created, "model": model,
+            "system_fingerprint": "fp_mock",
+            "choices": [{"index": 0, "message": message, "logprobs": null, "finish_reason": finish_reason}],
+            "usage": usage,
+        });
+        return (headers, Json(res)).into_response();
+    }
+
+    // 流式返回: 第一个数据块为角色，之后逐词返回内容或逐个返回工具调用，最后返回停止原因
+    let chunk = |delta: Value, finish_reason: Value| json!({
+        "id": id, "object": "chat.completion.chunk", "created": created, "model": model,
+        "system_fingerprint": "fp_mock",
+        "choices": [{"index": 0, "delta": delta, "logprobs": null, "finish_reason": finish_reason}],
+    });
+    let mut chunks = vec![chunk(json!({"role": "assistant", "content": ""}), Value::Null)];
+    if let Some(content) = &content {
+        for word in content.split_inclusive(' ') {
+            chunks.push(chunk(json!({"content": word}), Value::Null));
+        }
+    }
+    for (index, call) in tool_calls.iter().enumerate() {
+        let arguments = call["function"]["arguments"].as_str().unwrap_or_default();
+        let (head, tail) = arguments.split_at(arguments.len() / 2);
+        chunks.push(chunk(json!({"tool_calls": [{
+            "index": index, "id": call["id"], "type": "function",
+            "function": {"name": call["function"]["name"], "arguments": head}
+        }]}), Value::Null));
+        chunks.push(chunk(json!({"tool_calls": [{"index": index, "function": {"arguments": tail}}]}), Value::Null));
+    }
+    chunks.push(chunk(json!({}), finish_reason.into()));
+    if body["stream_options"]["include_usage"] == true {
+        chunks.push(json!({
+            "id": id, "object": "chat.completion.chunk", "created": created, "model": model,
+            "choices": [], "usage": usage,
+        }));
+    }
+    let mut events: String = chunks.iter().map(|c| format!("data: {c}\n\n")).collect();
+    events.push_str("data: [DONE]\n\n");
+    let mut headers = headers;
+    headers.insert("content-type", HeaderValue::from_static("text/event-stream"));
+    (headers, events).into_response()
+}
+
+async fn image_generations(State(state): State<Arc<ServerState>>, headers: HeaderMap, Json(body): Json<Value>) -> Response {
+    let (headers, _) = match state.begin(MockEndpoint::ImageGenerations, &headers, &body).await {
+        Ok(begin) => begin,
+        Err(res) => return res,
+    };
+    let n = body["n"].as_u64().unwrap_or(1);
+    let prompt = body["prompt"].as_str().unwrap_or_default();
+    let data: Vec<Value> = (0..n).map(|i| match body["response_format"].as_str() {
+        Some("b64_json") => json!({"b64_json": "bW9jaw==", "revised_prompt": prompt}),
+        _ => json!({"url": format!("https://mock.openai.local/images/{i}.png"), "revised_prompt": prompt}),
+    }).collect();
+    (headers, Json(json!({"created": unix_time(), "data": data}))).into_response()
+}
+
+async fn embeddings(State(state): State<Arc<ServerState>>, headers: HeaderMap, Json(body): Json<Value>) -> Response {
+    let (headers, _) = match state.begin(MockEndpoint::Embeddings, &headers, &body).await {
+        Ok(begin) => begin,
+        Err(res) => return res,
+    };
+    let inputs: Vec<String> = match &body["input"] {
+        Value::Array(items) => items.iter().map(|i| i.as_str().unwrap_or_default().to_string()).collect(),
+        input => vec![input.as_str().unwrap_or_default().to_string()],
+    };
+    let dimensions = body["dimensions"].as_u64().map(|d| d as usize).unwrap_or(DEFAULT_EMBEDDING_DIMENSIONS);
+    let tokens: usize = inputs.iter().map(|i| count_tokens(i)).sum();
+    let data: Vec<Value> = inputs.iter().enumerate()
+        .map(|(index, input)| json!({"object": "embedding", "embedding": mock_embedding(input, dimensions), "index": index}))
+        .collect();
+    let res = json!({
+        "object": "list",
+        "data": data,
+        "model": body["model"].as_str().unwrap_or("text-embedding-3-small"),
+        "usage": {"prompt_tokens": tokens, "total_tokens": tokens},
+    });
+    (headers, Json(res)).into_response()
+}
+
+async fn models(State(state): State<Arc<ServerState>>, headers: HeaderMap) -> Response {
+    let (headers, _) = match state.begin(MockEndpoint::Models, &headers, &Value::Null).await {
+        Ok(begin) => begin,
+        Err(res) => return res,
+    };
+    let data: Vec<Value> = MOCK_MODELS.iter()
+        .map(|id| json!({"id": id, "object": "model", "created": 1700000000, "owned_by": "mock"}))
+        .collect();
+    (headers, Json(json!({"object": "list", "data": data}))).into_response()
+}
+
+
+/// OpenAI 格式的错误响应
+fn error_response(status: u16, message: &str, r#type: &str, headers: HeaderMap) -> Response {
+    let status = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let body = json!({"error": {"message": message, "type": r#type, "param": null, "code": null}});
+    (status, headers, Json(body)).into_response()
+}
+
+fn header_value(value: String) -> HeaderValue {
+    HeaderValue::from_str(&value).unwrap_or_else(|_| HeaderValue::from_static(""))
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+/// 粗略的令牌数: 按空白字符切分的单词数
+fn count_tokens(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+///
+/// 确定性的模拟嵌入向量: 将每个小写单词哈希到一个维度上累加后归一化(词袋哈希)，
+/// 包含相同单词的文本之间余弦相似度较高，便于测试相似度检索
+///
+fn mock_embedding(text: &str, dimensions: usize) -> Vec<f32> {
+    let mut vector = vec![0f32; dimensions.max(1)];
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+        let mut hasher = DefaultHasher::new();
+        word.to_lowercase().hash(&mut hasher);
+        let len = vector.len() as u64;
+        vector[(hasher.finish() % len) as usize] += 1.0;
+    }
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+    vector
+}
+
+
+#[cfg(test)]
+mod tests{
+    use futures::StreamExt;
+    use crate::api::*;
+    use crate::error::ApiError;
+    use super::*;
+
+    fn chat_request(content: &str) -> ChatCompletionRequest {
+        ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatMessage::new_user(content, "")])
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn mock_server_should_serve_all_endpoints() -> Result<()>{
+        let server = MockServer::start().await?;
+        let sdk = server.sdk();
+
+        let res = sdk.chat_completion(chat_request("hello")).await?;
+        assert_eq!(res.choices[0].message.content.as_deref(), Some("This is a mock response to: hello"));
+
+        server.push_reply(MockEndpoint::ChatCompletions, MockReply::ToolCalls(vec![("get_weather".into(), json!({"city": "Paris"}))]));
+        let mut stream = sdk.chat_completion_stream(chat_request("weather?")).await?;
+        let mut arguments = String::new();
+        let mut finish_reason = None;
+        while let Some(chunk) = stream.next().await {
+            let choice = chunk?.choices.remove(0);
+            for call in choice.delta.tool_calls.unwrap_or_default() {
+                arguments.push_str(call.function.and_then(|f| f.arguments).as_deref().unwrap_or_default());
+            }
+            finish_reason = choice.finish_reason.or(finish_reason);
+        }
+        assert_eq!(arguments, r#"{"city":"Paris"}"#);
+        assert_eq!(finish_reason, Some(FinishReason::ToolCalls));
+
+        let images = sdk.create_image(CreateImageRequest::new("a cat")).await?;
+        assert!(images.data[0].url.is_some());
+
+        let embeddings = sdk.create_embedding(EmbeddingRequest::new(vec!["refund policy", "what is the refund policy"])).await?;
+        assert_eq!(embeddings.data.len(), 2);
+        assert_eq!(embeddings.data[0].embedding.len(), DEFAULT_EMBEDDING_DIMENSIONS);
+
+        let models = sdk.list_models().await?;
+        assert!(models.data.iter().any(|m| m.id == "gpt-4o"));
+        assert_eq!(server.requests().len(), 5);
+        assert_eq!(server.requests()[0].headers["authorization"], "Bearer sk-mock");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mock_server_should_inject_errors_and_rate_limits() -> Result<()>{
+        let server = MockServer::start().await?;
+        let sdk = server.sdk();
+
+        server.fail_next(MockEndpoint::ChatCompletions, 500, "upstream exploded");
+        let err = sdk.chat_completion(chat_request("hi")).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<ApiError>().unwrap().status.as_u16(), 500);
+
+        server.set_rate_limit(1);
+        sdk.list_models().await?;
+        let err = sdk.list_models().await.unwrap_err();
+        assert!(err.downcast_ref::<ApiError>().unwrap().is_rate_limited());
+        Ok(())
+    }
+}
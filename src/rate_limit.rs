@@ -0,0 +1,484 @@
+//!
+//! 客户端限流
+//! 按照模型分别跟踪每分钟请求数(RPM)与每分钟令牌数(TPM)，发送请求之前使用本地估算的令牌数预留额度，
+//! 收到响应后根据实际使用的令牌数以及 `x-ratelimit-*` 响应头修正额度;
+//! 额度不足时请求按照到达顺序排队等待，而不是直接发送后收到 429。
+//!
+
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::Duration};
+
+use anyhow::Result;
+use reqwest::{Request, Response, StatusCode, header::HeaderMap};
+use serde_json::Value;
+use tokio::time::Instant;
+
+use crate::{request_model, transport::Transport};
+
+/// 请求体中没有 model 字段时使用的额度分组
+const DEFAULT_GROUP: &str = "default";
+
+/// 收到 429 但没有返回重置时间时的等待时间
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+
+/// 每分钟的请求数与令牌数限制，0 表示不限制
+#[derive(Debug,Clone,Copy,PartialEq, Eq)]
+pub struct RateLimits{
+    /// 每分钟请求数，0 表示不限制
+    pub requests_per_minute: u32,
+    /// 每分钟令牌数，0 表示不限制
+    pub tokens_per_minute: u32,
+}
+
+impl RateLimits {
+    pub fn new(requests_per_minute: u32, tokens_per_minute: u32) -> Self {
+        Self { requests_per_minute, tokens_per_minute }
+    }
+}
+
+
+///
+/// 客户端限流器，通过 `OpenaiSdk::with_rate_limiter` 开启
+///
+#[derive(Debug)]
+pub struct RateLimiter{
+    /// 未单独配置的模型使用的限制
+    default_limits: RateLimits,
+    /// 按模型配置的限制
+    model_limits: HashMap<String, RateLimits>,
+    /// 收到 429 后的最大重试次数
+    max_retries: u32,
+    /// 各模型的排队与额度状态
+    queues: Mutex<HashMap<String, Arc<ModelQueue>>>,
+}
+
+/// 单个模型的排队与额度状态
+#[derive(Debug)]
+struct ModelQueue{
+    /// 排队锁，tokio 的互斥锁按照请求的先后顺序获取，保证公平
+    gate: tokio::sync::Mutex<()>,
+    budget: Mutex<Budget>,
+}
+
+/// 令牌桶: 额度按照每分钟的限制匀速恢复
+#[derive(Debug)]
+struct Budget{
+    limits: RateLimits,
+    requests: f64,
+    tokens: f64,
+    updated: Instant,
+    /// 收到 429 后暂停发送，直到该时间
+    paused_until: Option<Instant>,
+}
+
+/// 一次请求预留的额度，附加在响应的扩展中，读取到实际使用的令牌数后通过 `settle` 修正;
+/// 没有修正就被丢弃时(发送失败、流式响应没有返回用量等)只保留输入部分的估算，退回预留的输出令牌数
+#[derive(Debug)]
+pub(crate) struct Reservation{
+    queue: Arc<ModelQueue>,
+    prompt_tokens: u32,
+    estimated_tokens: u32,
+    pub(crate) retries: u32,
+    settled: bool,
+}
+
+impl Reservation {
+    /// 根据实际使用的令牌数修正预留的额度
+    pub(crate) fn settle(mut self, actual_tokens: u32) {
+        self.reconcile(actual_tokens);
+    }
+
+    fn reconcile(&mut self, actual_tokens: u32) {
+        if std::mem::replace(&mut self.settled, true) {
+            return;
+        }
+        let mut budget = self.queue.budget.lock().unwrap();
+        let cap = budget.limits.tokens_per_minute as f64;
+        budget.tokens = (budget.tokens + self.estimated_tokens as f64 - actual_tokens as f64).min(cap);
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.reconcile(self.prompt_tokens);
+    }
+}
+
+impl RateLimiter {
+
+    ///
+    /// 创建限流器，`limits` 作为所有模型的默认限制
+    ///
+    pub fn new(limits: RateLimits) -> Self {
+        Self { default_limits: limits, model_limits: HashMap::new(), max_retries: 3, queues: Mutex::new(HashMap::new()) }
+    }
+
+    /// 为指定模型设置单独的限制
+    pub fn model(mut self, model: impl Into<String>, limits: RateLimits) -> Self {
+        self.model_limits.insert(model.into(), limits);
+        self
+    }
+
+    /// 设置收到 429 后的最大重试次数，默认为 3
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// 指定模型当前剩余的 (请求数, 令牌数) 额度
+    pub fn remaining(&self, model: &str) -> (u32, u32) {
+        let queue = self.queue(model);
+        let mut budget = queue.budget.lock().unwrap();
+        budget.refill(Instant::now());
+        (budget.requests as u32, budget.tokens as u32)
+    }
+
+    fn queue(&self, model: &str) -> Arc<ModelQueue> {
+        let mut queues = self.queues.lock().unwrap();
+        queues.entry(model.to_string()).or_insert_with(|| {
+            let limits = self.model_limits.get(model).copied().unwrap_or(self.default_limits);
+            Arc::new(ModelQueue { gate: tokio::sync::Mutex::new(()), budget: Mutex::new(Budget::new(limits)) })
+        }).clone()
+    }
+
+    ///
+    /// 排队等待额度后发送请求，收到 429 时暂停该模型的发送并重试;
+    /// 无法克隆的请求(例如 multipart 上传)不会重试
+    ///
+    pub(crate) async fn execute(&self, transport: &dyn Transport, req: Request) -> Result<Response> {
+        let model = request_model(&req).unwrap_or_else(|| DEFAULT_GROUP.to_string());
+        let (prompt_tokens, output_tokens) = req.body()
+            .and_then(|b| b.as_bytes())
+            .and_then(|b| serde_json::from_slice::<Value>(b).ok())
+            .map(|body| estimate_prompt_and_output(&body))
+            .unwrap_or_default();
+        let prompt_tokens = prompt_tokens.min(u32::MAX as u64) as u32;
+        let estimated_tokens = (prompt_tokens as u64 + output_tokens).min(u32::MAX as u64) as u32;
+        let queue = self.queue(&model);
+        let mut req = Some(req);
+        let mut retries = 0;
+        loop {
+            self.acquire(&queue, estimated_tokens).await;
+            // 之后的任何路径上丢弃预留都会退回多预留的令牌数
+            let reservation = Reservation { queue: queue.clone(), prompt_tokens, estimated_tokens, retries, settled: false };
+            let current = req.take().expect("request is available for each attempt");
+            let retry = current.try_clone();
+            let mut res = transport.execute(current).await?;
+            {
+                let mut budget = queue.budget.lock().unwrap();
+                budget.update_from_headers(res.headers());
+                if res.status() == StatusCode::TOO_MANY_REQUESTS {
+                    budget.pause(retry_after(res.headers()));
+                }
+            }
+            match retry {
+                Some(retry) if res.status() == StatusCode::TOO_MANY_REQUESTS && retries < self.max_retries => {
+                    // 被拒绝的请求不消耗令牌
+                    reservation.settle(0);
+                    retries += 1;
+                    req = Some(retry);
+                }
+                _ => {
+                    res.extensions_mut().insert(reservation);
+                    return Ok(res);
+                }
+            }
+        }
+    }
+
+    /// 按照到达顺序排队，等待额度足够后预留额度
+    async fn acquire(&self, queue: &ModelQueue, tokens: u32) {
+        let _turn = queue.gate.lock().await;
+        loop {
+            let wait = queue.budget.lock().unwrap().try_reserve(tokens, Instant::now());
+            match wait {
+                None => return,
+                Some(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+}
+
+
+impl Budget {
+    fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            requests: limits.requests_per_minute as f64,
+            tokens: limits.tokens_per_minute as f64,
+            updated: Instant::now(),
+            paused_until: None,
+        }
+    }
+
+    /// 按照经过的时间恢复额度
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        let rpm = self.limits.requests_per_minute as f64;
+        let tpm = self.limits.tokens_per_minute as f64;
+        self.requests = (self.requests + elapsed * rpm / 60.0).min(rpm);
+        self.tokens = (self.tokens + elapsed * tpm / 60.0).min(tpm);
+        self.updated = now;
+    }
+
+    /// 额度足够时预留并返回 None，否则返回需要等待的时间
+    fn try_reserve(&mut self, tokens: u32, now: Instant) -> Option<Duration> {
+        self.refill(now);
+        if let Some(until) = self.paused_until {
+            if until > now {
+                return Some(until - now);
+            }
+            self.paused_until = None;
+        }
+        // 超过整分钟额度的请求只需要等待额度恢复满即可发送;限制为 0 时不检查对应的额度
+        let unlimited_requests = self.limits.requests_per_minute == 0;
+        let unlimited_tokens = self.limits.tokens_per_minute == 0;
+        let tpm = self.limits.tokens_per_minute as f64;
+        let tokens = (tokens as f64).min(tpm);
+        let enough_requests = unlimited_requests || self.requests >= 1.0;
+        let enough_tokens = unlimited_tokens || self.tokens >= tokens;
+        if enough_requests && enough_tokens {
+            if !unlimited_requests {
+                self.requests -= 1.0;
+            }
+            if !unlimited_tokens {
+                self.tokens -= tokens;
+            }
+            return None;
+        }
+        let wait_requests = if enough_requests { 0.0 } else { (1.0 - self.requests) * 60.0 / self.limits.requests_per_minute as f64 };
+        let wait_tokens = if enough_tokens { 0.0 } else { (tokens - self.tokens) * 60.0 / tpm };
+        Some(Duration::from_secs_f64(wait_requests.max(wait_tokens)).max(Duration::from_millis(1)))
+    }
+
+    fn pause(&mut self, duration: Duration) {
+        let until = Instant::now() + duration;
+        self.paused_until = Some(self.paused_until.map_or(until, |current| current.max(until)));
+    }
+
+    /// 使用服务端返回的 `x-ratelimit-*` 响应头修正限制与剩余额度，剩余额度取本地与服务端中较小的值
+    fn update_from_headers(&mut self, headers: &HeaderMap) {
+        let number = |name: &str| headers.get(name)?.to_str().ok()?.parse::<u32>().ok();
+        self.refill(Instant::now());
+        if let Some(limit) = number("x-ratelimit-limit-requests") {
+            self.limits.requests_per_minute = limit;
+        }
+        if let Some(limit) = number("x-ratelimit-limit-tokens") {
+            self.limits.tokens_per_minute = limit;
+        }
+        if let Some(remaining) = number("x-ratelimit-remaining-requests") {
+            self.requests = self.requests.min(remaining as f64);
+            if remaining == 0 {
+                if let Some(reset) = header_duration(headers, "x-ratelimit-reset-requests") {
+                    self.pause(reset);
+                }
+            }
+        }
+        if let Some(remaining) = number("x-ratelimit-remaining-tokens") {
+            self.tokens = self.tokens.min(remaining as f64);
+        }
+    }
+}
+
+
+/// 429 响应的等待时间: 优先使用 `retry-after`，其次使用重置时间中较大的一个
+pub(crate) fn retry_after(headers: &HeaderMap) -> Duration {
+    let retry_after = headers.get("retry-after")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<f64>().ok())
+        .map(Duration::from_secs_f64);
+    retry_after
+        .or_else(|| {
+            let requests = header_duration(headers, "x-ratelimit-reset-requests");
+            let tokens = header_duration(headers, "x-ratelimit-reset-tokens");
+            requests.max(tokens)
+        })
+        .unwrap_or(DEFAULT_RETRY_AFTER)
+}
+
+fn header_duration(headers: &HeaderMap, name: &str) -> Option<Duration> {
+    parse_duration(headers.get(name)?.to_str().ok()?)
+}
+
+/// 解析重置时间，例如 `6ms`、`1s`、`20.5s`、`1m30s`、`1h2m`
+pub(crate) fn parse_duration(value: &str) -> Option<Duration> {
+    let mut total = 0f64;
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let split = rest.find(|c: char| !(c.is_ascii_digit() || c == '.'))?;
+        let (number, tail) = rest.split_at(split);
+        let number: f64 = number.parse().ok()?;
+        let unit_len = tail.find(|c: char| c.is_ascii_digit()).unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        total += number * match unit {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            _ => return None,
+        };
+        rest = tail;
+    }
+    Some(Duration::from_secs_f64(total))
+}
+
+
+///
+/// 粗略估算 Json 请求体将消耗的令牌数: 文本按照 4 个字符 1 个令牌估算，每条消息额外计 4 个令牌，
+/// 再加上请求的最大输出令牌数(乘以选项数 n);只是近似值，不会与服务端的分词结果完全一致
+///
+pub fn estimate_tokens(body: &Value) -> u32 {
+    let (prompt, output) = estimate_prompt_and_output(body);
+    (prompt + output).min(u32::MAX as u64) as u32
+}
+
+/// 分别估算输入令牌数与最大输出令牌数，用于按照输入与输出的单价估算花费
+pub(crate) fn estimate_prompt_and_output(body: &Value) -> (u64, u64) {
+    let mut chars = 0usize;
+    let mut messages = 0usize;
+    for key in ["messages", "prompt", "input", "instructions"] {
+        match &body[key] {
+            Value::Array(items) if key == "messages" => {
+                messages += items.len();
+                items.iter().for_each(|m| chars += text_len(&m["content"]));
+            }
+            value => chars += text_len(value),
+        }
+    }
+    let prompt = chars.div_ceil(4) + messages * 4 + 3;
+    let max_output = ["max_completion_tokens", "max_tokens", "max_output_tokens"]
+        .iter()
+        .find_map(|key| body[key].as_u64())
+        .unwrap_or_default() as usize;
+    let n = body["n"].as_u64().unwrap_or(1) as usize;
+    (prompt as u64, max_output.saturating_mul(n) as u64)
+}
+
+/// 文本内容的字符数: 字符串、字符串数组或者带有 text 字段的内容块数组
+fn text_len(value: &Value) -> usize {
+    match value {
+        Value::String(s) => s.chars().count(),
+        Value::Array(items) => items.iter().map(|item| match item {
+            Value::String(s) => s.chars().count(),
+            other => text_len(&other["text"]) + text_len(&other["content"]),
+        }).sum(),
+        _ => 0,
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use futures::StreamExt;
+    use reqwest::Method;
+    use serde_json::json;
+    use crate::OpenaiSdk;
+    use crate::api::*;
+    use crate::transport::{MockTransport, MockResponse};
+    use super::*;
+
+    fn chat_response() -> MockResponse {
+        MockResponse::json(json!({
+            "id": "chatcmpl-1", "object": "chat.completion", "created": 1, "model": "gpt-3.5-turbo-1106",
+            "choices": [{"index": 0, "message": {"role": "assistant", "content": "hi"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 10, "completion_tokens": 2, "total_tokens": 12}
+        }))
+    }
+
+    fn chat_request() -> ChatCompletionRequest {
+        ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatMessage::new_user("hello there", "")])
+            .max_tokens(100)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn estimate_and_parse_should_work(){
+        let body = serde_json::to_value(chat_request()).unwrap();
+        assert_eq!(estimate_tokens(&body), 3 + 4 + 3 + 100);
+        assert_eq!(parse_duration("1m30s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("6ms"), Some(Duration::from_millis(6)));
+        assert_eq!(parse_duration("20.5s"), Some(Duration::from_millis(20500)));
+        assert_eq!(parse_duration("soon"), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limiter_should_queue_when_budget_is_exhausted() -> Result<()>{
+        let mock = MockTransport::new().on(Method::POST, "chat/completions", chat_response());
+        let limiter = RateLimiter::new(RateLimits::new(1000, 1_000_000))
+            .model("gpt-3.5-turbo-1106", RateLimits::new(2, 1_000_000));
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock.clone()).with_rate_limiter(limiter);
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            sdk.chat_completion(chat_request()).await?;
+        }
+        // 每分钟 2 个请求，第三个请求需要等待 30 秒恢复一个请求的额度
+        assert!(start.elapsed() >= Duration::from_secs(30));
+        assert_eq!(mock.requests().len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limiter_should_retry_after_429_and_follow_headers() -> Result<()>{
+        let mock = MockTransport::new()
+            .once(Method::POST, "chat/completions", MockResponse::error(StatusCode::TOO_MANY_REQUESTS, "slow down").header("retry-after", "2"))
+            .on(Method::POST, "chat/completions", chat_response()
+                .header("x-ratelimit-remaining-requests", "0")
+                .header("x-ratelimit-reset-requests", "5s")
+                .header("x-ratelimit-remaining-tokens", "500"));
+        let limiter = RateLimiter::new(RateLimits::new(1000, 1_000_000));
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock.clone()).with_rate_limiter(limiter);
+
+        let start = Instant::now();
+        sdk.chat_completion(chat_request()).await?;
+        assert!(start.elapsed() >= Duration::from_secs(2));
+        assert_eq!(mock.requests().len(), 2);
+
+        // 服务端返回剩余请求数为 0，下一个请求等待到重置时间之后才发送
+        let limiter = sdk.rate_limiter.as_ref().unwrap();
+        let (_, tokens) = limiter.remaining("gpt-3.5-turbo-1106");
+        assert!(tokens <= 500 + 110 - 12 + 1);
+        let start = Instant::now();
+        sdk.chat_completion(chat_request()).await?;
+        assert!(start.elapsed() >= Duration::from_secs(5));
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limiter_should_return_unused_reservations() -> Result<()>{
+        let chunk = |usage: serde_json::Value| json!({"id": "chatcmpl-1", "object": "chat.completion.chunk", "created": 1,
+            "model": "gpt-3.5-turbo-1106", "choices": [], "usage": usage});
+        let mock = MockTransport::new()
+            .once(Method::POST, "chat/completions", MockResponse::error(StatusCode::BAD_REQUEST, "bad request"))
+            .once(Method::POST, "chat/completions", MockResponse::sse([chunk(json!({"prompt_tokens": 10, "completion_tokens": 2, "total_tokens": 12}))]))
+            .on(Method::POST, "chat/completions", MockResponse::sse([chunk(serde_json::Value::Null)]));
+        // 每分钟请求数为 0 表示不限制
+        let limiter = RateLimiter::new(RateLimits::new(0, 1000));
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock.clone()).with_rate_limiter(limiter);
+        let limiter = sdk.rate_limiter.as_ref().unwrap();
+        let remaining = || limiter.remaining("gpt-3.5-turbo-1106").1;
+
+        // 失败的请求退回全部预留
+        assert!(sdk.chat_completion(chat_request()).await.is_err());
+        assert_eq!(remaining(), 1000);
+
+        // 流式响应按照用量数据块修正，没有用量时只保留输入部分的估算
+        let mut stream = sdk.chat_completion_stream(chat_request()).await?;
+        while stream.next().await.transpose()?.is_some() {}
+        assert_eq!(remaining(), 1000 - 12);
+        drop(sdk.chat_completion_stream(chat_request()).await?);
+        assert_eq!(remaining(), 1000 - 12 - 10);
+
+        let start = Instant::now();
+        for _ in 0..5 {
+            drop(sdk.chat_completion_stream(chat_request()).await?);
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        Ok(())
+    }
+}
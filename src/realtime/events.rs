@@ -0,0 +1,373 @@
+use serde::{Serialize, Deserialize};
+
+use crate::api::{Tool, ToolCall, CallFunction, ToolType};
+
+// Realtime API 的客户端事件与服务端事件
+// 所有事件都是带有 `type` 字段的 Json 文本帧
+
+
+/// 客户端发送给服务端的事件
+#[derive(Debug,Clone,Serialize)]
+#[serde(tag = "type")]
+pub enum ClientEvent{
+    /// 更新会话配置
+    #[serde(rename = "session.update")]
+    SessionUpdate{
+        session: Box<SessionConfig>,
+    },
+    /// 向输入音频缓冲区追加音频(base64 编码)
+    #[serde(rename = "input_audio_buffer.append")]
+    InputAudioBufferAppend{
+        audio: String,
+    },
+    /// 提交输入音频缓冲区，生成一条用户消息
+    #[serde(rename = "input_audio_buffer.commit")]
+    InputAudioBufferCommit,
+    /// 清空输入音频缓冲区
+    #[serde(rename = "input_audio_buffer.clear")]
+    InputAudioBufferClear,
+    /// 向会话中添加一个对话项，例如文本消息或者函数调用的输出
+    #[serde(rename = "conversation.item.create")]
+    ConversationItemCreate{
+        item: ConversationItem,
+    },
+    /// 请求模型生成回复
+    #[serde(rename = "response.create")]
+    ResponseCreate{
+        #[serde(skip_serializing_if = "Option::is_none")]
+        response: Option<ResponseConfig>,
+    },
+    /// 取消正在生成的回复
+    #[serde(rename = "response.cancel")]
+    ResponseCancel,
+}
+
+impl ClientEvent {
+    /// 更新会话配置
+    pub fn update_session(session: SessionConfig) -> Self {
+        ClientEvent::SessionUpdate { session: Box::new(session) }
+    }
+
+    /// 追加一段音频，`audio` 为 base64 编码后的音频数据
+    pub fn append_audio(audio: impl Into<String>) -> Self {
+        ClientEvent::InputAudioBufferAppend { audio: audio.into() }
+    }
+
+    /// 请求模型按照会话配置生成回复
+    pub fn create_response() -> Self {
+        ClientEvent::ResponseCreate { response: None }
+    }
+}
+
+
+/// 会话配置，未设置的字段保持服务端当前的值
+#[derive(Debug,Clone,Default,Serialize,Deserialize)]
+pub struct SessionConfig{
+    /// 输出模态，例如 ["text", "audio"]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modalities: Vec<String>,
+    /// 系统指令
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    /// 输出语音的音色，例如 alloy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voice: Option<String>,
+    /// 输入音频格式: pcm16、g711_ulaw 或 g711_alaw
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_audio_format: Option<String>,
+    /// 输出音频格式: pcm16、g711_ulaw 或 g711_alaw
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_audio_format: Option<String>,
+    /// 输入音频的转写配置
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_audio_transcription: Option<serde_json::Value>,
+    /// 语音活动检测配置，设置为 null 时关闭
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turn_detection: Option<serde_json::Value>,
+    /// 模型可以调用的函数列表
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<RealtimeTool>,
+    /// 工具选择: auto、none、required 或者指定函数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<serde_json::Value>,
+    /// 采样温度，取值 0.6~1.2
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// 单次回复最多生成的令牌数，或者 "inf"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_response_output_tokens: Option<serde_json::Value>,
+}
+
+/// 单次回复的配置，会覆盖会话配置
+#[derive(Debug,Clone,Default,Serialize)]
+pub struct ResponseConfig{
+    /// 输出模态
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub modalities: Vec<String>,
+    /// 本次回复使用的指令
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    /// 本次回复可以调用的函数列表
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<RealtimeTool>,
+}
+
+/// Realtime 会话中的函数定义
+/// 与聊天接口不同，函数信息直接位于工具对象上，而不是嵌套在 `function` 字段中
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct RealtimeTool{
+    /// 工具类型，目前仅支持 function
+    pub r#type: ToolType,
+    /// 函数名称
+    pub name: String,
+    /// 函数功能描述
+    #[serde(default)]
+    pub description: String,
+    /// 函数参数的 Json Schema
+    #[serde(default)]
+    pub parameters: serde_json::Value,
+}
+
+// 聊天接口的函数工具可以直接作为 Realtime 会话的工具
+impl From<Tool> for RealtimeTool {
+    fn from(tool: Tool) -> Self {
+        let function = tool.function_info();
+        Self {
+            r#type: ToolType::Function,
+            name: function.name().into(),
+            description: function.description().into(),
+            parameters: function.parameters().clone(),
+        }
+    }
+}
+
+
+/// 会话中的对话项
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ConversationItem{
+    /// 消息
+    Message{
+        /// 角色: user、assistant 或 system
+        role: String,
+        /// 消息内容
+        content: Vec<ContentPart>,
+    },
+    /// 模型发起的函数调用
+    FunctionCall{
+        call_id: String,
+        name: String,
+        arguments: String,
+    },
+    /// 函数调用的输出
+    FunctionCallOutput{
+        call_id: String,
+        output: String,
+    },
+}
+
+impl ConversationItem {
+    /// 用户文本消息
+    pub fn user_text(text: impl Into<String>) -> Self {
+        ConversationItem::Message { role: "user".into(), content: vec![ContentPart::InputText { text: text.into() }] }
+    }
+
+    /// 对指定工具调用的输出
+    pub fn function_output(call: &ToolCall, output: impl Into<String>) -> Self {
+        ConversationItem::FunctionCallOutput { call_id: call.id.clone(), output: output.into() }
+    }
+}
+
+/// 消息内容片段
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart{
+    /// 用户输入的文本
+    InputText{ text: String },
+    /// 用户输入的音频(base64)
+    InputAudio{
+        #[serde(default)]
+        audio: Option<String>,
+        #[serde(default)]
+        transcript: Option<String>,
+    },
+    /// 模型输出的文本
+    Text{ text: String },
+    /// 模型输出的音频
+    Audio{
+        #[serde(default)]
+        audio: Option<String>,
+        #[serde(default)]
+        transcript: Option<String>,
+    },
+}
+
+
+/// 服务端发送给客户端的事件
+#[derive(Debug,Clone,Deserialize)]
+#[serde(tag = "type")]
+pub enum ServerEvent{
+    /// 错误
+    #[serde(rename = "error")]
+    Error{
+        error: RealtimeError,
+    },
+    /// 会话已创建
+    #[serde(rename = "session.created")]
+    SessionCreated{
+        session: serde_json::Value,
+    },
+    /// 会话配置已更新
+    #[serde(rename = "session.updated")]
+    SessionUpdated{
+        session: serde_json::Value,
+    },
+    /// 对话项已创建
+    #[serde(rename = "conversation.item.created")]
+    ConversationItemCreated{
+        item: serde_json::Value,
+    },
+    /// 检测到用户开始说话
+    #[serde(rename = "input_audio_buffer.speech_started")]
+    SpeechStarted{
+        item_id: String,
+    },
+    /// 检测到用户停止说话
+    #[serde(rename = "input_audio_buffer.speech_stopped")]
+    SpeechStopped{
+        item_id: String,
+    },
+    /// 输入音频缓冲区已提交
+    #[serde(rename = "input_audio_buffer.committed")]
+    InputAudioBufferCommitted{
+        item_id: String,
+    },
+    /// 开始生成回复
+    #[serde(rename = "response.created")]
+    ResponseCreated{
+        response: serde_json::Value,
+    },
+    /// 回复生成结束
+    #[serde(rename = "response.done")]
+    ResponseDone{
+        response: serde_json::Value,
+    },
+    /// 文本增量
+    #[serde(rename = "response.text.delta")]
+    TextDelta{
+        response_id: String,
+        item_id: String,
+        delta: String,
+    },
+    /// 音频增量(base64)
+    #[serde(rename = "response.audio.delta")]
+    AudioDelta{
+        response_id: String,
+        item_id: String,
+        delta: String,
+    },
+    /// 音频转写文本增量
+    #[serde(rename = "response.audio_transcript.delta")]
+    AudioTranscriptDelta{
+        response_id: String,
+        item_id: String,
+        delta: String,
+    },
+    /// 函数调用参数增量
+    #[serde(rename = "response.function_call_arguments.delta")]
+    FunctionCallArgumentsDelta{
+        response_id: String,
+        call_id: String,
+        delta: String,
+    },
+    /// 函数调用参数生成完毕
+    #[serde(rename = "response.function_call_arguments.done")]
+    FunctionCallArgumentsDone{
+        response_id: String,
+        call_id: String,
+        name: String,
+        arguments: String,
+    },
+    /// 限流信息更新
+    #[serde(rename = "rate_limits.updated")]
+    RateLimitsUpdated{
+        rate_limits: Vec<serde_json::Value>,
+    },
+    /// 未建模的事件，保留原始 Json
+    #[serde(skip)]
+    Unknown(serde_json::Value),
+}
+
+impl ServerEvent {
+
+    /// 解析服务端事件，未知类型的事件解析为 `ServerEvent::Unknown`
+    pub fn parse(text: &str) -> serde_json::Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(text)?;
+        Ok(serde_json::from_value(value.clone()).unwrap_or(ServerEvent::Unknown(value)))
+    }
+
+    /// 如果事件是完整的函数调用，转换为聊天接口的 `ToolCall`
+    pub fn tool_call(&self) -> Option<ToolCall> {
+        match self {
+            ServerEvent::FunctionCallArgumentsDone { call_id, name, arguments, .. } => Some(ToolCall {
+                id: call_id.clone(),
+                r#type: ToolType::Function,
+                function: CallFunction { name: name.clone(), arguments: arguments.clone() },
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Realtime 会话中的错误
+#[derive(Debug,Clone,Deserialize)]
+pub struct RealtimeError{
+    /// 错误类型
+    pub r#type: String,
+    /// 错误码
+    #[serde(default)]
+    pub code: Option<String>,
+    /// 错误信息
+    pub message: String,
+    /// 导致错误的客户端事件ID
+    #[serde(default)]
+    pub event_id: Option<String>,
+}
+
+
+#[cfg(test)]
+mod tests{
+    use serde_json::json;
+    use super::*;
+
+    #[test]
+    fn client_events_should_serialize(){
+        let tool = Tool::function("get_weather", "Get the weather", json!({"type": "object"}));
+        let event = ClientEvent::update_session(
+            SessionConfig { instructions: Some("Be brief.".into()), tools: vec![tool.into()], ..Default::default() },
+        );
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({
+                "type": "session.update",
+                "session": {
+                    "instructions": "Be brief.",
+                    "tools": [{"type": "function", "name": "get_weather", "description": "Get the weather", "parameters": {"type": "object"}}]
+                }
+            })
+        );
+        assert_eq!(serde_json::to_value(ClientEvent::append_audio("AAA=")).unwrap(), json!({"type": "input_audio_buffer.append", "audio": "AAA="}));
+        assert_eq!(serde_json::to_value(ClientEvent::create_response()).unwrap(), json!({"type": "response.create"}));
+    }
+
+    #[test]
+    fn server_events_should_parse(){
+        let event = ServerEvent::parse(r#"{"type":"response.function_call_arguments.done","event_id":"e1","response_id":"resp_1","item_id":"item_1","output_index":0,"call_id":"call_1","name":"get_weather","arguments":"{}"}"#).unwrap();
+        let call = event.tool_call().unwrap();
+        assert_eq!(call.id, "call_1");
+        assert_eq!(call.function.name, "get_weather");
+
+        let event = ServerEvent::parse(r#"{"type":"response.output_item.added","item":{}}"#).unwrap();
+        assert!(matches!(event, ServerEvent::Unknown(v) if v["type"] == "response.output_item.added"));
+    }
+}
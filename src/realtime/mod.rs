@@ -0,0 +1,266 @@
+//!
+//! Realtime API 客户端
+//! 通过 WebSocket 与服务端建立会话，发送类型化的客户端事件，并以 `Stream` 的方式接收服务端事件。
+//! 需要开启 `realtime` 特性。
+//!
+
+use std::{pin::Pin, task::{Context, Poll}};
+
+use anyhow::{Result, bail};
+use futures::{Sink, SinkExt, Stream, StreamExt, stream::{SplitSink, SplitStream}};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream, connect_async,
+    tungstenite::{Message, client::IntoClientRequest, http::HeaderValue},
+};
+
+use crate::OpenaiSdk;
+
+mod events;
+pub use events::*;
+
+/// Realtime 接口的 beta 请求头
+const REALTIME_BETA: &str = "realtime=v1";
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+
+///
+/// Realtime 会话
+/// 实现了 `Stream<Item = Result<ServerEvent>>`，可以使用 `split` 将发送端与接收端拆分到不同的任务中
+///
+pub struct RealtimeSession{
+    ws: WsStream,
+}
+
+impl RealtimeSession {
+
+    ///
+    /// 连接到指定的 WebSocket 地址，`token` 不为空时使用 Bearer 认证
+    ///
+    pub async fn connect(url: &str, token: Option<&str>) -> Result<Self>{
+        let mut req = url.into_client_request()?;
+        let headers = req.headers_mut();
+        if let Some(token) = token.filter(|t| !t.is_empty()) {
+            let mut value = HeaderValue::from_str(&format!("Bearer {token}"))?;
+            value.set_sensitive(true);
+            headers.insert("Authorization", value);
+        }
+        headers.insert("OpenAI-Beta", HeaderValue::from_static(REALTIME_BETA));
+        let (ws, _) = connect_async(req).await?;
+        Ok(Self { ws })
+    }
+
+    ///
+    /// 发送一个客户端事件
+    ///
+    pub async fn send(&mut self, event: ClientEvent) -> Result<()>{
+        self.ws.send(encode(&event)?).await?;
+        Ok(())
+    }
+
+    ///
+    /// 关闭会话
+    ///
+    pub async fn close(mut self) -> Result<()>{
+        self.ws.close(None).await?;
+        Ok(())
+    }
+
+    ///
+    /// 拆分为发送端与接收端
+    ///
+    pub fn split(self) -> (RealtimeSender, RealtimeReceiver){
+        let (sink, stream) = self.ws.split();
+        (RealtimeSender { sink }, RealtimeReceiver { stream })
+    }
+}
+
+impl Stream for RealtimeSession {
+    type Item = Result<ServerEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        poll_event(&mut self.ws, cx)
+    }
+}
+
+
+/// 会话的发送端
+pub struct RealtimeSender{
+    sink: SplitSink<WsStream, Message>,
+}
+
+impl RealtimeSender {
+    /// 发送一个客户端事件
+    pub async fn send(&mut self, event: ClientEvent) -> Result<()>{
+        self.sink.send(encode(&event)?).await?;
+        Ok(())
+    }
+
+    /// 关闭会话
+    pub async fn close(mut self) -> Result<()>{
+        self.sink.close().await?;
+        Ok(())
+    }
+}
+
+impl Sink<ClientEvent> for RealtimeSender {
+    type Error = anyhow::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.sink.poll_ready_unpin(cx).map_err(Into::into)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: ClientEvent) -> Result<()> {
+        self.sink.start_send_unpin(encode(&item)?).map_err(Into::into)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.sink.poll_flush_unpin(cx).map_err(Into::into)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.sink.poll_close_unpin(cx).map_err(Into::into)
+    }
+}
+
+/// 会话的接收端
+pub struct RealtimeReceiver{
+    stream: SplitStream<WsStream>,
+}
+
+impl Stream for RealtimeReceiver {
+    type Item = Result<ServerEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        poll_event(&mut self.stream, cx)
+    }
+}
+
+
+/// 将客户端事件编码为文本帧
+fn encode(event: &ClientEvent) -> Result<Message> {
+    Ok(Message::Text(serde_json::to_string(event)?))
+}
+
+/// 读取下一个服务端事件，忽略 ping/pong 等控制帧，收到关闭帧时结束
+fn poll_event<S>(stream: &mut S, cx: &mut Context<'_>) -> Poll<Option<Result<ServerEvent>>>
+where
+    S: Stream<Item = std::result::Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    loop {
+        return match stream.poll_next_unpin(cx) {
+            Poll::Ready(Some(Ok(Message::Text(text)))) => Poll::Ready(Some(ServerEvent::parse(&text).map_err(Into::into))),
+            Poll::Ready(Some(Ok(Message::Binary(data)))) => {
+                let text = String::from_utf8_lossy(&data);
+                Poll::Ready(Some(ServerEvent::parse(&text).map_err(Into::into)))
+            }
+            Poll::Ready(Some(Ok(Message::Close(_)))) | Poll::Ready(None) => Poll::Ready(None),
+            Poll::Ready(Some(Ok(_))) => continue,
+            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(e.into()))),
+            Poll::Pending => Poll::Pending,
+        };
+    }
+}
+
+
+// Realtime 相关的 api 请求
+impl OpenaiSdk {
+
+    ///
+    /// 使用指定模型建立 Realtime 会话
+    /// 地址由基础地址转换而来，例如 `https://api.openai.com/v1` 对应 `wss://api.openai.com/v1/realtime?model=...`
+    ///
+    pub async fn realtime(&self, model: &str) -> Result<RealtimeSession>{
+        if self.azure.is_some() {
+            bail!("realtime sessions are not supported in azure mode, use RealtimeSession::connect");
+        }
+        let base = if let Some(rest) = self.base_url.strip_prefix("https://") {
+            format!("wss://{rest}")
+        } else if let Some(rest) = self.base_url.strip_prefix("http://") {
+            format!("ws://{rest}")
+        } else {
+            self.base_url.clone()
+        };
+        let mut url = reqwest::Url::parse(&format!("{base}/realtime"))?;
+        url.query_pairs_mut().append_pair("model", model);
+        let token = match &self.credentials {
+            Some(credentials) => Some(credentials.token().await?),
+            None => None,
+        };
+        RealtimeSession::connect(url.as_str(), token.as_ref().map(|t| t.expose_secret())).await
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use serde_json::json;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::accept_hdr_async;
+    use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+    use crate::api::Tool;
+    use super::*;
+
+    /// 本地回显服务: 校验握手请求头，收到 session.update 时回复 session.updated，收到 response.create 时回复一次函数调用
+    async fn echo_server() -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            #[allow(clippy::result_large_err)]
+            let callback = |req: &Request, res: Response| {
+                assert_eq!(req.uri().path(), "/v1/realtime");
+                assert_eq!(req.uri().query(), Some("model=gpt-4o-realtime-preview"));
+                assert_eq!(req.headers()["Authorization"], "Bearer sk-test");
+                assert_eq!(req.headers()["OpenAI-Beta"], "realtime=v1");
+                Ok(res)
+            };
+            let mut ws = accept_hdr_async(stream, callback).await.unwrap();
+            while let Some(Ok(Message::Text(text))) = ws.next().await {
+                let event: serde_json::Value = serde_json::from_str(&text).unwrap();
+                let reply = match event["type"].as_str().unwrap() {
+                    "session.update" => json!({"type": "session.updated", "event_id": "evt_1", "session": event["session"]}),
+                    "response.create" => json!({
+                        "type": "response.function_call_arguments.done",
+                        "event_id": "evt_2",
+                        "response_id": "resp_1",
+                        "item_id": "item_1",
+                        "output_index": 0,
+                        "call_id": "call_1",
+                        "name": "get_weather",
+                        "arguments": "{\"city\":\"Paris\"}"
+                    }),
+                    _ => json!({"type": "error", "error": {"type": "invalid_request_error", "message": "unexpected event"}}),
+                };
+                ws.send(Message::Text(reply.to_string())).await.unwrap();
+            }
+        });
+        Ok(format!("http://{addr}/v1"))
+    }
+
+    #[tokio::test]
+    async fn realtime_session_should_exchange_typed_events() -> Result<()>{
+        let base_url = echo_server().await?;
+        let sdk = OpenaiSdk::new("sk-test".into()).with_base_url(base_url);
+        let mut session = sdk.realtime("gpt-4o-realtime-preview").await?;
+
+        let tool = Tool::function("get_weather", "Get the weather", json!({"type": "object"}));
+        let session_config = SessionConfig { tools: vec![tool.into()], ..Default::default() };
+        session.send(ClientEvent::update_session(session_config)).await?;
+        let Some(Ok(ServerEvent::SessionUpdated { session: updated })) = session.next().await else {
+            panic!("expected session.updated");
+        };
+        assert_eq!(updated["tools"][0]["name"], "get_weather");
+
+        let (mut sender, mut receiver) = session.split();
+        sender.send(ClientEvent::create_response()).await?;
+        let event = receiver.next().await.unwrap()?;
+        let call = event.tool_call().unwrap();
+        assert_eq!(call.function.arguments, "{\"city\":\"Paris\"}");
+
+        sender.send(ClientEvent::ConversationItemCreate { item: ConversationItem::function_output(&call, "sunny") }).await?;
+        assert!(matches!(receiver.next().await.unwrap()?, ServerEvent::Error { .. }));
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+//!
+//! 多服务商故障转移路由
+//! 在多个 `OpenaiSdk` 后端(例如 OpenAI、Azure OpenAI、自建的兼容服务)之间按优先级路由请求:
+//! 后端返回 429、5xx 或者网络错误时自动切换到下一个后端，连续失败达到阈值时由 `CircuitBreaker` 熔断一段时间;
+//! 可以定期执行健康检查，并为每个后端配置模型名称的映射。对调用方而言与直接使用 `OpenaiSdk` 一致。
+//!
+
+use std::{collections::HashMap, future::Future, sync::atomic::{AtomicBool, Ordering}, time::Duration};
+
+use anyhow::{Result, anyhow};
+use reqwest::StatusCode;
+use crate::OpenaiSdk;
+use crate::api::{ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse, Model};
+use crate::circuit_breaker::{CircuitBreaker, CircuitState};
+use crate::error::{ApiError, CircuitOpen};
+use crate::stream::EventStream;
+
+/// 默认的熔断阈值(连续失败次数)
+const DEFAULT_FAILURE_THRESHOLD: usize = 3;
+
+
+///
+/// 路由的后端
+///
+#[derive(Debug)]
+pub struct Backend{
+    /// 后端名称，用于查看状态
+    name: String,
+    sdk: OpenaiSdk,
+    /// 优先级，数值越小越优先，相同优先级按添加顺序
+    priority: u32,
+    /// 请求中的模型ID到该后端模型的映射
+    models: HashMap<String, Model>,
+}
+
+impl Backend {
+    pub fn new(name: impl Into<String>, sdk: OpenaiSdk) -> Self {
+        Self { name: name.into(), sdk, priority: 0, models: HashMap::new() }
+    }
+
+    /// 设置优先级，数值越小越优先，默认为 0
+    pub fn priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// 将请求中的模型映射为该后端的模型，例如自建服务上将 `gpt-4o` 映射为 `llama3`
+    pub fn model(mut self, from: Model, to: Model) -> Self {
+        self.models.insert(from.as_str().to_string(), to);
+        self
+    }
+}
+
+/// 后端的当前状态
+#[derive(Debug,Clone,PartialEq, Eq)]
+pub struct BackendStatus{
+    /// 后端名称
+    pub name: String,
+    /// 优先级
+    pub priority: u32,
+    /// 最近一次健康检查或者请求是否成功
+    pub healthy: bool,
+    /// 是否处于熔断状态(包括半开状态)
+    pub open: bool,
+    /// 熔断器的状态
+    pub circuit: CircuitState,
+    /// 连续失败的次数
+    pub consecutive_failures: u32,
+}
+
+
+///
+/// 多后端路由
+///
+#[derive(Debug)]
+pub struct Router{
+    /// 按优先级排序的后端，以及最近一次健康检查的结果
+    backends: Vec<(Backend, AtomicBool)>,
+    /// 按后端名称熔断
+    breaker: CircuitBreaker,
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Router {
+    pub fn new() -> Self {
+        let breaker = CircuitBreaker::new()
+            .failure_rate(1.0)
+            .window_size(DEFAULT_FAILURE_THRESHOLD)
+            .minimum_requests(DEFAULT_FAILURE_THRESHOLD);
+        Self { backends: Vec::new(), breaker }
+    }
+
+    /// 添加后端
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backends.push((backend, AtomicBool::new(true)));
+        // 稳定排序，相同优先级保持添加顺序
+        self.backends.sort_by_key(|(backend, _)| backend.priority);
+        self
+    }
+
+    /// 设置熔断阈值(连续失败次数)，默认为 3
+    pub fn failure_threshold(mut self, threshold: usize) -> Self {
+        self.breaker = std::mem::take(&mut self.breaker).window_size(threshold).minimum_requests(threshold);
+        self
+    }
+
+    /// 设置熔断的时间，默认为 30 秒
+    pub fn open_duration(mut self, duration: Duration) -> Self {
+        self.breaker = std::mem::take(&mut self.breaker).open_duration(duration);
+        self
+    }
+
+    /// 使用自定义的熔断器，例如按失败率熔断，替代 `failure_threshold` 与 `open_duration`
+    pub fn circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = breaker;
+        self
+    }
+
+    /// 各个后端的当前状态，按优先级排序
+    pub fn status(&self) -> Vec<BackendStatus> {
+        self.backends.iter().map(|(backend, healthy)| {
+            let stats = self.breaker.stats(&backend.name);
+            let circuit = stats.as_ref().map_or(CircuitState::Closed, |s| s.state);
+            BackendStatus {
+                name: backend.name.clone(),
+                priority: backend.priority,
+                healthy: healthy.load(Ordering::Relaxed),
+                open: circuit != CircuitState::Closed,
+                circuit,
+                consecutive_failures: stats.map_or(0, |s| s.consecutive_failures),
+            }
+        }).collect()
+    }
+
+    ///
+    /// 对所有后端执行一次健康检查(获取模型列表)，检查失败的后端在再次检查成功之前不接收请求
+    ///
+    pub async fn health_check(&self) {
+        for (backend, healthy) in &self.backends {
+            healthy.store(backend.sdk.list_models().await.is_ok(), Ordering::Relaxed);
+        }
+    }
+
+    ///
+    /// 每隔 `interval` 执行一次健康检查，不会结束，通常在单独的任务中运行:
+    /// `tokio::spawn(async move { router.run_health_checks(interval).await })`
+    ///
+    pub async fn run_health_checks(&self, interval: Duration) {
+        loop {
+            self.health_check().await;
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    ///
+    /// 文字聊天，按优先级依次尝试可用的后端
+    ///
+    pub async fn chat_completion(&self, req: ChatCompletionRequest) -> Result<ChatCompletionResponse> {
+        self.route(req, |sdk, req| async move { sdk.chat_completion(req).await }).await
+    }
+
+    ///
+    /// 文字聊天，以流式方式返回;只在建立连接阶段切换后端，开始返回数据后不再切换
+    ///
+    pub async fn chat_completion_stream(&self, req: ChatCompletionRequest) -> Result<EventStream<ChatCompletionChunk>> {
+        self.route(req, |sdk, req| async move { sdk.chat_completion_stream(req).await }).await
+    }
+
+    /// 依次尝试可用的后端，返回第一个成功的结果;请求本身有误(例如 400)时直接返回错误
+    async fn route<'a, T, F, Fut>(&'a self, req: ChatCompletionRequest, call: F) -> Result<T>
+    where
+        F: Fn(&'a OpenaiSdk, ChatCompletionRequest) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut last_error = None;
+        for (backend, healthy) in &self.backends {
+            if !healthy.load(Ordering::Relaxed) {
+                continue;
+            }
+            // 熔断中的后端直接跳过
+            let Ok(permit) = self.breaker.acquire(&backend.name) else { continue };
+            let mut req = req.clone();
+            if let Some(model) = backend.models.get(req.model().as_str()) {
+                *req.model_mut() = model.clone();
+            }
+            match call(&backend.sdk, req).await {
+                Ok(res) => {
+                    permit.record(false);
+                    healthy.store(true, Ordering::Relaxed);
+                    return Ok(res);
+                }
+                Err(e) if should_fail_over(&e) => {
+                    permit.record(true);
+                    last_error = Some(e.context(format!("backend {} failed", backend.name)));
+                }
+                Err(e) => {
+                    // 请求本身有误，后端是正常的
+                    permit.record(false);
+                    return Err(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow!("no backend available in the router")))
+    }
+}
+
+/// 是否切换到下一个后端: 限流、服务端错误、网络错误以及后端自身的熔断
+fn should_fail_over(err: &anyhow::Error) -> bool {
+    if err.is::<CircuitOpen>() {
+        return true;
+    }
+    if let Some(err) = err.downcast_ref::<ApiError>() {
+        return err.status == StatusCode::TOO_MANY_REQUESTS
+            || err.status == StatusCode::REQUEST_TIMEOUT
+            || err.status.is_server_error();
+    }
+    err.downcast_ref::<reqwest::Error>().is_some()
+}
+
+
+#[cfg(test)]
+mod tests{
+    use reqwest::Method;
+    use serde_json::json;
+    use crate::api::*;
+    use crate::transport::{MockTransport, MockResponse};
+    use super::*;
+
+    fn completion(model: &str) -> MockResponse {
+        MockResponse::json(json!({
+            "id": "chatcmpl-1", "object": "chat.completion", "created": 1, "model": model,
+            "choices": [{"index": 0, "message": {"role": "assistant", "content": "ok"}, "finish_reason": "stop"}]
+        }))
+    }
+
+    fn request() -> Result<ChatCompletionRequest> {
+        Ok(ChatCompletionRequestBuilder::default()
+            .model(Model::Other("gpt-4o".into()))
+            .messages(vec![ChatMessage::new_user("hi", "")])
+            .build()?)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn router_should_fail_over_and_open_circuit() -> Result<()>{
+        let primary = MockTransport::new()
+            .on(Method::POST, "chat/completions", MockResponse::error(StatusCode::SERVICE_UNAVAILABLE, "outage"));
+        let local = MockTransport::new().on(Method::POST, "chat/completions", completion("llama3"));
+        let router = Router::new()
+            .failure_threshold(2)
+            .backend(Backend::new("local", OpenaiSdk::new(String::new()).with_transport(local.clone()))
+                .priority(1)
+                .model(Model::Other("gpt-4o".into()), Model::Other("llama3".into())))
+            .backend(Backend::new("openai", OpenaiSdk::new("sk-test".into()).with_transport(primary.clone())));
+
+        for _ in 0..3 {
+            assert_eq!(router.chat_completion(request()?).await?.model.as_str(), "llama3");
+        }
+        // 主后端连续失败 2 次后熔断，第三次请求直接发送到备用后端
+        assert_eq!(primary.requests().len(), 2);
+        assert_eq!(local.requests()[0].json()?["model"], "llama3");
+        let status = router.status();
+        assert_eq!((status[0].name.as_str(), status[0].open, status[0].consecutive_failures), ("openai", true, 2));
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        router.chat_completion(request()?).await?;
+        assert_eq!(primary.requests().len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn router_should_skip_unhealthy_and_not_retry_bad_requests() -> Result<()>{
+        let down = MockTransport::new();
+        let up = MockTransport::new()
+            .on(Method::GET, "models", MockResponse::json(json!({"object": "list", "data": []})))
+            .once(Method::POST, "chat/completions", MockResponse::error(StatusCode::BAD_REQUEST, "invalid"))
+            .on(Method::POST, "chat/completions", completion("gpt-4o"));
+        let router = Router::new()
+            .backend(Backend::new("down", OpenaiSdk::new(String::new()).with_transport(down.clone())))
+            .backend(Backend::new("up", OpenaiSdk::new(String::new()).with_transport(up)).priority(1));
+        router.health_check().await;
+        assert!(!router.status()[0].healthy);
+
+        let err = router.chat_completion(request()?).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<ApiError>().unwrap().status, StatusCode::BAD_REQUEST);
+        router.chat_completion(request()?).await?;
+        assert_eq!(down.requests().len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,265 @@
+//!
+//! 语义缓存
+//! 将对话中最后一条用户消息转换为嵌入向量，保存在本地的向量索引中;之后的请求与已缓存的问题余弦相似度达到阈值时，
+//! 直接返回缓存的 `ChatCompletionResponse`，适用于大量换一种说法提出相同问题的场景(例如常见问题机器人)。
+//! 只有模型与系统提示词都相同的请求之间才会匹配。通过 `OpenaiSdk::with_semantic_cache` 开启，
+//! 请求的 `bypass_cache` 同样会跳过语义缓存;嵌入接口调用失败时直接发送请求，不使用语义缓存。
+//!
+
+use std::{collections::VecDeque, sync::Mutex};
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::OpenaiSdk;
+use crate::api::{ChatCompletionRequest, ChatCompletionResponse, ChatMessage, EmbeddingModel, EmbeddingRequestBuilder};
+
+///
+/// 语义缓存，按照写入顺序保存，超出容量时淘汰最早的条目
+///
+#[derive(Debug)]
+pub struct SemanticCache{
+    /// 命中缓存所需的最小余弦相似度
+    threshold: f32,
+    /// 计算嵌入向量使用的模型
+    embedding_model: EmbeddingModel,
+    /// 最多保存的条目数
+    capacity: usize,
+    entries: Mutex<VecDeque<SemanticEntry>>,
+}
+
+/// 向量索引中的一个条目
+#[derive(Debug)]
+struct SemanticEntry{
+    /// 模型与系统提示词
+    scope: String,
+    /// 归一化后的嵌入向量
+    vector: Vec<f32>,
+    /// 原始响应体，每次命中时重新反序列化并交给中间件处理
+    body: Value,
+}
+
+impl SemanticCache {
+
+    ///
+    /// 创建语义缓存，`threshold` 为命中缓存所需的最小余弦相似度(-1.0~1.0)，通常取 0.9 以上
+    ///
+    pub fn new(threshold: f32) -> Self {
+        Self { threshold, embedding_model: EmbeddingModel::default(), capacity: 1000, entries: Mutex::new(VecDeque::new()) }
+    }
+
+    /// 设置计算嵌入向量使用的模型，默认为 text-embedding-3-small
+    pub fn embedding_model(mut self, model: EmbeddingModel) -> Self {
+        self.embedding_model = model;
+        self
+    }
+
+    /// 设置最多保存的条目数，默认为 1000
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// 当前保存的条目数
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 清空缓存
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    ///
+    /// 先经过请求中间件，再查询语义缓存，未命中时通过 SDK 发送请求(仍然经过精确匹配的响应缓存)，并且将响应写入语义缓存;
+    /// 没有用户消息的请求直接发送，返回的响应只经过一次中间件的 `on_data`
+    ///
+    pub(crate) async fn complete(&self, sdk: &OpenaiSdk, mut req: ChatCompletionRequest) -> Result<ChatCompletionResponse> {
+        // 问题与匹配范围取自中间件处理后的请求，例如脱敏后的消息
+        if let Some(res) = sdk.on_request(&mut req)? {
+            return sdk.read_json(res).await;
+        }
+        let Some(question) = last_user_message(&req) else {
+            return sdk.on_data(sdk.send_raw_cached(req, false, None).await?.0);
+        };
+        let scope = scope(&req);
+        let Ok(vector) = self.embed(sdk, question).await else {
+            return sdk.on_data(sdk.send_raw_cached(req, false, None).await?.0);
+        };
+        if let Some(body) = self.search(&scope, &vector) {
+            return sdk.on_data(serde_json::from_value(body)?);
+        }
+        let (response, body) = sdk.send_raw_cached(req, false, None).await?;
+        self.insert(SemanticEntry { scope, vector, body });
+        sdk.on_data(response)
+    }
+
+    /// 计算问题的归一化嵌入向量
+    async fn embed(&self, sdk: &OpenaiSdk, question: String) -> Result<Vec<f32>> {
+        let embedding = EmbeddingRequestBuilder::default()
+            .input(question)
+            .model(self.embedding_model.clone())
+            .build()?;
+        Ok(sdk.create_embedding(embedding).await?.data.pop()
+            .map(|e| normalize(e.embedding))
+            .unwrap_or_default())
+    }
+
+    /// 在相同范围的条目中查找相似度最高且达到阈值的响应体
+    fn search(&self, scope: &str, vector: &[f32]) -> Option<Value> {
+        let entries = self.entries.lock().unwrap();
+        entries.iter()
+            .filter(|entry| entry.scope == scope)
+            .map(|entry| (dot(&entry.vector, vector), entry))
+            .filter(|(similarity, _)| *similarity >= self.threshold)
+            .max_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, entry)| entry.body.clone())
+    }
+
+    fn insert(&self, entry: SemanticEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        while !entries.is_empty() && entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        if self.capacity > 0 {
+            entries.push_back(entry);
+        }
+    }
+}
+
+/// 最后一条用户消息的内容
+fn last_user_message(req: &ChatCompletionRequest) -> Option<String> {
+    req.messages().iter().rev().find_map(|message| match message {
+        ChatMessage::User(user) => Some(user.content.clone()),
+        _ => None,
+    })
+}
+
+/// 缓存的匹配范围: 模型与所有系统提示词
+fn scope(req: &ChatCompletionRequest) -> String {
+    let mut scope = req.model().as_str().to_string();
+    for message in req.messages() {
+        if let ChatMessage::System(system) = message {
+            scope.push('\n');
+            scope.push_str(&system.content);
+        }
+    }
+    scope
+}
+
+/// 归一化向量，之后的余弦相似度即为点积
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = dot(&vector, &vector).sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+    vector
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+
+#[cfg(all(test, feature = "mock-server"))]
+mod tests{
+    use std::{any::Any, sync::{Arc, atomic::{AtomicU32, Ordering}}};
+    use anyhow::Result;
+    use crate::api::*;
+    use crate::middleware::Middleware;
+    use crate::mock_server::{MockServer, MockEndpoint};
+    use super::*;
+
+    /// 统计中间件收到的聊天响应
+    #[derive(Debug,Default)]
+    struct CountResponses(AtomicU32);
+
+    impl Middleware for CountResponses {
+        fn on_data(&self, data: &mut dyn Any) -> Result<()> {
+            if data.is::<ChatCompletionResponse>() {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(())
+        }
+    }
+
+    fn chat_request(system: &str, question: &str) -> ChatCompletionRequest {
+        ChatCompletionRequestBuilder::default()
+            .messages(vec![ChatMessage::new_system(system, ""), ChatMessage::new_user(question, "")])
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn semantic_cache_should_match_paraphrased_questions() -> Result<()>{
+        let server = MockServer::start().await?;
+        let responses = Arc::new(CountResponses::default());
+        let sdk = server.sdk().with_semantic_cache(SemanticCache::new(0.8)).with_middleware(responses.clone());
+        let chat_requests = || server.requests().iter().filter(|r| r.endpoint == MockEndpoint::ChatCompletions).count();
+
+        let first = sdk.chat_completion(chat_request("You are a FAQ bot.", "How do I reset my password?")).await?;
+        let second = sdk.chat_completion(chat_request("You are a FAQ bot.", "how do I reset my password")).await?;
+        assert_eq!(second.id, first.id);
+        assert_eq!(chat_requests(), 1);
+        // 语义缓存命中的响应同样经过中间件
+        assert_eq!(responses.0.load(Ordering::Relaxed), 2);
+
+        // 问题不同，或者系统提示词不同时不会命中
+        sdk.chat_completion(chat_request("You are a FAQ bot.", "What are your opening hours?")).await?;
+        sdk.chat_completion(chat_request("You are a support agent.", "How do I reset my password?")).await?;
+        assert_eq!(chat_requests(), 3);
+        assert_eq!(sdk.semantic_cache.as_ref().unwrap().len(), 3);
+        Ok(())
+    }
+
+    /// 脱敏用户消息，并且在回复末尾追加说明
+    #[derive(Debug)]
+    struct Redact;
+
+    impl Middleware for Redact {
+        fn on_request(&self, req: &mut dyn Any) -> Result<Option<reqwest::Response>> {
+            if let Some(req) = req.downcast_mut::<ChatCompletionRequest>() {
+                for message in req.messages_mut() {
+                    if let ChatMessage::User(user) = message {
+                        user.content = user.content.replace("4111-1111", "[card]");
+                    }
+                }
+            }
+            Ok(None)
+        }
+
+        fn on_data(&self, data: &mut dyn Any) -> Result<()> {
+            if let Some(res) = data.downcast_mut::<ChatCompletionResponse>() {
+                res.choices[0].message.content.get_or_insert_with(String::new).push_str(" (auto)");
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn semantic_cache_should_run_middleware_once_and_survive_embedding_errors() -> Result<()>{
+        let server = MockServer::start().await?;
+        let sdk = server.sdk().with_semantic_cache(SemanticCache::new(0.8)).with_middleware(Redact);
+        let content = |res: &ChatCompletionResponse| res.choices[0].message.content.clone().unwrap_or_default();
+
+        let first = sdk.chat_completion(chat_request("You are a FAQ bot.", "Why was card 4111-1111 declined?")).await?;
+        let second = sdk.chat_completion(chat_request("You are a FAQ bot.", "why was card 4111-1111 declined")).await?;
+        assert_eq!(content(&second), content(&first));
+        assert!(content(&second).ends_with(" (auto)") && !content(&second).ends_with(" (auto) (auto)"));
+        // 发送给嵌入接口的是中间件处理后的问题
+        let requests = server.requests();
+        assert!(requests.iter().filter(|r| r.endpoint == MockEndpoint::Embeddings).all(|r| !r.body.to_string().contains("4111-1111")));
+
+        // 嵌入接口失败时直接发送聊天请求
+        server.fail_next(MockEndpoint::Embeddings, 500, "embeddings unavailable");
+        sdk.chat_completion(chat_request("You are a FAQ bot.", "What are your opening hours?")).await?;
+        assert_eq!(server.requests().iter().filter(|r| r.endpoint == MockEndpoint::ChatCompletions).count(), 2);
+        assert_eq!(sdk.semantic_cache.as_ref().unwrap().len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+//!
+//! 服务端推送事件(SSE)数据流的解析
+//! 当请求参数 `stream` 为 true 时，API 以 `text/event-stream` 格式逐块返回结果，
+//! 每个事件的 `data` 为一个 Json 数据块，最终以 `data: [DONE]` 结束。
+//!
+
+use std::{pin::Pin, task::{Context, Poll}, marker::PhantomData, time::Duration};
+
+use anyhow::{Result, anyhow};
+use bytes::Bytes;
+use futures::{Stream, StreamExt, stream::BoxStream};
+use serde::de::DeserializeOwned;
+
+use crate::{cost::StreamCharge, rate_limit::Reservation};
+
+/// 流式响应中相邻两个数据块之间的最长间隔，推理模型在返回第一个数据块之前可能需要较长时间
+const STREAM_READ_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// 单个服务端推送事件
+#[derive(Debug,Clone,Default,PartialEq, Eq)]
+pub struct SseEvent{
+    /// 事件名称(`event:` 行)，未指定时为 None
+    pub event: Option<String>,
+    /// 事件数据(`data:` 行)，多行数据以换行符连接
+    pub data: String,
+}
+
+impl SseEvent {
+    /// 解析一个完整的事件块，不包含任何 `data:` 行的块(例如注释)返回 None
+    fn parse(block: &str) -> Option<SseEvent> {
+        let mut event = None;
+        let mut data: Option<String> = None;
+        for line in block.lines() {
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (line, ""),
+            };
+            match field {
+                "event" => event = Some(value.to_string()),
+                "data" => match &mut data {
+                    Some(data) => {
+                        data.push('\n');
+                        data.push_str(value);
+                    }
+                    None => data = Some(value.to_string()),
+                },
+                _ => {}
+            }
+        }
+        data.map(|data| SseEvent { event, data })
+    }
+}
+
+
+/// 反序列化后的事件数据流，每一项为一个数据块
+/// 读取到 `[DONE]` 事件或者连接关闭时结束
+pub struct EventStream<T>{
+    inner: BoxStream<'static, Result<SseEvent>>,
+    done: bool,
+    /// 开启限流时预留的额度，收到用量数据块时修正，流结束前被丢弃时退回预留的输出令牌数
+    reservation: Option<Reservation>,
+    /// 开启成本统计时的计费信息，收到用量数据块时记录实际花费
+    charge: Option<StreamCharge>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> EventStream<T> {
+
+    /// 从原始字节流构建事件数据流
+    pub fn new<S, E>(bytes: S) -> Self
+    where
+        S: Stream<Item = std::result::Result<Bytes, E>> + Send + 'static,
+        E: Into<anyhow::Error> + 'static,
+    {
+        Self { inner: sse_events(bytes).boxed(), done: false, reservation: None, charge: None, _marker: PhantomData }
+    }
+
+    /// 从响应体构建事件数据流，超过 `STREAM_READ_TIMEOUT` 没有收到数据时返回错误
+    pub(crate) fn from_response(mut res: reqwest::Response, charge: Option<StreamCharge>) -> Self {
+        let reservation = res.extensions_mut().remove::<Reservation>();
+        Self { reservation, charge, ..Self::new(read_timeout(res.bytes_stream(), STREAM_READ_TIMEOUT)) }
+    }
+}
+
+/// 限制相邻两个数据块之间的间隔，超时后返回错误并结束
+fn read_timeout<S, E>(bytes: S, timeout: Duration) -> impl Stream<Item = Result<Bytes>> + Send + 'static
+where
+    S: Stream<Item = std::result::Result<Bytes, E>> + Send + 'static,
+    E: Into<anyhow::Error> + 'static,
+{
+    futures::stream::unfold(Some(bytes.boxed()), move |bytes| async move {
+        let mut bytes = bytes?;
+        match tokio::time::timeout(timeout, bytes.next()).await {
+            Ok(Some(chunk)) => Some((chunk.map_err(Into::into), Some(bytes))),
+            Ok(None) => None,
+            Err(_) => Some((Err(anyhow!("no data received from the stream for {timeout:?}")), None)),
+        }
+    })
+}
+
+impl<T: DeserializeOwned> Stream for EventStream<T> {
+    type Item = Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        match self.inner.poll_next_unpin(cx) {
+            Poll::Ready(Some(Ok(event))) => {
+                if event.data == "[DONE]" {
+                    self.done = true;
+                    return Poll::Ready(None);
+                }
+                if self.reservation.is_some() || self.charge.is_some() {
+                    if let Some(usage) = usage(&event.data) {
+                        if let Some(reservation) = self.reservation.take() {
+                            let tokens = usage["total_tokens"].as_u64().unwrap_or_default();
+                            reservation.settle(tokens.min(u32::MAX as u64) as u32);
+                        }
+                        if let Some(charge) = &mut self.charge {
+                            charge.record_usage(&usage);
+                        }
+                    }
+                }
+                Poll::Ready(Some(parse_data(&event.data)))
+            }
+            Poll::Ready(Some(Err(e))) => {
+                self.done = true;
+                Poll::Ready(Some(Err(e)))
+            }
+            Poll::Ready(None) => {
+                self.done = true;
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// 解析事件数据，如果数据块中包含 `error` 对象，则作为错误返回
+fn parse_data<T: DeserializeOwned>(data: &str) -> Result<T> {
+    let value: serde_json::Value = serde_json::from_str(data)?;
+    if let Some(error) = value.get("error").filter(|e| !e.is_null()) {
+        return Err(anyhow!("stream error: {}", error));
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+/// 数据块中的令牌用量: 聊天与补全接口设置 `include_usage` 后的最后一个数据块，或者 Responses 接口的 `response.completed` 事件
+fn usage(data: &str) -> Option<serde_json::Value> {
+    if !data.contains("\"usage\"") {
+        return None;
+    }
+    let mut value: serde_json::Value = serde_json::from_str(data).ok()?;
+    let usage = match value["usage"].is_object() {
+        true => value["usage"].take(),
+        false => value["response"]["usage"].take(),
+    };
+    usage.is_object().then_some(usage)
+}
+
+
+/// 将字节流按照空行切分为一个个事件
+pub fn sse_events<S, E>(bytes: S) -> impl Stream<Item = Result<SseEvent>> + Send
+where
+    S: Stream<Item = std::result::Result<Bytes, E>> + Send + 'static,
+    E: Into<anyhow::Error>,
+{
+    // 以字节缓存数据，避免多字节字符被拆分到两个数据块时解码出错
+    let state = (bytes.boxed(), Vec::<u8>::new(), false);
+    futures::stream::unfold(state, |(mut bytes, mut buffer, mut eof)| async move {
+        loop {
+            // 先从缓冲区中取出完整的事件块
+            if let Some(pos) = buffer.windows(2).position(|w| w == b"\n\n") {
+                let block: Vec<u8> = buffer.drain(..pos + 2).collect();
+                match SseEvent::parse(&String::from_utf8_lossy(&block)) {
+                    Some(event) => return Some((Ok(event), (bytes, buffer, eof))),
+                    None => continue,
+                }
+            }
+            if eof {
+                // 连接关闭时，缓冲区中剩余的数据作为最后一个事件
+                let block = std::mem::take(&mut buffer);
+                let event = SseEvent::parse(&String::from_utf8_lossy(&block))?;
+                return Some((Ok(event), (bytes, buffer, eof)));
+            }
+            match bytes.next().await {
+                // 统一换行符为 \n
+                Some(Ok(chunk)) => buffer.extend(chunk.iter().filter(|b| **b != b'\r')),
+                Some(Err(e)) => return Some((Err(e.into()), (bytes, buffer, true))),
+                None => eof = true,
+            }
+        }
+    })
+}
+
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Chunk {
+        text: String,
+    }
+
+    fn byte_stream(chunks: &[&'static str]) -> impl Stream<Item = Result<Bytes>> + Send + 'static {
+        let chunks: Vec<Result<Bytes>> = chunks.iter().map(|c| Ok(Bytes::from_static(c.as_bytes()))).collect();
+        futures::stream::iter(chunks)
+    }
+
+    #[tokio::test]
+    async fn event_stream_should_parse_split_chunks() -> Result<()>{
+        let bytes = byte_stream(&[
+            ": keep-alive\n\ndata: {\"text\":",
+            "\"Hel\"}\n\ndata: {\"text\":\"lo\"}\r\n\r\n",
+            "data: [DONE]\n\ndata: {\"text\":\"ignored\"}\n\n",
+        ]);
+        let chunks: Vec<Chunk> = EventStream::new(bytes).collect::<Vec<_>>().await.into_iter().collect::<Result<_>>()?;
+        let text: String = chunks.into_iter().map(|c| c.text).collect();
+        assert_eq!(text, "Hello");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn event_stream_should_surface_errors(){
+        let bytes = byte_stream(&["data: {\"error\":{\"message\":\"boom\"}}\n\n"]);
+        let mut stream = EventStream::<Chunk>::new(bytes);
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn event_stream_should_decode_split_multibyte_chars(){
+        // "你" 的 UTF-8 编码被拆分到两个数据块中
+        let raw = "data: {\"text\":\"你\"}\n\n".as_bytes();
+        let chunks: Vec<Result<Bytes>> = vec![Ok(Bytes::copy_from_slice(&raw[..16])), Ok(Bytes::copy_from_slice(&raw[16..]))];
+        let chunk = EventStream::<Chunk>::new(futures::stream::iter(chunks)).next().await.unwrap().unwrap();
+        assert_eq!(chunk.text, "你");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn event_stream_should_time_out_when_idle(){
+        let bytes = byte_stream(&["data: {\"text\":\"a\"}\n\n"]).chain(futures::stream::pending());
+        let mut stream = EventStream::<Chunk>::new(read_timeout(bytes, Duration::from_secs(5)));
+        assert_eq!(stream.next().await.unwrap().unwrap().text, "a");
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert!(err.to_string().contains("no data received"));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn sse_events_should_keep_event_names(){
+        let bytes = byte_stream(&["event: response.created\ndata: {}\n\nevent: done\ndata: a\ndata: b"]);
+        let events: Vec<SseEvent> = sse_events(bytes).map(|e| e.unwrap()).collect().await;
+        assert_eq!(events[0].event.as_deref(), Some("response.created"));
+        assert_eq!(events[1].data, "a\nb");
+    }
+}
@@ -0,0 +1,186 @@
+//!
+//! 请求的 tracing 埋点
+//! 开启 `tracing` 特性后，每个请求都会创建一个 `openai.request` span，记录接口、模型、请求ID、状态码、耗时、
+//! 重试次数以及令牌使用统计;请求体与响应体只有在调用 `OpenaiSdk::with_trace_bodies(true)` 后才会记录。
+//! 请求头(包括认证信息)不会被记录。未开启特性时以下方法均为空操作。
+//!
+
+use std::future::Future;
+
+use reqwest::Request;
+
+use crate::{OpenaiSdk, meta::ResponseMeta};
+
+/// 单个请求的 span，保存在响应的扩展中，读取响应体后继续记录令牌使用统计
+#[derive(Debug,Clone)]
+pub(crate) struct CallSpan{
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "tracing")]
+    record_bodies: bool,
+}
+
+#[cfg(feature = "tracing")]
+impl CallSpan {
+    pub(crate) fn start(sdk: &OpenaiSdk, req: &Request) -> Self {
+        let record_bodies = sdk.trace_bodies;
+        let span = tracing::info_span!(
+            "openai.request",
+            otel.kind = "client",
+            http.method = %req.method(),
+            endpoint = crate::api_path(req.url()),
+            model = crate::request_model(req).as_deref(),
+            request_id = tracing::field::Empty,
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+            retries = tracing::field::Empty,
+            prompt_tokens = tracing::field::Empty,
+            completion_tokens = tracing::field::Empty,
+            total_tokens = tracing::field::Empty,
+            error = tracing::field::Empty,
+            request_body = tracing::field::Empty,
+            response_body = tracing::field::Empty,
+        );
+        if record_bodies {
+            if let Some(body) = req.body().and_then(|b| b.as_bytes()) {
+                span.record("request_body", String::from_utf8_lossy(body).as_ref());
+            }
+        }
+        Self { span, record_bodies }
+    }
+
+    pub(crate) fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+        tracing::Instrument::instrument(future, self.span.clone())
+    }
+
+    pub(crate) fn record_response(&self, meta: &ResponseMeta) {
+        self.span.record("status", meta.status.as_u16());
+        self.span.record("latency_ms", meta.latency.as_millis() as u64);
+        self.span.record("retries", meta.retries);
+        if let Some(request_id) = meta.request_id() {
+            self.span.record("request_id", request_id);
+        }
+    }
+
+    pub(crate) fn record_error(&self, error: &dyn std::fmt::Display) {
+        self.span.record("error", tracing::field::display(error));
+        let _enter = self.span.enter();
+        tracing::warn!(%error, "openai request failed");
+    }
+
+    /// 记录响应体中的令牌使用统计，开启记录时同时记录响应体
+    pub(crate) fn record_body(&self, body: &[u8]) {
+        #[derive(serde::Deserialize)]
+        struct Body{ usage: Option<Usage> }
+        #[derive(serde::Deserialize)]
+        struct Usage{
+            #[serde(alias = "input_tokens")]
+            prompt_tokens: Option<u64>,
+            #[serde(alias = "output_tokens")]
+            completion_tokens: Option<u64>,
+            total_tokens: Option<u64>,
+        }
+        if let Some(usage) = serde_json::from_slice::<Body>(body).ok().and_then(|b| b.usage) {
+            if let Some(tokens) = usage.prompt_tokens {
+                self.span.record("prompt_tokens", tokens);
+            }
+            if let Some(tokens) = usage.completion_tokens {
+                self.span.record("completion_tokens", tokens);
+            }
+            if let Some(tokens) = usage.total_tokens {
+                self.span.record("total_tokens", tokens);
+            }
+        }
+        if self.record_bodies {
+            self.span.record("response_body", String::from_utf8_lossy(body).as_ref());
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl CallSpan {
+    pub(crate) fn start(_sdk: &OpenaiSdk, _req: &Request) -> Self {
+        Self {}
+    }
+
+    pub(crate) fn instrument<F: Future>(&self, future: F) -> F {
+        future
+    }
+
+    pub(crate) fn record_response(&self, _meta: &ResponseMeta) {}
+
+    pub(crate) fn record_error(&self, _error: &dyn std::fmt::Display) {}
+
+    pub(crate) fn record_body(&self, _body: &[u8]) {}
+}
+
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests{
+    use std::{collections::HashMap, sync::{Arc, Mutex}};
+
+    use anyhow::Result;
+    use reqwest::Method;
+    use serde_json::json;
+    use tracing::{Event, Id, Metadata, Subscriber, field::{Field, Visit}, span::{Attributes, Record}};
+    use crate::OpenaiSdk;
+    use crate::api::*;
+    use crate::transport::{MockTransport, MockResponse};
+
+    /// 收集所有 span 字段的订阅者
+    #[derive(Clone,Default)]
+    struct Collector(Arc<Mutex<HashMap<String, String>>>);
+
+    impl Visit for Collector {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.lock().unwrap().insert(field.name().to_string(), format!("{value:?}"));
+        }
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.lock().unwrap().insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    impl Subscriber for Collector {
+        fn enabled(&self, _: &Metadata<'_>) -> bool { true }
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            span.record(&mut self.clone());
+            Id::from_u64(1)
+        }
+        fn record(&self, _: &Id, values: &Record<'_>) {
+            values.record(&mut self.clone());
+        }
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event<'_>) {}
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[tokio::test]
+    async fn requests_should_be_traced() -> Result<()>{
+        let collector = Collector::default();
+        let _guard = tracing::subscriber::set_default(collector.clone());
+        let mock = MockTransport::new().on(Method::POST, "chat/completions", MockResponse::json(json!({
+            "id": "chatcmpl-1", "object": "chat.completion", "created": 1, "model": "gpt-3.5-turbo-1106",
+            "choices": [{"index": 0, "message": {"role": "assistant", "content": "hi"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 10, "completion_tokens": 2, "total_tokens": 12}
+        })).header("x-request-id", "req_1"));
+        let sdk = OpenaiSdk::new("sk-secret".into()).with_transport(mock);
+        let req = ChatCompletionRequestBuilder::default().messages(vec![ChatMessage::new_user("hello", "")]).build()?;
+        sdk.chat_completion(req.clone()).await?;
+
+        let fields = collector.0.lock().unwrap().clone();
+        assert_eq!(fields["endpoint"], "chat/completions");
+        assert_eq!(fields["model"], "gpt-3.5-turbo-1106");
+        assert_eq!(fields["request_id"], "req_1");
+        assert_eq!(fields["status"], "200");
+        assert_eq!(fields["total_tokens"], "12");
+        assert!(!fields.contains_key("request_body"));
+        assert!(fields.values().all(|v| !v.contains("sk-secret")));
+
+        sdk.with_trace_bodies(true).chat_completion(req).await?;
+        let fields = collector.0.lock().unwrap().clone();
+        assert!(fields["request_body"].contains("hello"));
+        assert!(fields["response_body"].contains("chatcmpl-1"));
+        Ok(())
+    }
+}
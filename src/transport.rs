@@ -0,0 +1,293 @@
+//!
+//! 网络传输层
+//! `OpenaiSdk` 构建好的 `reqwest::Request` 最终交给 `Transport` 发送，默认使用 reqwest 客户端;
+//! 可以替换为自定义的 HTTP 实现，或者在测试中使用内存中的 `MockTransport` 返回预设的响应。
+//!
+
+use std::{fmt::Debug, sync::{Arc, Mutex}, time::Duration};
+
+use anyhow::{Result, anyhow};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use reqwest::{Client, Method, Request, Response, StatusCode};
+
+use crate::api_path;
+
+///
+/// 传输特征: 发送一个请求并返回响应
+///
+pub trait Transport: Debug + Send + Sync {
+    fn execute(&self, req: Request) -> BoxFuture<'_, Result<Response>>;
+}
+
+
+///
+/// 基于 reqwest 客户端的传输实现
+///
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestTransport{
+    client: Client,
+}
+
+impl ReqwestTransport {
+    /// 使用自定义的 reqwest 客户端，例如设置了代理或者连接池参数的客户端
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn execute(&self, req: Request) -> BoxFuture<'_, Result<Response>> {
+        Box::pin(async move { Ok(self.client.execute(req).await?) })
+    }
+}
+
+
+///
+/// 内存中的模拟传输，按照请求方法和接口路径匹配预设的响应
+/// 克隆后共享同一组路由和请求记录
+///
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport{
+    routes: Arc<Mutex<Vec<MockRoute>>>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+#[derive(Debug)]
+struct MockRoute{
+    method: Method,
+    path: String,
+    response: MockResponse,
+    /// 剩余可匹配的次数，None 表示不限次数
+    remaining: Option<usize>,
+}
+
+/// 模拟传输收到的请求
+#[derive(Debug, Clone)]
+pub struct RecordedRequest{
+    /// 请求方法
+    pub method: Method,
+    /// 接口路径，例如 `chat/completions`
+    pub path: String,
+    /// 请求头
+    pub headers: reqwest::header::HeaderMap,
+    /// 请求体，multipart 等流式请求体为空
+    pub body: Bytes,
+}
+
+impl RecordedRequest {
+    /// 将请求体解析为 Json
+    pub fn json(&self) -> Result<serde_json::Value> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// 注册一个响应，`path` 为 `/v1/` 之后的接口路径，例如 `chat/completions`;
+    /// 该路由可以被匹配任意次
+    ///
+    pub fn on(self, method: Method, path: impl Into<String>, response: MockResponse) -> Self {
+        self.push(method, path.into(), response, None)
+    }
+
+    ///
+    /// 注册一个只匹配一次的响应，多次注册同一路径时按注册顺序依次返回
+    ///
+    pub fn once(self, method: Method, path: impl Into<String>, response: MockResponse) -> Self {
+        self.push(method, path.into(), response, Some(1))
+    }
+
+    /// 已收到的所有请求
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn push(self, method: Method, path: String, response: MockResponse, remaining: Option<usize>) -> Self {
+        let path = path.trim_matches('/').to_string();
+        self.routes.lock().unwrap().push(MockRoute { method, path, response, remaining });
+        self
+    }
+
+    /// 查找第一个路径完全相同且未用完的路由
+    fn respond(&self, method: &Method, path: &str) -> Option<MockResponse> {
+        let path = azure_api_path(path);
+        let mut routes = self.routes.lock().unwrap();
+        let route = routes.iter_mut().find(|r| {
+            r.method == method && r.remaining != Some(0) && r.path == path
+        })?;
+        if let Some(remaining) = &mut route.remaining {
+            *remaining -= 1;
+        }
+        Some(route.response.clone())
+    }
+}
+
+/// 去掉 Azure 接口路径的 `openai/deployments/{deployment}/` 或 `openai/` 前缀，与 OpenAI 的路由使用相同的路径
+fn azure_api_path(path: &str) -> &str {
+    let Some(rest) = path.strip_prefix("openai/") else {
+        return path;
+    };
+    rest.strip_prefix("deployments/")
+        .and_then(|deployment| deployment.split_once('/'))
+        .map_or(rest, |(_, rest)| rest)
+}
+
+impl Transport for MockTransport {
+    fn execute(&self, req: Request) -> BoxFuture<'_, Result<Response>> {
+        Box::pin(async move {
+            let path = api_path(req.url()).to_string();
+            let body = req.body().and_then(|b| b.as_bytes()).map(Bytes::copy_from_slice).unwrap_or_default();
+            self.requests.lock().unwrap().push(RecordedRequest {
+                method: req.method().clone(),
+                path: path.clone(),
+                headers: req.headers().clone(),
+                body,
+            });
+            let response = self
+                .respond(req.method(), &path)
+                .ok_or_else(|| anyhow!("no mock response for {} {}", req.method(), path))?;
+            response.into_response().await
+        })
+    }
+}
+
+
+///
+/// 预设的模拟响应
+///
+#[derive(Debug, Clone)]
+pub struct MockResponse{
+    status: StatusCode,
+    headers: Vec<(String, String)>,
+    /// 响应体按块发送，流式响应每个事件为一块
+    chunks: Vec<Bytes>,
+    delay: Option<Duration>,
+}
+
+impl MockResponse {
+    /// 状态码为 200 的 Json 响应
+    pub fn json(body: serde_json::Value) -> Self {
+        Self::bytes(body.to_string()).header("content-type", "application/json")
+    }
+
+    /// 状态码为 200 的原始字节响应
+    pub fn bytes(body: impl Into<Bytes>) -> Self {
+        Self { status: StatusCode::OK, headers: Vec::new(), chunks: vec![body.into()], delay: None }
+    }
+
+    /// OpenAI 格式的错误响应
+    pub fn error(status: StatusCode, message: &str) -> Self {
+        let body = serde_json::json!({"error": {"message": message, "type": "invalid_request_error", "param": null, "code": null}});
+        Self::json(body).status(status)
+    }
+
+    /// 服务端推送事件(SSE)流式响应，每个数据块为一个事件，最后追加 `[DONE]` 事件
+    pub fn sse(events: impl IntoIterator<Item = serde_json::Value>) -> Self {
+        let mut chunks: Vec<Bytes> = events.into_iter().map(|e| format!("data: {e}\n\n").into()).collect();
+        chunks.push(Bytes::from_static(b"data: [DONE]\n\n"));
+        Self { status: StatusCode::OK, headers: vec![("content-type".into(), "text/event-stream".into())], chunks, delay: None }
+    }
+
+    /// 设置状态码
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// 添加响应头
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// 返回响应之前等待指定时间，用于模拟网络延迟
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// 构建为 `reqwest::Response`，多块响应体以流的方式返回
+    async fn into_response(self) -> Result<Response> {
+        if let Some(delay) = self.delay {
+            tokio::time::sleep(delay).await;
+        }
+        let mut builder = http::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        let body = match <[Bytes; 1]>::try_from(self.chunks) {
+            std::result::Result::Ok([chunk]) => reqwest::Body::from(chunk),
+            Err(chunks) => reqwest::Body::wrap_stream(futures::stream::iter(
+                chunks.into_iter().map(std::result::Result::<_, std::io::Error>::Ok),
+            )),
+        };
+        Ok(Response::from(builder.body(body)?))
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use futures::StreamExt;
+    use serde_json::json;
+    use crate::OpenaiSdk;
+    use crate::api::{CompletionRequest, CreateImageRequest};
+    use crate::error::ApiError;
+    use super::*;
+
+    #[tokio::test]
+    async fn mock_transport_should_match_routes_and_record_requests() -> Result<()>{
+        let mock = MockTransport::new()
+            .once(Method::POST, "images/generations", MockResponse::error(StatusCode::TOO_MANY_REQUESTS, "slow down"))
+            .on(Method::POST, "images/generations", MockResponse::json(json!({
+                "created": 1700000000,
+                "data": [{"url": "https://example.com/cat.png"}]
+            })));
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock.clone());
+
+        let err = sdk.create_image(CreateImageRequest::new("a cat")).await.unwrap_err();
+        assert!(err.downcast_ref::<ApiError>().unwrap().is_rate_limited());
+        let res = sdk.create_image(CreateImageRequest::new("a cat")).await?;
+        assert_eq!(res.data[0].url.as_deref(), Some("https://example.com/cat.png"));
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].path, "images/generations");
+        assert_eq!(requests[0].json()?["prompt"], "a cat");
+        assert_eq!(requests[0].headers[reqwest::header::AUTHORIZATION], "Bearer sk-test");
+
+        assert!(sdk.list_files().await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mock_transport_should_stream_events() -> Result<()>{
+        let chunk = |text: &str| json!({
+            "id": "cmpl-1", "object": "text_completion", "created": 1, "model": "gpt-3.5-turbo-instruct",
+            "choices": [{"text": text, "index": 0, "finish_reason": null}]
+        });
+        let mock = MockTransport::new().on(Method::POST, "completions", MockResponse::sse([chunk("Hello"), chunk(" world")]));
+        let sdk = OpenaiSdk::new("sk-test".into()).with_transport(mock);
+        let text: Vec<String> = sdk.completion_stream(CompletionRequest::new("Say hello")).await?
+            .map(|c| c.unwrap().choices[0].text.clone())
+            .collect()
+            .await;
+        assert_eq!(text.concat(), "Hello world");
+        Ok(())
+    }
+
+    #[test]
+    fn mock_transport_should_match_exact_paths(){
+        let mock = MockTransport::new().on(Method::POST, "completions", MockResponse::json(json!({})));
+        assert!(mock.respond(&Method::POST, "chat/completions").is_none());
+        assert!(mock.respond(&Method::POST, "completions").is_some());
+        // Azure 的部署路径去掉前缀后匹配
+        assert!(mock.respond(&Method::POST, "openai/deployments/instruct-prod/completions").is_some());
+        assert!(mock.respond(&Method::POST, "openai/deployments/gpt4-prod/chat/completions").is_none());
+    }
+}